@@ -9,18 +9,34 @@ use winit::{
     window::{WindowAttributes, WindowId},
 };
 
+use fluid_simulation::checkpoint::Checkpoint;
+use fluid_simulation::scene::Scene;
+
+use super::cli::RunArgs;
 use super::state;
 
 const WINDOWS_INNER_SIZE: LogicalSize<u32> = LogicalSize::new(800, 600);
 
-#[derive(Default)]
 struct App {
     state: Option<state::State>,
+    args: RunArgs,
+    scene: Scene,
+    resume: Option<Checkpoint>,
 }
 
 impl App {
-    fn new(_: &EventLoop<state::State>) -> Self {
-        Self { state: None }
+    fn new(
+        _: &EventLoop<state::State>,
+        args: RunArgs,
+        scene: Scene,
+        resume: Option<Checkpoint>,
+    ) -> Self {
+        Self {
+            state: None,
+            args,
+            scene,
+            resume,
+        }
     }
 }
 
@@ -33,7 +49,15 @@ impl ApplicationHandler<state::State> for App {
 
         let window = Arc::new(event_loop.create_window(attrs).unwrap());
 
-        self.state = Some(pollster::block_on(state::State::new(window)).unwrap());
+        self.state = Some(
+            pollster::block_on(state::State::new(
+                window,
+                &self.args,
+                &self.scene,
+                self.resume.as_ref(),
+            ))
+            .unwrap(),
+        );
     }
 
     #[allow(unused_mut)]
@@ -50,7 +74,7 @@ impl ApplicationHandler<state::State> for App {
         match event {
             WindowEvent::CloseRequested => {
                 println!("The close button was pressed; stopping");
-                event_loop.exit();
+                state.exit(event_loop);
             }
 
             WindowEvent::Resized(size) => {
@@ -81,21 +105,37 @@ impl ApplicationHandler<state::State> for App {
                 ..
             } => state.handle_key(event_loop, code, key_state.is_pressed()),
 
-            WindowEvent::CursorMoved {
-                position,
-                device_id,
-            } => state.handle_mouse_moved(position),
+            WindowEvent::CursorMoved { position, .. } => state.handle_mouse_moved(position),
+
+            WindowEvent::CursorLeft { .. } => state.handle_cursor_left(),
+
+            WindowEvent::MouseInput {
+                state: button_state,
+                button,
+                ..
+            } => state.handle_mouse_input(button, button_state.is_pressed()),
+
+            WindowEvent::MouseWheel { delta, .. } => state.handle_mouse_wheel(delta),
 
             _ => (),
         }
     }
 }
 
-pub fn run() -> anyhow::Result<()> {
-    env_logger::init();
+pub fn run(args: RunArgs) -> anyhow::Result<()> {
+    if !(args.interaction_radius.is_finite() && args.interaction_radius > 0.0) {
+        anyhow::bail!("`--interaction-radius` must be a positive number");
+    }
+
+    if !(args.interaction_strength.is_finite() && args.interaction_strength >= 0.0) {
+        anyhow::bail!("`--interaction-strength` must be a non-negative number");
+    }
+
+    let scene = args.scene.load_scene()?;
+    let resume = args.resume.as_deref().map(Checkpoint::load).transpose()?;
 
     let event_loop = EventLoop::with_user_event().build()?;
-    let mut app = App::new(&event_loop);
+    let mut app = App::new(&event_loop, args, scene, resume);
     event_loop.run_app(&mut app)?;
 
     Ok(())
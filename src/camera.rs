@@ -0,0 +1,107 @@
+use crate::pipelines::render::ViewTransform;
+use crate::simulation::SimulationParams;
+
+/// Orthographic 2D camera looking at the simulation domain.
+///
+/// At zoom 1 the whole domain fits into the render target with its aspect ratio
+/// preserved; larger zoom values magnify around `center`. All positions passed in are
+/// clip-space coordinates of the render target, so they do not depend on its size.
+#[derive(Copy, Clone, Debug)]
+pub struct Camera {
+    center: [f32; 2],
+    zoom: f32,
+    aspect_ratio: f32,
+    domain_center: [f32; 2],
+    domain_size: [f32; 2],
+    particle_size: f32,
+}
+
+impl Camera {
+    pub const MIN_ZOOM: f32 = 0.1;
+    pub const MAX_ZOOM: f32 = 100.0;
+
+    /// Frames the domain of `simulation_params` in a target of `width` x `height` pixels.
+    pub fn new(simulation_params: &SimulationParams, width: u32, height: u32) -> Self {
+        let mut camera = Self {
+            center: [0.0; 2],
+            zoom: 1.0,
+            aspect_ratio: 1.0,
+            domain_center: [0.0; 2],
+            domain_size: [1.0; 2],
+            particle_size: 0.0,
+        };
+        camera.set_domain(simulation_params);
+        camera.resize(width, height);
+        camera
+    }
+
+    /// Frames the domain of `simulation_params` again, dropping any pan and zoom.
+    pub fn set_domain(&mut self, simulation_params: &SimulationParams) {
+        let (min, max) = simulation_params.domain_bounds();
+
+        self.domain_center = [0.5 * (min[0] + max[0]), 0.5 * (min[1] + max[1])];
+        self.domain_size = simulation_params.domain_size();
+        self.particle_size = simulation_params.smoothing_radius() / 3.0;
+        self.fit();
+    }
+
+    /// Centres the domain and resets the zoom to 1.
+    pub fn fit(&mut self) {
+        self.center = self.domain_center;
+        self.zoom = 1.0;
+    }
+
+    /// Keeps the projection undistorted for a target of `width` x `height` pixels.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.aspect_ratio = width as f32 / height as f32;
+        }
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// Multiplies the zoom by `factor`, keeping the world point under `clip` in place.
+    pub fn zoom_at(&mut self, factor: f32, clip: [f32; 2]) {
+        let anchor = self.clip_to_world(clip);
+
+        self.zoom = (self.zoom * factor).clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+
+        let moved = self.clip_to_world(clip);
+        self.center[0] += anchor[0] - moved[0];
+        self.center[1] += anchor[1] - moved[1];
+    }
+
+    /// Moves the view so that content follows a cursor moved by `clip_delta`.
+    pub fn pan(&mut self, clip_delta: [f32; 2]) {
+        let scale = self.scale();
+
+        self.center[0] -= clip_delta[0] / scale[0];
+        self.center[1] -= clip_delta[1] / scale[1];
+    }
+
+    pub fn clip_to_world(&self, clip: [f32; 2]) -> [f32; 2] {
+        self.view_transform().clip_to_world(clip)
+    }
+
+    pub fn view_transform(&self) -> ViewTransform {
+        let scale = self.scale();
+
+        ViewTransform::new(
+            scale,
+            [-self.center[0] * scale[0], -self.center[1] * scale[1]],
+            self.particle_size,
+        )
+    }
+
+    /// Clip-space units per world unit on each axis.
+    fn scale(&self) -> [f32; 2] {
+        // Half the visible height that fits the whole domain at zoom 1.
+        let half_height =
+            (0.5 * self.domain_size[1]).max(0.5 * self.domain_size[0] / self.aspect_ratio);
+        let scale_y = self.zoom / half_height;
+
+        [scale_y / self.aspect_ratio, scale_y]
+    }
+}
@@ -0,0 +1,433 @@
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::obstacle::{GpuObstacle, Obstacle};
+use crate::rigid_body::RigidBody;
+use crate::scene::validate_rigid_body;
+use crate::simulation::{Particle, SimulationParams};
+
+const MAGIC: [u8; 8] = *b"FSIMCKPT";
+
+/// Snapshot of the full solver state that can be written to disk and resumed later.
+///
+/// The file starts with a magic tag and [`Checkpoint::VERSION`], followed by the step
+/// counter, the simulated time, the raw `SimulationParams` uniform, the particle fields,
+/// the packed obstacles and the rigid bodies, all stored little-endian. Files written
+/// with a different version or uniform layout are rejected instead of being
+/// reinterpreted.
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    pub step: u64,
+    pub time: f64,
+    pub simulation_params: SimulationParams,
+    pub particles: Vec<Particle>,
+    pub densities: Vec<f32>,
+    pub pressures: Vec<f32>,
+    pub obstacles: Vec<Obstacle>,
+    pub rigid_bodies: Vec<RigidBody>,
+}
+
+impl Checkpoint {
+    pub const VERSION: u32 = 1;
+
+    /// Writes the checkpoint next to `path` first and renames it into place, so an
+    /// interrupted save never clobbers the previous checkpoint.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let temp_path = path.with_extension("tmp");
+
+        let file = File::create(&temp_path)
+            .with_context(|| format!("Failed to create checkpoint {}", temp_path.display()))?;
+        let mut writer = BufWriter::new(file);
+
+        self.write(&mut writer)
+            .and_then(|_| writer.flush().map_err(Into::into))
+            .with_context(|| format!("Failed to write checkpoint {}", temp_path.display()))?;
+        drop(writer);
+
+        std::fs::rename(&temp_path, path)
+            .with_context(|| format!("Failed to move checkpoint to {}", path.display()))
+    }
+
+    /// Reads the whole file up front, so every length in it can be checked against the
+    /// bytes that are actually left before anything is allocated for it.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to open checkpoint {}", path.display()))?;
+
+        Self::read(&mut bytes.as_slice())
+            .with_context(|| format!("Failed to read checkpoint {}", path.display()))
+    }
+
+    pub fn particles_len(&self) -> u32 {
+        self.simulation_params.particles_len
+    }
+
+    fn write(&self, writer: &mut impl Write) -> anyhow::Result<()> {
+        let params: &[u32] = bytemuck::cast_slice(bytemuck::bytes_of(&self.simulation_params));
+
+        writer.write_all(&MAGIC)?;
+        write_u32(writer, Self::VERSION)?;
+        write_u32(writer, std::mem::size_of::<SimulationParams>() as u32)?;
+        write_u32(writer, self.particles.len() as u32)?;
+        writer.write_all(&self.step.to_le_bytes())?;
+        writer.write_all(&self.time.to_le_bytes())?;
+
+        for word in params {
+            write_u32(writer, *word)?;
+        }
+
+        let fields: [fn(&Particle) -> f32; 4] = [
+            |p| p.position_x,
+            |p| p.position_y,
+            |p| p.velocity_x,
+            |p| p.velocity_y,
+        ];
+        for field in fields {
+            for particle in &self.particles {
+                write_u32(writer, field(particle).to_bits())?;
+            }
+        }
+
+        for value in self.densities.iter().chain(&self.pressures) {
+            write_u32(writer, value.to_bits())?;
+        }
+
+        write_obstacles(writer, &self.obstacles)?;
+
+        // Body outlines are stored like obstacles, followed by the per-body state.
+        let shapes: Vec<Obstacle> = self
+            .rigid_bodies
+            .iter()
+            .map(RigidBody::local_shape)
+            .collect();
+        write_obstacles(writer, &shapes)?;
+        for rigid_body in &self.rigid_bodies {
+            for value in [
+                rigid_body.density,
+                rigid_body.position[0],
+                rigid_body.position[1],
+                rigid_body.angle,
+                rigid_body.velocity[0],
+                rigid_body.velocity[1],
+                rigid_body.angular_velocity,
+            ] {
+                write_u32(writer, value.to_bits())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read(reader: &mut &[u8]) -> anyhow::Result<Self> {
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            anyhow::bail!("Not a fluid simulation checkpoint");
+        }
+
+        let version = read_u32(reader)?;
+        if version != Self::VERSION {
+            anyhow::bail!(
+                "Unsupported checkpoint version {version}, expected {}",
+                Self::VERSION
+            );
+        }
+
+        let params_size = read_u32(reader)? as usize;
+        if params_size != std::mem::size_of::<SimulationParams>() {
+            anyhow::bail!(
+                "Checkpoint simulation params are {params_size} bytes, expected {}",
+                std::mem::size_of::<SimulationParams>()
+            );
+        }
+
+        let particles_len = read_u32(reader)? as usize;
+
+        let mut step = [0; 8];
+        reader.read_exact(&mut step)?;
+        let step = u64::from_le_bytes(step);
+
+        let mut time = [0; 8];
+        reader.read_exact(&mut time)?;
+        let time = f64::from_le_bytes(time);
+
+        let mut simulation_params: SimulationParams = bytemuck::Zeroable::zeroed();
+        for word in
+            bytemuck::cast_slice_mut::<_, u32>(bytemuck::bytes_of_mut(&mut simulation_params))
+        {
+            *word = read_u32(reader)?;
+        }
+
+        if simulation_params.particles_len as usize != particles_len {
+            anyhow::bail!(
+                "Checkpoint holds {particles_len} particles but its params expect {}",
+                simulation_params.particles_len
+            );
+        }
+        simulation_params.validate()?;
+        // Positions, velocities, densities and pressures.
+        ensure_remaining(reader, 6 * particles_len)?;
+
+        let position_x = read_f32s(reader, particles_len)?;
+        let position_y = read_f32s(reader, particles_len)?;
+        let velocity_x = read_f32s(reader, particles_len)?;
+        let velocity_y = read_f32s(reader, particles_len)?;
+        let densities = read_f32s(reader, particles_len)?;
+        let pressures = read_f32s(reader, particles_len)?;
+
+        let obstacles = read_obstacles(reader)?;
+        if obstacles.len() != simulation_params.obstacles_len() as usize {
+            anyhow::bail!(
+                "Checkpoint holds {} obstacles but its params expect {}",
+                obstacles.len(),
+                simulation_params.obstacles_len()
+            );
+        }
+
+        let shapes = read_obstacles(reader)?;
+        if shapes.len() != simulation_params.rigid_bodies_len() as usize {
+            anyhow::bail!(
+                "Checkpoint holds {} rigid bodies but its params expect {}",
+                shapes.len(),
+                simulation_params.rigid_bodies_len()
+            );
+        }
+
+        let rigid_bodies = shapes
+            .into_iter()
+            .enumerate()
+            .map(|(i, shape)| {
+                let state = read_f32s(reader, 7)?;
+                let rigid_body = RigidBody {
+                    shape: RigidBody::shape_from_local(shape)?,
+                    density: state[0],
+                    position: [state[1], state[2]],
+                    angle: state[3],
+                    velocity: [state[4], state[5]],
+                    angular_velocity: state[6],
+                };
+                validate_rigid_body(
+                    &format!("rigid_bodies[{i}]"),
+                    &rigid_body,
+                    simulation_params.domain_size(),
+                )?;
+
+                Ok(rigid_body)
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        let particles = (0..particles_len)
+            .map(|i| {
+                Particle::new(
+                    [position_x[i], position_y[i]],
+                    [velocity_x[i], velocity_y[i]],
+                )
+            })
+            .collect();
+
+        Ok(Self {
+            step,
+            time,
+            simulation_params,
+            particles,
+            densities,
+            pressures,
+            obstacles,
+            rigid_bodies,
+        })
+    }
+}
+
+fn write_obstacles(writer: &mut impl Write, obstacles: &[Obstacle]) -> anyhow::Result<()> {
+    let (records, vertices) = Obstacle::pack(obstacles);
+    write_u32(writer, records.len() as u32)?;
+    write_u32(writer, vertices.len() as u32)?;
+    for word in bytemuck::cast_slice::<_, u32>(&records)
+        .iter()
+        .chain(bytemuck::cast_slice(&vertices))
+    {
+        write_u32(writer, *word)?;
+    }
+
+    Ok(())
+}
+
+fn read_obstacles(reader: &mut &[u8]) -> anyhow::Result<Vec<Obstacle>> {
+    let records_len = read_u32(reader)? as usize;
+    let vertices_len = read_u32(reader)? as usize;
+    ensure_remaining(
+        reader,
+        records_len * (std::mem::size_of::<GpuObstacle>() / 4) + 2 * vertices_len,
+    )?;
+
+    let mut records = vec![GpuObstacle::default(); records_len];
+    for word in bytemuck::cast_slice_mut::<_, u32>(&mut records) {
+        *word = read_u32(reader)?;
+    }
+    let vertices = read_f32s(reader, 2 * vertices_len)?;
+
+    Obstacle::unpack(&records, bytemuck::cast_slice(&vertices))
+}
+
+/// Fails unless `reader` still holds `words` 32-bit words.
+fn ensure_remaining(reader: &[u8], words: usize) -> anyhow::Result<()> {
+    if words.saturating_mul(4) > reader.len() {
+        anyhow::bail!(
+            "Checkpoint is truncated: expected {words} more words, {} bytes are left",
+            reader.len()
+        );
+    }
+
+    Ok(())
+}
+
+fn write_u32(writer: &mut impl Write, value: u32) -> std::io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f32s(reader: &mut impl Read, len: usize) -> std::io::Result<Vec<f32>> {
+    (0..len)
+        .map(|_| read_u32(reader).map(f32::from_bits))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rigid_body::BodyShape;
+
+    fn checkpoint(smoothing_radius: f32) -> Checkpoint {
+        let mut simulation_params = SimulationParams::new(
+            1.0 / 60.0,
+            1.0,
+            1000.0,
+            1.0,
+            smoothing_radius,
+            0.5,
+            0.1,
+            [0.0, -9.81],
+            3,
+            2,
+        );
+        simulation_params.set_colliders_len(1, 1);
+
+        Checkpoint {
+            step: 42,
+            time: 0.7,
+            simulation_params,
+            particles: vec![
+                Particle::new([0.0, 0.1], [1.0, -1.0]),
+                Particle::new([0.2, 0.3], [0.0, 0.5]),
+                Particle::new([-0.4, 0.5], [-2.0, 0.0]),
+            ],
+            densities: vec![1000.0, 1010.0, 990.0],
+            pressures: vec![0.0, 10.0, 20.0],
+            obstacles: vec![Obstacle::Circle {
+                center: [0.5, -0.5],
+                radius: 0.1,
+            }],
+            rigid_bodies: vec![RigidBody {
+                shape: BodyShape::Box {
+                    half_size: [0.05, 0.02],
+                },
+                density: 500.0,
+                position: [0.0, -0.3],
+                angle: 0.3,
+                velocity: [0.1, 0.0],
+                angular_velocity: 1.0,
+            }],
+        }
+    }
+
+    fn to_bytes(checkpoint: &Checkpoint) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        checkpoint.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn read_error(bytes: &[u8]) -> String {
+        format!("{:#}", Checkpoint::read(&mut &bytes[..]).unwrap_err())
+    }
+
+    #[test]
+    fn round_trips() {
+        let checkpoint = checkpoint(0.1);
+        let bytes = to_bytes(&checkpoint);
+        let mut reader = bytes.as_slice();
+        let read = Checkpoint::read(&mut reader).unwrap();
+
+        assert!(reader.is_empty());
+        assert_eq!(read.step, checkpoint.step);
+        assert_eq!(read.time, checkpoint.time);
+        assert_eq!(
+            bytemuck::bytes_of(&read.simulation_params),
+            bytemuck::bytes_of(&checkpoint.simulation_params)
+        );
+        assert_eq!(read.particles, checkpoint.particles);
+        assert_eq!(read.densities, checkpoint.densities);
+        assert_eq!(read.pressures, checkpoint.pressures);
+        assert_eq!(read.obstacles, checkpoint.obstacles);
+        assert_eq!(read.rigid_bodies, checkpoint.rigid_bodies);
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut bytes = to_bytes(&checkpoint(0.1));
+        bytes[0] = b'X';
+
+        assert!(read_error(&bytes).contains("Not a fluid simulation checkpoint"));
+    }
+
+    #[test]
+    fn rejects_unsupported_version() {
+        let mut bytes = to_bytes(&checkpoint(0.1));
+        bytes[MAGIC.len()..MAGIC.len() + 4]
+            .copy_from_slice(&(Checkpoint::VERSION + 1).to_le_bytes());
+
+        assert!(read_error(&bytes).contains("Unsupported checkpoint version 2"));
+    }
+
+    /// Every prefix ends inside one of the sections, from the header through the
+    /// params, particle fields and obstacles to the rigid body state.
+    #[test]
+    fn rejects_truncation() {
+        let bytes = to_bytes(&checkpoint(0.1));
+
+        for len in 0..bytes.len() {
+            assert!(
+                Checkpoint::read(&mut &bytes[..len]).is_err(),
+                "read a checkpoint cut off after {len} of {} bytes",
+                bytes.len()
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_params() {
+        let bytes = to_bytes(&checkpoint(-0.1));
+
+        assert!(read_error(&bytes).contains("smoothing radius must be positive"));
+    }
+
+    #[test]
+    fn rejects_invalid_rigid_bodies() {
+        let mut checkpoint = checkpoint(0.1);
+        checkpoint.rigid_bodies[0].density = 0.0;
+
+        assert!(read_error(&to_bytes(&checkpoint)).contains("rigid_bodies[0].density"));
+
+        checkpoint.rigid_bodies[0].density = 500.0;
+        checkpoint.rigid_bodies[0].shape = BodyShape::Disc { radius: f32::NAN };
+
+        assert!(read_error(&to_bytes(&checkpoint)).contains("rigid_bodies[0].shape.radius"));
+    }
+}
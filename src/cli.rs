@@ -0,0 +1,419 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::{Args, FromArgMatches, Parser, Subcommand, ValueEnum};
+
+use fluid_simulation::export::{self, FrameExporter};
+use fluid_simulation::playback::Playback;
+use fluid_simulation::scene::Scene;
+use fluid_simulation::simulation;
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "GPU smoothed-particle hydrodynamics fluid simulation"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run a scene in a window
+    Run(RunArgs),
+    /// Step a scene without a window or surface
+    Headless(HeadlessArgs),
+    /// List the GPU adapters available for the selected backend
+    ListAdapters {
+        /// Graphics backend to enumerate
+        #[arg(long, value_enum, default_value_t = Backend::All)]
+        backend: Backend,
+    },
+}
+
+/// Ids of the [`SceneArgs`] that describe the scene. A checkpoint carries its own
+/// particles and parameters, so none of them can be combined with `--resume`.
+const SCENE_OVERRIDES: [&str; 11] = [
+    "scene",
+    "particles",
+    "substeps",
+    "adaptive_time_step",
+    "boundary_particles",
+    "integrator",
+    "equation_of_state",
+    "speed_of_sound",
+    "clamp_negative_pressure",
+    "pressure_solver",
+    "seed",
+];
+
+#[derive(Args)]
+pub struct SceneArgs {
+    /// Scene file in TOML format; the built-in scene is used when omitted
+    pub scene: Option<PathBuf>,
+
+    /// Override `simulation.particle_count` from the scene
+    #[arg(long)]
+    pub particles: Option<u32>,
+
+    /// Override `simulation.substeps` from the scene
+    #[arg(long)]
+    pub substeps: Option<u32>,
+
+    /// Pick each solver step from the CFL, force and viscous limits, overriding
+    /// `simulation.adaptive_time_step` from the scene
+    #[arg(long)]
+    pub adaptive_time_step: bool,
+
+    /// Sample walls and obstacles with boundary particles, overriding
+    /// `simulation.boundary_particles` from the scene
+    #[arg(long)]
+    pub boundary_particles: bool,
+
+    /// Override `simulation.integrator` from the scene
+    #[arg(long, value_enum)]
+    pub integrator: Option<Integrator>,
+
+    /// Override `simulation.equation_of_state` from the scene
+    #[arg(long, value_enum)]
+    pub equation_of_state: Option<EquationOfState>,
+
+    /// Override `simulation.speed_of_sound` from the scene
+    #[arg(long)]
+    pub speed_of_sound: Option<f32>,
+
+    /// Clamp negative pressures to zero, overriding
+    /// `simulation.clamp_negative_pressure` from the scene
+    #[arg(long)]
+    pub clamp_negative_pressure: bool,
+
+    /// Override `simulation.pressure_solver` from the scene
+    #[arg(long, value_enum)]
+    pub pressure_solver: Option<PressureSolver>,
+
+    /// Seed for the initial velocity jitter
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Graphics backend used to pick the adapter
+    #[arg(long, value_enum, default_value_t = Backend::All)]
+    pub backend: Backend,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Directory that receives exported particle frames
+    #[arg(long)]
+    pub export: Option<PathBuf>,
+
+    /// File format of exported frames
+    #[arg(long, value_enum, default_value_t = ExportFormat::Vtu)]
+    pub export_format: ExportFormat,
+
+    /// Export every N-th simulation step
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub export_stride: u32,
+}
+
+#[derive(Args, Clone)]
+pub struct RecordArgs {
+    /// Resolution of recorded PNG frames as WIDTHxHEIGHT, independent of the window
+    #[arg(long, value_parser = parse_size, default_value = "1920x1080")]
+    pub record_size: (u32, u32),
+
+    /// Record every N-th simulation step
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub record_stride: u32,
+}
+
+#[derive(Args)]
+pub struct RunArgs {
+    #[command(flatten)]
+    pub scene: SceneArgs,
+
+    #[command(flatten)]
+    pub export: ExportArgs,
+
+    #[command(flatten)]
+    pub record: RecordArgs,
+
+    /// Directory that receives PNG frames while recording is toggled with R
+    #[arg(long, default_value = "recording")]
+    pub record_dir: PathBuf,
+
+    /// Most simulation steps run per rendered frame before falling behind real time,
+    /// multiplied by the time scale when running faster than real time
+    #[arg(long, default_value_t = Playback::DEFAULT_MAX_STEPS_PER_FRAME, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_steps_per_frame: u32,
+
+    /// Radius around the cursor, in simulation units, affected by left/right drag
+    #[arg(long, default_value_t = 0.3)]
+    pub interaction_radius: f32,
+
+    /// Acceleration towards (left drag) or away from (right drag) the cursor
+    #[arg(long, default_value_t = 60.0)]
+    pub interaction_strength: f32,
+
+    /// Swapchain presentation mode
+    #[arg(long, value_enum, default_value_t = PresentMode::AutoVsync)]
+    pub present_mode: PresentMode,
+
+    /// Resume from a checkpoint instead of spawning the scene's particles; the scene
+    /// file and its overrides cannot be given with it
+    #[arg(long, conflicts_with_all = SCENE_OVERRIDES)]
+    pub resume: Option<PathBuf>,
+
+    /// Checkpoint file written with F5 and restored with F9
+    #[arg(long, default_value = "checkpoint.fsim")]
+    pub checkpoint: PathBuf,
+}
+
+#[derive(Args)]
+pub struct HeadlessArgs {
+    #[command(flatten)]
+    pub scene: SceneArgs,
+
+    #[command(flatten)]
+    pub export: ExportArgs,
+
+    #[command(flatten)]
+    pub record: RecordArgs,
+
+    /// Render frames offscreen and write them as numbered PNGs into this directory
+    #[arg(long, conflicts_with = "cpu")]
+    pub record_dir: Option<PathBuf>,
+
+    /// Number of simulation steps to run
+    #[arg(long, default_value_t = 1000)]
+    pub steps: u32,
+
+    /// Directory that receives the run outputs: the scene is written there as
+    /// scene.toml, and relative --export, --record-dir and --checkpoint paths are
+    /// placed inside it
+    #[arg(long)]
+    pub out: Option<PathBuf>,
+
+    /// Use the CPU reference solver instead of the GPU
+    #[arg(long)]
+    pub cpu: bool,
+
+    /// Resume from a checkpoint instead of spawning the scene's particles; the scene
+    /// file and its overrides cannot be given with it
+    #[arg(long, conflicts_with_all = SCENE_OVERRIDES)]
+    pub resume: Option<PathBuf>,
+
+    /// Write a checkpoint to this file when the run finishes
+    #[arg(long)]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Also write the checkpoint every N steps
+    #[arg(long, requires = "checkpoint", value_parser = clap::value_parser!(u32).range(1..))]
+    pub checkpoint_interval: Option<u32>,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum Backend {
+    All,
+    Vulkan,
+    Metal,
+    Dx12,
+    Gl,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum ExportFormat {
+    /// VTK unstructured grid per frame plus a .pvd series for ParaView
+    Vtu,
+    /// Binary PLY point cloud per frame
+    Ply,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum Integrator {
+    /// Kick then drift, one force evaluation per solver step
+    SymplecticEuler,
+    /// Kick-drift-kick, two force evaluations per solver step
+    Leapfrog,
+    /// Velocity Verlet, two force evaluations per solver step
+    VelocityVerlet,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum EquationOfState {
+    /// Pressure proportional to the density excess, scaled by the stiffness
+    Linear,
+    /// Tait equation with exponent 7 and a speed-of-sound parameter
+    Tait,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum PressureSolver {
+    /// Weakly compressible SPH with the equation of state, one pass per force evaluation
+    Wcsph,
+    /// Predictive-corrective incompressible SPH, iterated to the density error tolerance
+    Pcisph,
+    /// Divergence-free SPH with a divergence-free and a constant-density solve
+    Dfsph,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum PresentMode {
+    AutoVsync,
+    AutoNoVsync,
+    Fifo,
+    FifoRelaxed,
+    Immediate,
+    Mailbox,
+}
+
+impl SceneArgs {
+    pub fn load_scene(&self) -> anyhow::Result<Scene> {
+        let mut scene = match &self.scene {
+            Some(path) => Scene::load(path)?,
+            None => Scene::default(),
+        };
+
+        if let Some(particles) = self.particles {
+            scene.simulation.particle_count = particles;
+        }
+
+        if let Some(substeps) = self.substeps {
+            scene.simulation.substeps = substeps;
+        }
+
+        if self.adaptive_time_step {
+            scene.simulation.adaptive_time_step = true;
+        }
+
+        if self.boundary_particles {
+            scene.simulation.boundary_particles = true;
+        }
+
+        if let Some(integrator) = self.integrator {
+            scene.simulation.integrator = integrator.into();
+        }
+
+        if let Some(equation_of_state) = self.equation_of_state {
+            scene.simulation.equation_of_state = equation_of_state.into();
+        }
+
+        if let Some(speed_of_sound) = self.speed_of_sound {
+            scene.simulation.speed_of_sound = speed_of_sound;
+        }
+
+        if self.clamp_negative_pressure {
+            scene.simulation.clamp_negative_pressure = true;
+        }
+
+        if let Some(pressure_solver) = self.pressure_solver {
+            scene.simulation.pressure_solver = pressure_solver.into();
+        }
+
+        if let Some(seed) = self.seed {
+            scene.seed = Some(seed);
+        }
+
+        scene
+            .validate()
+            .context("Invalid command-line scene override")?;
+
+        Ok(scene)
+    }
+}
+
+impl ExportArgs {
+    pub fn exporter(&self) -> anyhow::Result<Option<FrameExporter>> {
+        self.export
+            .as_deref()
+            .map(|dir| FrameExporter::new(dir, self.export_format.into(), self.export_stride))
+            .transpose()
+    }
+}
+
+impl Default for RunArgs {
+    fn default() -> Self {
+        let matches = RunArgs::augment_args(clap::Command::new("run")).get_matches_from(["run"]);
+
+        RunArgs::from_arg_matches(&matches).expect("Default run arguments must parse")
+    }
+}
+
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, got `{value}`"))?;
+
+    let parse = |dimension: &str| {
+        dimension
+            .parse::<u32>()
+            .ok()
+            .filter(|dimension| *dimension > 0)
+            .ok_or_else(|| format!("`{dimension}` is not a positive integer"))
+    };
+
+    Ok((parse(width)?, parse(height)?))
+}
+
+impl From<Backend> for wgpu::Backends {
+    fn from(backend: Backend) -> Self {
+        match backend {
+            Backend::All => wgpu::Backends::all(),
+            Backend::Vulkan => wgpu::Backends::VULKAN,
+            Backend::Metal => wgpu::Backends::METAL,
+            Backend::Dx12 => wgpu::Backends::DX12,
+            Backend::Gl => wgpu::Backends::GL,
+        }
+    }
+}
+
+impl From<ExportFormat> for export::ExportFormat {
+    fn from(format: ExportFormat) -> Self {
+        match format {
+            ExportFormat::Vtu => export::ExportFormat::Vtu,
+            ExportFormat::Ply => export::ExportFormat::Ply,
+        }
+    }
+}
+
+impl From<Integrator> for simulation::Integrator {
+    fn from(integrator: Integrator) -> Self {
+        match integrator {
+            Integrator::SymplecticEuler => simulation::Integrator::SymplecticEuler,
+            Integrator::Leapfrog => simulation::Integrator::Leapfrog,
+            Integrator::VelocityVerlet => simulation::Integrator::VelocityVerlet,
+        }
+    }
+}
+
+impl From<EquationOfState> for simulation::EquationOfState {
+    fn from(equation_of_state: EquationOfState) -> Self {
+        match equation_of_state {
+            EquationOfState::Linear => simulation::EquationOfState::Linear,
+            EquationOfState::Tait => simulation::EquationOfState::Tait,
+        }
+    }
+}
+
+impl From<PressureSolver> for simulation::PressureSolver {
+    fn from(pressure_solver: PressureSolver) -> Self {
+        match pressure_solver {
+            PressureSolver::Wcsph => simulation::PressureSolver::Wcsph,
+            PressureSolver::Pcisph => simulation::PressureSolver::Pcisph,
+            PressureSolver::Dfsph => simulation::PressureSolver::Dfsph,
+        }
+    }
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(present_mode: PresentMode) -> Self {
+        match present_mode {
+            PresentMode::AutoVsync => wgpu::PresentMode::AutoVsync,
+            PresentMode::AutoNoVsync => wgpu::PresentMode::AutoNoVsync,
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::FifoRelaxed => wgpu::PresentMode::FifoRelaxed,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+        }
+    }
+}
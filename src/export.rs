@@ -0,0 +1,451 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::pipelines::readback::ParticleFrame;
+use crate::simulation::Simulation;
+
+const SERIES_FILE_NAME: &str = "frames.pvd";
+const SERIES_HEADER: &str = concat!(
+    "<?xml version=\"1.0\"?>\n",
+    "<VTKFile type=\"Collection\" version=\"0.1\" byte_order=\"LittleEndian\">\n",
+    "  <Collection>\n",
+);
+const SERIES_FOOTER: &str = "  </Collection>\n</VTKFile>\n";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// VTK unstructured grid per frame plus a `.pvd` series index for ParaView.
+    Vtu,
+    /// Binary little-endian PLY point cloud per frame.
+    Ply,
+}
+
+/// Writes every `stride`-th particle frame into a directory.
+///
+/// Frames are named after their step, e.g. `frame_00000120.vtu`. For VTU output each
+/// frame is appended to the `frames.pvd` index by rewriting only its closing tags, so it
+/// stays loadable even if the run is interrupted. An index already in the directory is
+/// continued: its entries from the first written step on are dropped, so a run resumed
+/// from a checkpoint extends the series and a fresh run replaces it.
+pub struct FrameExporter {
+    dir: PathBuf,
+    format: ExportFormat,
+    stride: u64,
+    series: Option<Series>,
+    last_requested_step: Option<u64>,
+}
+
+/// The open `frames.pvd` index with the step and line of every entry.
+struct Series {
+    file: File,
+    entries: Vec<(u64, String)>,
+    /// Where the closing tags start, and the next entry goes.
+    footer_offset: u64,
+}
+
+impl FrameExporter {
+    pub fn new(dir: &Path, format: ExportFormat, stride: u32) -> anyhow::Result<Self> {
+        if stride == 0 {
+            anyhow::bail!("Export stride must be at least 1");
+        }
+
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create export directory {}", dir.display()))?;
+
+        let series = match format {
+            ExportFormat::Vtu => Some(Series::open(&dir.join(SERIES_FILE_NAME))?),
+            ExportFormat::Ply => None,
+        };
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            format,
+            stride: stride as u64,
+            series,
+            last_requested_step: None,
+        })
+    }
+
+    /// Requests a readback when `simulation` sits on an exported step and writes the
+    /// frames that have arrived since the last call, without waiting for the GPU.
+    ///
+    /// Only blocks when every readback slot is still in flight.
+    pub fn update(&mut self, simulation: &mut Simulation) -> anyhow::Result<()> {
+        let step = simulation.current_step();
+
+        if self.wants(step) && self.last_requested_step != Some(step) {
+            while !simulation.request_readback() {
+                if let Some(frame) = simulation.wait_readback()? {
+                    self.write(&frame)?;
+                }
+            }
+            self.last_requested_step = Some(step);
+        }
+
+        while let Some(frame) = simulation.poll_readback()? {
+            self.write(&frame)?;
+        }
+
+        Ok(())
+    }
+
+    /// Waits for every requested frame and writes it.
+    pub fn flush(&mut self, simulation: &mut Simulation) -> anyhow::Result<()> {
+        while let Some(frame) = simulation.wait_readback()? {
+            self.write(&frame)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the frame after `step` simulation steps should be exported.
+    pub fn wants(&self, step: u64) -> bool {
+        step.is_multiple_of(self.stride)
+    }
+
+    pub fn write(&mut self, frame: &ParticleFrame) -> anyhow::Result<()> {
+        let extension = match self.format {
+            ExportFormat::Vtu => "vtu",
+            ExportFormat::Ply => "ply",
+        };
+        let file_name = format!("frame_{:08}.{extension}", frame.step);
+        let path = self.dir.join(&file_name);
+
+        let write = match self.format {
+            ExportFormat::Vtu => write_vtu,
+            ExportFormat::Ply => write_ply,
+        };
+        write_file(&path, |writer| write(writer, frame))?;
+
+        if let Some(series) = &mut self.series {
+            series
+                .add(frame.step, frame.time, &file_name)
+                .with_context(|| format!("Failed to write {SERIES_FILE_NAME}"))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Series {
+    /// Opens the index at `path`, keeping the entries of an existing one.
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        let entries = match std::fs::read_to_string(path) {
+            Ok(contents) => contents
+                .lines()
+                .filter_map(|line| Some((entry_step(line)?, line.to_string())))
+                .collect(),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => {
+                return Err(error).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+
+        let mut series = Self {
+            file,
+            entries,
+            footer_offset: 0,
+        };
+        series
+            .rewrite()
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+
+        Ok(series)
+    }
+
+    /// Appends the entry of a frame. Entries at or after `step` are left over from an
+    /// earlier run and are dropped, which rewrites the whole index once.
+    fn add(&mut self, step: u64, time: f64, file_name: &str) -> anyhow::Result<()> {
+        let line =
+            format!(r#"    <DataSet timestep="{time}" group="" part="0" file="{file_name}"/>"#);
+
+        if self.entries.last().is_some_and(|(last, _)| *last >= step) {
+            self.entries.retain(|(entry_step, _)| *entry_step < step);
+            self.entries.push((step, line));
+            return self.rewrite();
+        }
+
+        self.file.seek(SeekFrom::Start(self.footer_offset))?;
+        self.file.write_all(format!("{line}\n").as_bytes())?;
+        self.footer_offset = self.file.stream_position()?;
+        self.file.write_all(SERIES_FOOTER.as_bytes())?;
+        self.entries.push((step, line));
+
+        Ok(())
+    }
+
+    fn rewrite(&mut self) -> anyhow::Result<()> {
+        let mut contents = SERIES_HEADER.to_string();
+        for (_, line) in &self.entries {
+            contents.push_str(line);
+            contents.push('\n');
+        }
+        self.footer_offset = contents.len() as u64;
+        contents.push_str(SERIES_FOOTER);
+
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(contents.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+/// Step of an index line naming a `frame_<step>.vtu` file, if it is an entry.
+fn entry_step(line: &str) -> Option<u64> {
+    let (_, file) = line
+        .trim()
+        .strip_prefix("<DataSet ")?
+        .split_once(r#"file="frame_"#)?;
+    let (step, _) = file.split_once(".vtu\"")?;
+
+    step.parse().ok()
+}
+
+fn write_file(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+
+    write(&mut writer)
+        .and_then(|_| writer.flush().map_err(Into::into))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn write_vtu(writer: &mut impl Write, frame: &ParticleFrame) -> anyhow::Result<()> {
+    let len = frame.particles.len();
+
+    writeln!(writer, r#"<?xml version="1.0"?>"#)?;
+    writeln!(
+        writer,
+        r#"<VTKFile type="UnstructuredGrid" version="1.0" byte_order="LittleEndian" header_type="UInt64">"#
+    )?;
+    writeln!(writer, "  <UnstructuredGrid>")?;
+    writeln!(
+        writer,
+        r#"    <Piece NumberOfPoints="{len}" NumberOfCells="{len}">"#
+    )?;
+
+    writeln!(
+        writer,
+        r#"      <PointData Scalars="density" Vectors="velocity">"#
+    )?;
+    write_data_array(writer, "velocity", 3, |writer| {
+        for particle in &frame.particles {
+            writeln!(writer, "{} {} 0", particle.velocity_x, particle.velocity_y)?;
+        }
+        Ok(())
+    })?;
+    write_data_array(writer, "density", 1, |writer| {
+        for density in &frame.densities {
+            writeln!(writer, "{density}")?;
+        }
+        Ok(())
+    })?;
+    write_data_array(writer, "pressure", 1, |writer| {
+        for pressure in &frame.pressures {
+            writeln!(writer, "{pressure}")?;
+        }
+        Ok(())
+    })?;
+    writeln!(writer, "      </PointData>")?;
+
+    writeln!(writer, "      <Points>")?;
+    write_data_array(writer, "position", 3, |writer| {
+        for particle in &frame.particles {
+            writeln!(writer, "{} {} 0", particle.position_x, particle.position_y)?;
+        }
+        Ok(())
+    })?;
+    writeln!(writer, "      </Points>")?;
+
+    // One VTK_VERTEX cell per particle so the points render without extra filters.
+    writeln!(writer, "      <Cells>")?;
+    writeln!(
+        writer,
+        r#"        <DataArray type="Int64" Name="connectivity" format="ascii">"#
+    )?;
+    for i in 0..len {
+        writeln!(writer, "{i}")?;
+    }
+    writeln!(writer, "        </DataArray>")?;
+    writeln!(
+        writer,
+        r#"        <DataArray type="Int64" Name="offsets" format="ascii">"#
+    )?;
+    for i in 1..=len {
+        writeln!(writer, "{i}")?;
+    }
+    writeln!(writer, "        </DataArray>")?;
+    writeln!(
+        writer,
+        r#"        <DataArray type="UInt8" Name="types" format="ascii">"#
+    )?;
+    for _ in 0..len {
+        writeln!(writer, "1")?;
+    }
+    writeln!(writer, "        </DataArray>")?;
+    writeln!(writer, "      </Cells>")?;
+
+    writeln!(writer, "    </Piece>")?;
+    writeln!(writer, "  </UnstructuredGrid>")?;
+    writeln!(writer, "</VTKFile>")?;
+
+    Ok(())
+}
+
+fn write_data_array<W: Write>(
+    writer: &mut W,
+    name: &str,
+    components: u32,
+    write_values: impl FnOnce(&mut W) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    writeln!(
+        writer,
+        r#"        <DataArray type="Float32" Name="{name}" NumberOfComponents="{components}" format="ascii">"#
+    )?;
+    write_values(writer)?;
+    writeln!(writer, "        </DataArray>")?;
+
+    Ok(())
+}
+
+fn write_ply(writer: &mut impl Write, frame: &ParticleFrame) -> anyhow::Result<()> {
+    writeln!(writer, "ply")?;
+    writeln!(writer, "format binary_little_endian 1.0")?;
+    writeln!(writer, "comment step {} time {}", frame.step, frame.time)?;
+    writeln!(writer, "element vertex {}", frame.particles.len())?;
+    for property in ["x", "y", "z", "vx", "vy", "density", "pressure"] {
+        writeln!(writer, "property float {property}")?;
+    }
+    writeln!(writer, "end_header")?;
+
+    for ((particle, density), pressure) in frame
+        .particles
+        .iter()
+        .zip(&frame.densities)
+        .zip(&frame.pressures)
+    {
+        for value in [
+            particle.position_x,
+            particle.position_y,
+            0.0,
+            particle.velocity_x,
+            particle.velocity_y,
+            *density,
+            *pressure,
+        ] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh directory for one test, cleared of anything an earlier run left behind.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("fluid_simulation_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entry(step: u64, time: f64) -> String {
+        format!(
+            "    <DataSet timestep=\"{time}\" group=\"\" part=\"0\" file=\"frame_{step:08}.vtu\"/>\n"
+        )
+    }
+
+    fn add(series: &mut Series, step: u64, time: f64) {
+        series
+            .add(step, time, &format!("frame_{step:08}.vtu"))
+            .unwrap();
+    }
+
+    /// Checks the whole index, so every state it is left in is a complete document.
+    fn assert_index(path: &Path, entries: &[(u64, f64)]) {
+        let expected: String = std::iter::once(SERIES_HEADER.to_string())
+            .chain(entries.iter().map(|(step, time)| entry(*step, *time)))
+            .chain(std::iter::once(SERIES_FOOTER.to_string()))
+            .collect();
+
+        assert_eq!(std::fs::read_to_string(path).unwrap(), expected);
+    }
+
+    #[test]
+    fn opens_a_fresh_index() {
+        let dir = temp_dir("fresh_index");
+        let path = dir.join(SERIES_FILE_NAME);
+
+        Series::open(&path).unwrap();
+        assert_index(&path, &[]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn appends_entries() {
+        let dir = temp_dir("appends_entries");
+        let path = dir.join(SERIES_FILE_NAME);
+        let mut series = Series::open(&path).unwrap();
+
+        add(&mut series, 0, 0.0);
+        assert_index(&path, &[(0, 0.0)]);
+        add(&mut series, 10, 0.5);
+        assert_index(&path, &[(0, 0.0), (10, 0.5)]);
+        add(&mut series, 20, 1.0);
+        assert_index(&path, &[(0, 0.0), (10, 0.5), (20, 1.0)]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn resumed_run_replaces_later_entries() {
+        let dir = temp_dir("resumed_run");
+        let path = dir.join(SERIES_FILE_NAME);
+        let mut series = Series::open(&path).unwrap();
+        for (step, time) in [(0, 0.0), (10, 0.5), (20, 1.0), (30, 1.5)] {
+            add(&mut series, step, time);
+        }
+        drop(series);
+
+        // Resumed from a checkpoint at step 10, so its first export is step 20.
+        let mut series = Series::open(&path).unwrap();
+        assert_index(&path, &[(0, 0.0), (10, 0.5), (20, 1.0), (30, 1.5)]);
+        add(&mut series, 20, 1.0);
+        assert_index(&path, &[(0, 0.0), (10, 0.5), (20, 1.0)]);
+        add(&mut series, 30, 1.5);
+        assert_index(&path, &[(0, 0.0), (10, 0.5), (20, 1.0), (30, 1.5)]);
+        add(&mut series, 40, 2.0);
+        assert_index(
+            &path,
+            &[(0, 0.0), (10, 0.5), (20, 1.0), (30, 1.5), (40, 2.0)],
+        );
+
+        // A fresh run starting from step 0 replaces the whole series.
+        let mut series = Series::open(&path).unwrap();
+        add(&mut series, 0, 0.0);
+        assert_index(&path, &[(0, 0.0)]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,73 @@
+pub fn create_instance(backends: wgpu::Backends) -> wgpu::Instance {
+    wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    })
+}
+
+pub fn list_adapters(backends: wgpu::Backends) {
+    let adapters = create_instance(backends).enumerate_adapters(backends);
+
+    if adapters.is_empty() {
+        println!("No adapters found");
+    }
+
+    for (i, adapter) in adapters.iter().enumerate() {
+        let info = adapter.get_info();
+        println!(
+            "{i}: {} ({:?}, {:?}) driver: {} {}",
+            info.name, info.backend, info.device_type, info.driver, info.driver_info
+        );
+    }
+}
+
+pub async fn init_headless_adapter(instance: &wgpu::Instance) -> anyhow::Result<wgpu::Adapter> {
+    let adapter = match instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            compatible_surface: None,
+        })
+        .await
+    {
+        Ok(adapter) => adapter,
+        Err(_) => {
+            log::warn!("No hardware adapter found, falling back to a software adapter");
+
+            instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::HighPerformance,
+                    force_fallback_adapter: true,
+                    compatible_surface: None,
+                })
+                .await?
+        }
+    };
+
+    Ok(adapter)
+}
+
+/// Storage buffers bound by a single compute stage, above the WebGPU default of 8.
+const REQUIRED_STORAGE_BUFFERS_PER_SHADER_STAGE: u32 = 16;
+
+pub async fn init_device(adapter: &wgpu::Adapter) -> anyhow::Result<(wgpu::Device, wgpu::Queue)> {
+    let supported = adapter.limits().max_storage_buffers_per_shader_stage;
+    if supported < REQUIRED_STORAGE_BUFFERS_PER_SHADER_STAGE {
+        anyhow::bail!(
+            "Adapter supports {supported} storage buffers per shader stage, {REQUIRED_STORAGE_BUFFERS_PER_SHADER_STAGE} are required"
+        );
+    }
+
+    Ok(adapter
+        .request_device(&wgpu::DeviceDescriptor {
+            label: None,
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits {
+                max_storage_buffers_per_shader_stage: REQUIRED_STORAGE_BUFFERS_PER_SHADER_STAGE,
+                ..wgpu::Limits::default()
+            },
+            memory_hints: wgpu::MemoryHints::MemoryUsage,
+            trace: wgpu::Trace::Off,
+        })
+        .await?)
+}
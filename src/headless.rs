@@ -0,0 +1,312 @@
+use std::path::Path;
+use std::time::Instant;
+
+use anyhow::Context;
+
+use fluid_simulation::checkpoint::Checkpoint;
+use fluid_simulation::export::FrameExporter;
+use fluid_simulation::gpu;
+use fluid_simulation::pipelines::readback::ParticleFrame;
+use fluid_simulation::recording::FrameRecorder;
+use fluid_simulation::scene::Scene;
+use fluid_simulation::simulation::cpu::CpuSolver;
+use fluid_simulation::simulation::{PressureSolver, Simulation, SimulationParams};
+
+use crate::cli::HeadlessArgs;
+
+const PROGRESS_INTERVAL: u32 = 100;
+
+pub fn run(mut args: HeadlessArgs) -> anyhow::Result<()> {
+    root_outputs(&mut args)?;
+
+    let initial_state = match &args.resume {
+        Some(path) => {
+            let checkpoint = Checkpoint::load(path)?;
+            log::info!(
+                "Resuming {} particles from step {} of {}",
+                checkpoint.particles_len(),
+                checkpoint.step,
+                path.display()
+            );
+            checkpoint
+        }
+        None => {
+            let scene = args.scene.load_scene()?;
+
+            if let Some(out) = &args.out {
+                write_scene(out, &scene)?;
+            }
+
+            initial_state(&scene)
+        }
+    };
+
+    let particles_len = initial_state.particles_len();
+    let exporter = args.export.exporter()?;
+    let started = Instant::now();
+
+    if args.cpu {
+        run_cpu(&args, initial_state, exporter)?;
+    } else {
+        pollster::block_on(run_gpu(&args, &initial_state, exporter))?;
+    }
+
+    let elapsed = started.elapsed().as_secs_f64();
+    println!(
+        "Simulated {} steps of {particles_len} particles in {elapsed:.2}s ({:.1} steps/s)",
+        args.steps,
+        args.steps as f64 / elapsed,
+    );
+
+    Ok(())
+}
+
+fn run_cpu(
+    args: &HeadlessArgs,
+    initial_state: Checkpoint,
+    mut exporter: Option<FrameExporter>,
+) -> anyhow::Result<()> {
+    let mut solver = CpuSolver::new(
+        initial_state.particles,
+        initial_state.obstacles,
+        initial_state.rigid_bodies,
+        initial_state.simulation_params,
+    );
+    solver.densities = initial_state.densities;
+    solver.pressures = initial_state.pressures;
+    solver.time = initial_state.time;
+
+    let steps = args.steps;
+    let frame = |solver: &CpuSolver, step: u32| ParticleFrame {
+        step: initial_state.step + step as u64,
+        time: solver.time,
+        time_step: solver.time_step(),
+        density_error: solver.density_error(),
+        pressure_iterations: solver.pressure_iterations(),
+        divergence_error: solver.divergence_error(),
+        divergence_iterations: solver.divergence_iterations(),
+        particles: solver.particles.clone(),
+        densities: solver.densities.clone(),
+        pressures: solver.pressures.clone(),
+    };
+    let checkpoint = |solver: &CpuSolver, step: u32| {
+        let frame = frame(solver, step);
+
+        Checkpoint {
+            step: frame.step,
+            time: frame.time,
+            simulation_params: *solver.params(),
+            particles: frame.particles,
+            densities: frame.densities,
+            pressures: frame.pressures,
+            obstacles: solver.obstacles().to_vec(),
+            rigid_bodies: solver.rigid_bodies().to_vec(),
+        }
+    };
+    let mut export = |solver: &CpuSolver, step: u32| match &mut exporter {
+        Some(exporter) if exporter.wants(initial_state.step + step as u64) => {
+            exporter.write(&frame(solver, step))
+        }
+        _ => Ok(()),
+    };
+
+    export(&solver, 0)?;
+
+    for step in 1..=steps {
+        solver.step();
+        export(&solver, step)?;
+
+        if step % PROGRESS_INTERVAL == 0 {
+            log::info!("Step {step}/{steps}");
+            log_pressure_solve(
+                solver.params(),
+                [solver.divergence_error(), solver.density_error()],
+                [solver.divergence_iterations(), solver.pressure_iterations()],
+            );
+        }
+
+        if should_checkpoint(args, step) {
+            save_checkpoint(args, &checkpoint(&solver, step))?;
+        }
+    }
+
+    if args.checkpoint.is_some() {
+        save_checkpoint(args, &checkpoint(&solver, steps))?;
+    }
+
+    Ok(())
+}
+
+async fn run_gpu(
+    args: &HeadlessArgs,
+    initial_state: &Checkpoint,
+    mut exporter: Option<FrameExporter>,
+) -> anyhow::Result<()> {
+    let instance = gpu::create_instance(args.scene.backend.into());
+    let adapter = gpu::init_headless_adapter(&instance).await?;
+    log::info!("Using adapter {:?}", adapter.get_info());
+
+    let (device, queue) = gpu::init_device(&adapter).await?;
+    let mut simulation = Simulation::from_checkpoint(&device, &queue, initial_state)?;
+    let time_step = simulation.params().time_step();
+    let steps = args.steps;
+
+    let mut recorder = args
+        .record_dir
+        .as_deref()
+        .map(|dir| {
+            FrameRecorder::new(
+                &device,
+                dir,
+                args.record.record_size,
+                args.record.record_stride,
+            )
+        })
+        .transpose()?;
+
+    let mut update_outputs =
+        |simulation: &mut Simulation, exporter: Option<&mut FrameExporter>| -> anyhow::Result<()> {
+            if let Some(exporter) = exporter {
+                exporter.update(simulation)?;
+            }
+
+            if let Some(recorder) = &mut recorder {
+                recorder.update(&device, &queue, simulation)?;
+            }
+
+            Ok(())
+        };
+
+    update_outputs(&mut simulation, exporter.as_mut())?;
+
+    for step in 1..=steps {
+        simulation.step(time_step);
+        update_outputs(&mut simulation, exporter.as_mut())?;
+
+        if step % PROGRESS_INTERVAL == 0 {
+            device.poll(wgpu::PollType::Wait)?;
+            log::info!("Step {step}/{steps}");
+
+            if simulation.params().pressure_solver() != PressureSolver::Wcsph {
+                let state = simulation.read_pressure_solve()?;
+                log_pressure_solve(
+                    simulation.params(),
+                    [state.divergence_error, state.density_error],
+                    [state.divergence_iterations, state.iterations],
+                );
+            }
+        }
+
+        if should_checkpoint(args, step) {
+            save_gpu_checkpoint(args, &mut simulation, exporter.as_mut())?;
+        }
+    }
+
+    if let Some(exporter) = &mut exporter {
+        exporter.flush(&mut simulation)?;
+    }
+
+    if args.checkpoint.is_some() {
+        save_gpu_checkpoint(args, &mut simulation, exporter.as_mut())?;
+    }
+
+    device.poll(wgpu::PollType::Wait)?;
+
+    Ok(())
+}
+
+/// `errors` and `iterations` hold the divergence solve, only logged with DFSPH, followed
+/// by the density solve.
+fn log_pressure_solve(params: &SimulationParams, errors: [f32; 2], iterations: [u32; 2]) {
+    let [divergence_error, density_error] = errors;
+    let [divergence_iterations, iterations] = iterations;
+
+    match params.pressure_solver() {
+        PressureSolver::Wcsph => return,
+        PressureSolver::Pcisph => {}
+        PressureSolver::Dfsph => log::info!(
+            "Divergence error {:.3}% after {divergence_iterations}/{} divergence iterations",
+            divergence_error * 100.0,
+            params.max_pressure_iterations(),
+        ),
+    }
+
+    log::info!(
+        "Density error {:.3}% after {iterations}/{} pressure iterations",
+        density_error * 100.0,
+        params.max_pressure_iterations(),
+    );
+}
+
+fn initial_state(scene: &Scene) -> Checkpoint {
+    let simulation_params = scene.simulation_params();
+    let particles_len = simulation_params.particles_len as usize;
+
+    Checkpoint {
+        step: 0,
+        time: 0.0,
+        simulation_params,
+        particles: scene.spawn_particles(),
+        densities: vec![0.0; particles_len],
+        pressures: vec![0.0; particles_len],
+        obstacles: scene.obstacles.clone(),
+        rigid_bodies: scene.rigid_bodies.clone(),
+    }
+}
+
+fn should_checkpoint(args: &HeadlessArgs, step: u32) -> bool {
+    args.checkpoint_interval
+        .is_some_and(|interval| step.is_multiple_of(interval) && step != args.steps)
+}
+
+fn save_gpu_checkpoint(
+    args: &HeadlessArgs,
+    simulation: &mut Simulation,
+    exporter: Option<&mut FrameExporter>,
+) -> anyhow::Result<()> {
+    // Checkpoint readback needs a free slot, so pending export frames go out first.
+    if let Some(exporter) = exporter {
+        exporter.flush(simulation)?;
+    }
+
+    save_checkpoint(args, &simulation.checkpoint()?)
+}
+
+fn save_checkpoint(args: &HeadlessArgs, checkpoint: &Checkpoint) -> anyhow::Result<()> {
+    if let Some(path) = &args.checkpoint {
+        checkpoint.save(path)?;
+        log::info!("Saved step {} to {}", checkpoint.step, path.display());
+    }
+
+    Ok(())
+}
+
+/// Creates the `--out` directory and moves every relative output path into it, so a
+/// run, resumed or not, keeps all its artifacts together.
+fn root_outputs(args: &mut HeadlessArgs) -> anyhow::Result<()> {
+    let Some(out) = &args.out else {
+        return Ok(());
+    };
+
+    std::fs::create_dir_all(out)
+        .with_context(|| format!("Failed to create output directory {}", out.display()))?;
+
+    for path in [
+        &mut args.export.export,
+        &mut args.record_dir,
+        &mut args.checkpoint,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        *path = out.join(&*path);
+    }
+
+    Ok(())
+}
+
+fn write_scene(out: &Path, scene: &Scene) -> anyhow::Result<()> {
+    let path = out.join("scene.toml");
+    std::fs::write(&path, toml::to_string(scene)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
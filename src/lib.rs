@@ -0,0 +1,30 @@
+//! GPU smoothed-particle hydrodynamics solver built on wgpu.
+//!
+//! [`simulation::Simulation`] owns the compute pipelines and particle buffers and is
+//! the entry point for embedding the solver; the `fluid_simulation` binary is a thin
+//! windowed/headless front end on top of it.
+
+pub mod camera;
+pub mod checkpoint;
+pub mod constants;
+pub mod export;
+pub mod gpu;
+pub mod obstacle;
+pub mod playback;
+pub mod recording;
+pub mod rigid_body;
+pub mod scene;
+pub mod simulation;
+
+pub mod pipelines {
+    pub mod compute;
+    pub mod dfsph;
+    pub mod neighbor_search;
+    pub mod offscreen;
+    pub mod pcisph;
+    pub mod radix_sort;
+    pub mod readback;
+    pub mod render;
+    pub mod rigid_body;
+    pub mod time_step;
+}
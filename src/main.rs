@@ -1,13 +1,23 @@
+use clap::Parser;
+use fluid_simulation::gpu;
+
 mod app;
-mod constants;
-mod simulation;
+mod cli;
+mod headless;
 mod state;
 
-mod pipelines {
-    pub mod compute;
-    pub mod render;
-}
-
 fn main() -> anyhow::Result<()> {
-    app::run()
+    env_logger::init();
+
+    let cli = cli::Cli::parse();
+
+    match cli.command {
+        Some(cli::Command::Run(args)) => app::run(args),
+        Some(cli::Command::Headless(args)) => headless::run(args),
+        Some(cli::Command::ListAdapters { backend }) => {
+            gpu::list_adapters(backend.into());
+            Ok(())
+        }
+        None => app::run(cli::RunArgs::default()),
+    }
 }
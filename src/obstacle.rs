@@ -0,0 +1,324 @@
+use serde::{Deserialize, Serialize};
+
+const KIND_CIRCLE: u32 = 0;
+const KIND_BOX: u32 = 1;
+const KIND_CAPSULE: u32 = 2;
+const KIND_POLYGON: u32 = 3;
+
+/// Static collider, evaluated as a signed distance function by the physics kernels.
+///
+/// Particles that end up inside an obstacle are pushed back to its surface and have the
+/// velocity component into it reflected and scaled by the restitution.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "shape", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Obstacle {
+    Circle {
+        center: [f32; 2],
+        radius: f32,
+    },
+    Box {
+        center: [f32; 2],
+        half_size: [f32; 2],
+        /// Counter-clockwise rotation in radians.
+        #[serde(default)]
+        rotation: f32,
+    },
+    /// Segment from `start` to `end` inflated by `radius`.
+    Capsule {
+        start: [f32; 2],
+        end: [f32; 2],
+        radius: f32,
+    },
+    /// Simple polygon; the winding order does not matter.
+    Polygon {
+        vertices: Vec<[f32; 2]>,
+    },
+}
+
+/// Mirror of `Obstacle` in `obstacles.wgsl`. Polygon vertices live in a separate buffer
+/// and are referenced by `vertex_offset` and `vertex_count`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuObstacle {
+    kind: u32,
+    vertex_offset: u32,
+    vertex_count: u32,
+    _padding: u32,
+    params: [f32; 4],
+    extra: [f32; 4],
+}
+
+impl Obstacle {
+    pub fn signed_distance(&self, point: [f32; 2]) -> f32 {
+        match self {
+            Obstacle::Circle { center, radius } => length(sub(point, *center)) - radius,
+            Obstacle::Box {
+                center,
+                half_size,
+                rotation,
+            } => {
+                let local = rotate(sub(point, *center), -rotation);
+                let d = [local[0].abs() - half_size[0], local[1].abs() - half_size[1]];
+
+                length([d[0].max(0.0), d[1].max(0.0)]) + d[0].max(d[1]).min(0.0)
+            }
+            Obstacle::Capsule { start, end, radius } => {
+                length(segment_offset(point, *start, *end)) - radius
+            }
+            Obstacle::Polygon { vertices } => polygon_signed_distance(vertices, point),
+        }
+    }
+
+    /// Outward unit normal at `point`, from central differences of the signed distance
+    /// with a step of `epsilon`.
+    pub fn normal(&self, point: [f32; 2], epsilon: f32) -> [f32; 2] {
+        let [x, y] = point;
+        let gradient = [
+            self.signed_distance([x + epsilon, y]) - self.signed_distance([x - epsilon, y]),
+            self.signed_distance([x, y + epsilon]) - self.signed_distance([x, y - epsilon]),
+        ];
+        let gradient_length = length(gradient);
+
+        if gradient_length < 1.0e-12 {
+            return [0.0, 1.0];
+        }
+
+        [gradient[0] / gradient_length, gradient[1] / gradient_length]
+    }
+
+    /// Points along the outline, no further apart than `spacing`.
+    pub fn sample_outline(&self, spacing: f32) -> Vec<[f32; 2]> {
+        match self {
+            Obstacle::Circle { center, radius } => {
+                sample_arc(*center, *radius, 0.0, std::f32::consts::TAU, spacing)
+            }
+            Obstacle::Box {
+                center,
+                half_size,
+                rotation,
+            } => {
+                let corners = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]].map(|corner| {
+                    let offset = rotate(
+                        [corner[0] * half_size[0], corner[1] * half_size[1]],
+                        *rotation,
+                    );
+                    [center[0] + offset[0], center[1] + offset[1]]
+                });
+
+                sample_polygon(&corners, spacing)
+            }
+            Obstacle::Capsule { start, end, radius } => {
+                let axis = sub(*end, *start);
+                let axis_length = length(axis);
+                let normal_angle = if axis_length > 0.0 {
+                    axis[1].atan2(axis[0]) + std::f32::consts::FRAC_PI_2
+                } else {
+                    0.0
+                };
+                let normal = [radius * normal_angle.cos(), radius * normal_angle.sin()];
+                let side = |point: [f32; 2], sign: f32| {
+                    [point[0] + sign * normal[0], point[1] + sign * normal[1]]
+                };
+                let pi = std::f32::consts::PI;
+
+                let mut points = sample_segment(side(*start, 1.0), side(*end, 1.0), spacing);
+                points.extend(sample_arc(*end, *radius, normal_angle, -pi, spacing));
+                points.extend(sample_segment(
+                    side(*end, -1.0),
+                    side(*start, -1.0),
+                    spacing,
+                ));
+                points.extend(sample_arc(*start, *radius, normal_angle - pi, -pi, spacing));
+                points
+            }
+            Obstacle::Polygon { vertices } => sample_polygon(vertices, spacing),
+        }
+    }
+
+    /// Packs `obstacles` into the records and polygon vertices uploaded to the GPU.
+    pub fn pack(obstacles: &[Obstacle]) -> (Vec<GpuObstacle>, Vec<[f32; 2]>) {
+        let mut vertices = Vec::new();
+        let records = obstacles
+            .iter()
+            .map(|obstacle| obstacle.pack_into(&mut vertices))
+            .collect();
+
+        (records, vertices)
+    }
+
+    /// Packs a single obstacle, appending its polygon vertices to `vertices`.
+    pub fn pack_into(&self, vertices: &mut Vec<[f32; 2]>) -> GpuObstacle {
+        match self {
+            Obstacle::Circle { center, radius } => GpuObstacle {
+                kind: KIND_CIRCLE,
+                params: [center[0], center[1], *radius, 0.0],
+                ..Default::default()
+            },
+            Obstacle::Box {
+                center,
+                half_size,
+                rotation,
+            } => GpuObstacle {
+                kind: KIND_BOX,
+                params: [center[0], center[1], half_size[0], half_size[1]],
+                extra: [*rotation, 0.0, 0.0, 0.0],
+                ..Default::default()
+            },
+            Obstacle::Capsule { start, end, radius } => GpuObstacle {
+                kind: KIND_CAPSULE,
+                params: [start[0], start[1], end[0], end[1]],
+                extra: [*radius, 0.0, 0.0, 0.0],
+                ..Default::default()
+            },
+            Obstacle::Polygon { vertices: polygon } => {
+                let record = GpuObstacle {
+                    kind: KIND_POLYGON,
+                    vertex_offset: vertices.len() as u32,
+                    vertex_count: polygon.len() as u32,
+                    ..Default::default()
+                };
+                vertices.extend_from_slice(polygon);
+                record
+            }
+        }
+    }
+
+    /// Inverse of [`Obstacle::pack`].
+    pub fn unpack(records: &[GpuObstacle], vertices: &[[f32; 2]]) -> anyhow::Result<Vec<Self>> {
+        records
+            .iter()
+            .map(|record| {
+                let [a, b, c, d] = record.params;
+
+                Ok(match record.kind {
+                    KIND_CIRCLE => Obstacle::Circle {
+                        center: [a, b],
+                        radius: c,
+                    },
+                    KIND_BOX => Obstacle::Box {
+                        center: [a, b],
+                        half_size: [c, d],
+                        rotation: record.extra[0],
+                    },
+                    KIND_CAPSULE => Obstacle::Capsule {
+                        start: [a, b],
+                        end: [c, d],
+                        radius: record.extra[0],
+                    },
+                    KIND_POLYGON => {
+                        let start = record.vertex_offset as usize;
+                        let end = start + record.vertex_count as usize;
+                        let polygon = vertices.get(start..end).ok_or_else(|| {
+                            anyhow::anyhow!("Polygon vertices {start}..{end} are out of range")
+                        })?;
+
+                        Obstacle::Polygon {
+                            vertices: polygon.to_vec(),
+                        }
+                    }
+                    kind => anyhow::bail!("Unknown obstacle kind {kind}"),
+                })
+            })
+            .collect()
+    }
+}
+
+fn polygon_signed_distance(vertices: &[[f32; 2]], point: [f32; 2]) -> f32 {
+    let mut distance_sq = f32::MAX;
+    let mut is_inside = false;
+
+    for (i, vertex) in vertices.iter().enumerate() {
+        let previous = vertices[(i + vertices.len() - 1) % vertices.len()];
+        let offset = segment_offset(point, previous, *vertex);
+        distance_sq = distance_sq.min(dot(offset, offset));
+
+        // Crossing number test along +x.
+        if (vertex[1] > point[1]) != (previous[1] > point[1])
+            && point[0]
+                < (previous[0] - vertex[0]) * (point[1] - vertex[1]) / (previous[1] - vertex[1])
+                    + vertex[0]
+        {
+            is_inside = !is_inside;
+        }
+    }
+
+    let distance = distance_sq.sqrt();
+    if is_inside { -distance } else { distance }
+}
+
+/// Points from `start` towards `end`, excluding `end`.
+fn sample_segment(start: [f32; 2], end: [f32; 2], spacing: f32) -> Vec<[f32; 2]> {
+    let segment = sub(end, start);
+    let count = (length(segment) / spacing).ceil().max(1.0) as usize;
+
+    (0..count)
+        .map(|i| {
+            let t = i as f32 / count as f32;
+            [start[0] + t * segment[0], start[1] + t * segment[1]]
+        })
+        .collect()
+}
+
+/// Points along every edge of the closed polygon.
+fn sample_polygon(vertices: &[[f32; 2]], spacing: f32) -> Vec<[f32; 2]> {
+    vertices
+        .iter()
+        .enumerate()
+        .flat_map(|(i, vertex)| {
+            sample_segment(*vertex, vertices[(i + 1) % vertices.len()], spacing)
+        })
+        .collect()
+}
+
+/// Points on the arc of `radius` around `center` from `start_angle` over `sweep`
+/// radians, excluding the end of the arc.
+fn sample_arc(
+    center: [f32; 2],
+    radius: f32,
+    start_angle: f32,
+    sweep: f32,
+    spacing: f32,
+) -> Vec<[f32; 2]> {
+    let count = (radius * sweep.abs() / spacing).ceil().max(1.0) as usize;
+
+    (0..count)
+        .map(|i| {
+            let angle = start_angle + sweep * i as f32 / count as f32;
+            [
+                center[0] + radius * angle.cos(),
+                center[1] + radius * angle.sin(),
+            ]
+        })
+        .collect()
+}
+
+/// Offset from the closest point of the segment `start`-`end` to `point`.
+fn segment_offset(point: [f32; 2], start: [f32; 2], end: [f32; 2]) -> [f32; 2] {
+    let to_point = sub(point, start);
+    let segment = sub(end, start);
+    let length_sq = dot(segment, segment);
+    let t = if length_sq > 0.0 {
+        (dot(to_point, segment) / length_sq).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    [to_point[0] - segment[0] * t, to_point[1] - segment[1] * t]
+}
+
+pub(crate) fn rotate(v: [f32; 2], angle: f32) -> [f32; 2] {
+    let (sin, cos) = angle.sin_cos();
+    [cos * v[0] - sin * v[1], sin * v[0] + cos * v[1]]
+}
+
+pub(crate) fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+pub(crate) fn dot(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn length(v: [f32; 2]) -> f32 {
+    dot(v, v).sqrt()
+}
@@ -1,10 +1,51 @@
-use crate::simulation::{Particle, SimulationParams};
+use crate::obstacle::Obstacle;
+use crate::pipelines::dfsph::DfsphPipelineState;
+use crate::pipelines::neighbor_search::NeighborSearchPipelineState;
+use crate::pipelines::pcisph::PcisphPipelineState;
+use crate::pipelines::rigid_body::RigidBodyPipelineState;
+use crate::pipelines::time_step::TimeStepPipelineState;
+use crate::rigid_body::{RigidBody, RigidBodyImpulse};
+use crate::simulation::boundary::BoundaryParticles;
+use crate::simulation::{
+    Integrator, InteractionParams, Particle, PressureSolver, SimulationParams,
+};
 use wgpu::util::DeviceExt;
 
+const COMMON_SHADER: &str = include_str!("../shaders/common.wgsl");
+
+/// Obstacle signed distance functions, prepended to every shader that evaluates them.
+pub const OBSTACLES_SHADER: &str = include_str!("../shaders/obstacles.wgsl");
+
+/// Particle bindings, neighbor grid and smoothing kernels shared by the pressure solvers.
+pub const SPH_SHADER: &str = include_str!("../shaders/sph.wgsl");
+
+/// Mirror of `PressureSolveState` in `sph.wgsl`, shared by the PCISPH and DFSPH solvers.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PressureSolveState {
+    /// Average relative compression after the last pressure correction, or before the
+    /// first one when none was needed.
+    pub density_error: f32,
+    /// Pressure corrections applied in the last solver step.
+    pub iterations: u32,
+    /// Average relative density change left by the DFSPH divergence solve.
+    pub divergence_error: f32,
+    /// Divergence corrections applied in the last solver step.
+    pub divergence_iterations: u32,
+    converged: u32,
+    _padding: u32,
+}
+
 pub struct ComputePipelineState {
     pub compute_densities_pipeline: wgpu::ComputePipeline,
     pub compute_pressures_pipeline: wgpu::ComputePipeline,
-    pub compute_new_positions_pipeline: wgpu::ComputePipeline,
+    pub compute_accelerations_pipeline: wgpu::ComputePipeline,
+    pub compute_non_pressure_accelerations_pipeline: wgpu::ComputePipeline,
+    pub integrate_pipeline: wgpu::ComputePipeline,
+    pub half_kick_pipeline: wgpu::ComputePipeline,
+    pub drift_pipeline: wgpu::ComputePipeline,
+    pub verlet_predict_pipeline: wgpu::ComputePipeline,
+    pub verlet_correct_pipeline: wgpu::ComputePipeline,
 
     pub position_x_buffer: wgpu::Buffer,
     pub position_y_buffer: wgpu::Buffer,
@@ -13,21 +54,68 @@ pub struct ComputePipelineState {
 
     pub densities_buffer: wgpu::Buffer,
     pub pressures_buffer: wgpu::Buffer,
+    pub accelerations_buffer: wgpu::Buffer,
+    pub previous_accelerations_buffer: wgpu::Buffer,
     pub simulation_params_buffer: wgpu::Buffer,
+    pub interaction_params_buffer: wgpu::Buffer,
+    pub colliders: ColliderBuffers,
+    pub boundary: BoundaryBuffers,
+    boundary_particles: BoundaryParticles,
 
     pub compute_bind_group_0: wgpu::BindGroup,
     pub compute_bind_group_1: wgpu::BindGroup,
     pub compute_bind_group_2: wgpu::BindGroup,
+    pub compute_bind_group_3: wgpu::BindGroup,
+
+    pub neighbor_search: NeighborSearchPipelineState,
+    pub time_step: TimeStepPipelineState,
+    pub rigid_bodies: RigidBodyPipelineState,
+    pub pcisph: PcisphPipelineState,
+    pub dfsph: DfsphPipelineState,
+    pub pressure_solve_state_buffer: wgpu::Buffer,
+
+    compute_bind_group_layout_3: wgpu::BindGroupLayout,
+    particles_len: u32,
+    rigid_bodies_len: u32,
+}
+
+/// Static obstacles, rigid bodies and the polygon vertices both refer to.
+///
+/// Every buffer holds at least one element, since empty bindings are not allowed;
+/// shaders bound their loops by the counts in `SimulationParams` instead of the buffer
+/// lengths.
+pub struct ColliderBuffers {
+    pub obstacles_buffer: wgpu::Buffer,
+    pub obstacle_vertices_buffer: wgpu::Buffer,
+    pub rigid_bodies_buffer: wgpu::Buffer,
+    pub rigid_body_impulses_buffer: wgpu::Buffer,
+}
+
+/// Boundary particles and their cell ranges, padded to one element like
+/// [`ColliderBuffers`]. The rigid body samples follow the static ones, with one range per
+/// body after the hash table slots. The physics kernels skip the static samples unless
+/// `SimulationParams::has_boundary_particles` is set.
+pub struct BoundaryBuffers {
+    pub boundary_particles_buffer: wgpu::Buffer,
+    pub boundary_cell_ranges_buffer: wgpu::Buffer,
 }
 
 impl ComputePipelineState {
     pub fn new(
         device: &wgpu::Device,
         particles: &[Particle],
+        obstacles: &[Obstacle],
+        rigid_bodies: &[RigidBody],
         simulation_params: &SimulationParams,
     ) -> Self {
-        let compute_shader =
-            device.create_shader_module(wgpu::include_wgsl!("../shaders/physics.wgsl"));
+        let compute_shader = create_shader_module(
+            device,
+            "Physics Shader",
+            &format!(
+                "{OBSTACLES_SHADER}\n{SPH_SHADER}\n{}",
+                include_str!("../shaders/physics.wgsl")
+            ),
+        );
 
         let position_x: Vec<f32> = particles.iter().map(|p| p.position_x).collect();
         let position_y: Vec<f32> = particles.iter().map(|p| p.position_y).collect();
@@ -73,15 +161,31 @@ impl ComputePipelineState {
         let densities_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Densities Buffer"),
             contents: bytemuck::cast_slice(&vec![0.0; simulation_params.particles_len as usize]),
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
         });
 
         let pressures_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Pressures Buffer"),
             contents: bytemuck::cast_slice(&vec![0.0; simulation_params.particles_len as usize]),
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
         });
 
+        let accelerations_buffer = storage_buffer(
+            device,
+            "Accelerations Buffer",
+            simulation_params.particles_len as u64 * 8,
+        );
+
+        let previous_accelerations_buffer = storage_buffer(
+            device,
+            "Previous Accelerations Buffer",
+            simulation_params.particles_len as u64 * 8,
+        );
+
         let simulation_params_buffer =
             device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Simulation Params Buffer"),
@@ -89,6 +193,13 @@ impl ComputePipelineState {
                 usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             });
 
+        let interaction_params_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Interaction Params Buffer"),
+                contents: bytemuck::cast_slice(&[InteractionParams::default()]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
         let compute_bind_group_layout_0 =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("Compute Bind Group Layout 0"),
@@ -160,22 +271,30 @@ impl ComputePipelineState {
                         },
                         count: None,
                     },
+                    storage_layout_entry(2, false),
+                    storage_layout_entry(3, false),
                 ],
             });
 
         let compute_bind_group_layout_2 =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("Compute Bind Group Layout 2"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
+                entries: &[uniform_layout_entry(0), uniform_layout_entry(1)],
+            });
+
+        let compute_bind_group_layout_3 =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Compute Bind Group Layout 3"),
+                entries: &[
+                    storage_layout_entry(0, true),
+                    storage_layout_entry(1, true),
+                    storage_layout_entry(2, true),
+                    storage_layout_entry(3, true),
+                    storage_layout_entry(4, true),
+                    storage_layout_entry(5, false),
+                    storage_layout_entry(6, true),
+                    storage_layout_entry(7, true),
+                ],
             });
 
         let compute_pipeline_layout =
@@ -185,39 +304,41 @@ impl ComputePipelineState {
                     &compute_bind_group_layout_0,
                     &compute_bind_group_layout_1,
                     &compute_bind_group_layout_2,
+                    &compute_bind_group_layout_3,
                 ],
                 push_constant_ranges: &[],
             });
 
-        let compute_densities_pipeline =
+        let create_physics_pipeline = |label: &str, entry_point: &str| {
             device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                label: Some("Physics Compute Densities Pipeline"),
+                label: Some(label),
                 layout: Some(&compute_pipeline_layout),
                 module: &compute_shader,
-                entry_point: Some("compute_densities"),
+                entry_point: Some(entry_point),
                 compilation_options: Default::default(),
                 cache: Default::default(),
-            });
+            })
+        };
 
+        let compute_densities_pipeline =
+            create_physics_pipeline("Physics Compute Densities Pipeline", "compute_densities");
         let compute_pressures_pipeline =
-            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                label: Some("Physics Compute Pressures Pipeline"),
-                layout: Some(&compute_pipeline_layout),
-                module: &compute_shader,
-                entry_point: Some("compute_pressures"),
-                compilation_options: Default::default(),
-                cache: Default::default(),
-            });
-
-        let compute_new_positions_pipeline =
-            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                label: Some("Physics Compute Main Pipeline"),
-                layout: Some(&compute_pipeline_layout),
-                module: &compute_shader,
-                entry_point: Some("main"),
-                compilation_options: Default::default(),
-                cache: Default::default(),
-            });
+            create_physics_pipeline("Physics Compute Pressures Pipeline", "compute_pressures");
+        let compute_accelerations_pipeline = create_physics_pipeline(
+            "Physics Compute Accelerations Pipeline",
+            "compute_accelerations",
+        );
+        let compute_non_pressure_accelerations_pipeline = create_physics_pipeline(
+            "Physics Compute Non-Pressure Accelerations Pipeline",
+            "compute_non_pressure_accelerations",
+        );
+        let integrate_pipeline = create_physics_pipeline("Physics Integrate Pipeline", "integrate");
+        let half_kick_pipeline = create_physics_pipeline("Physics Half Kick Pipeline", "half_kick");
+        let drift_pipeline = create_physics_pipeline("Physics Drift Pipeline", "drift");
+        let verlet_predict_pipeline =
+            create_physics_pipeline("Physics Verlet Predict Pipeline", "verlet_predict");
+        let verlet_correct_pipeline =
+            create_physics_pipeline("Physics Verlet Correct Pipeline", "verlet_correct");
 
         let compute_bind_group_0 = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Bind Group 0"),
@@ -254,28 +375,111 @@ impl ComputePipelineState {
                     binding: 1,
                     resource: pressures_buffer.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: accelerations_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: previous_accelerations_buffer.as_entire_binding(),
+                },
             ],
         });
 
         let compute_bind_group_2 = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Bind Group 2"),
             layout: &compute_bind_group_layout_2,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: simulation_params_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: simulation_params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: interaction_params_buffer.as_entire_binding(),
+                },
+            ],
         });
 
+        let neighbor_search = NeighborSearchPipelineState::new(
+            device,
+            &position_x_buffer,
+            &position_y_buffer,
+            &simulation_params_buffer,
+            simulation_params,
+        );
+
+        let time_step = TimeStepPipelineState::new(
+            device,
+            &velocity_x_buffer,
+            &velocity_y_buffer,
+            &accelerations_buffer,
+            &simulation_params_buffer,
+        );
+
+        let colliders = ColliderBuffers::new(device, obstacles, rigid_bodies);
+        let boundary_particles = BoundaryParticles::new(simulation_params, obstacles, rigid_bodies);
+        let boundary = BoundaryBuffers::new(device, &boundary_particles);
+
+        let compute_bind_group_3 = create_bind_group_3(
+            device,
+            &compute_bind_group_layout_3,
+            &neighbor_search,
+            &colliders,
+            &boundary,
+        );
+
+        let rigid_bodies_pipeline_state =
+            RigidBodyPipelineState::new(device, &colliders, &simulation_params_buffer);
+
+        let pressure_solve_state_buffer = storage_buffer(
+            device,
+            "Pressure Solve State Buffer",
+            std::mem::size_of::<PressureSolveState>() as u64,
+        );
+        let shared_layouts = [
+            &compute_bind_group_layout_0,
+            &compute_bind_group_layout_1,
+            &compute_bind_group_layout_2,
+        ];
+        let pcisph = PcisphPipelineState::new(
+            device,
+            shared_layouts,
+            &neighbor_search,
+            &boundary,
+            &pressure_solve_state_buffer,
+            simulation_params.particles_len,
+        );
+        let dfsph = DfsphPipelineState::new(
+            device,
+            shared_layouts,
+            &neighbor_search,
+            &boundary,
+            &pressure_solve_state_buffer,
+            simulation_params.particles_len,
+        );
+
         Self {
             compute_densities_pipeline,
             compute_pressures_pipeline,
-            compute_new_positions_pipeline,
+            compute_accelerations_pipeline,
+            compute_non_pressure_accelerations_pipeline,
+            integrate_pipeline,
+            half_kick_pipeline,
+            drift_pipeline,
+            verlet_predict_pipeline,
+            verlet_correct_pipeline,
 
             compute_bind_group_0,
             compute_bind_group_1,
             compute_bind_group_2,
+            compute_bind_group_3,
 
             simulation_params_buffer,
+            interaction_params_buffer,
+            colliders,
+            boundary,
+            boundary_particles,
 
             position_x_buffer,
             position_y_buffer,
@@ -284,6 +488,386 @@ impl ComputePipelineState {
 
             densities_buffer,
             pressures_buffer,
+            accelerations_buffer,
+            previous_accelerations_buffer,
+
+            neighbor_search,
+            time_step,
+            rigid_bodies: rigid_bodies_pipeline_state,
+            pcisph,
+            dfsph,
+            pressure_solve_state_buffer,
+
+            compute_bind_group_layout_3,
+            particles_len: simulation_params.particles_len,
+            rigid_bodies_len: rigid_bodies.len() as u32,
         }
     }
+
+    /// Replaces the obstacles and rigid bodies. The counts in `SimulationParams` have
+    /// to be updated to match separately.
+    pub fn set_colliders(
+        &mut self,
+        device: &wgpu::Device,
+        obstacles: &[Obstacle],
+        rigid_bodies: &[RigidBody],
+    ) {
+        self.colliders = ColliderBuffers::new(device, obstacles, rigid_bodies);
+        self.rigid_bodies_len = rigid_bodies.len() as u32;
+
+        self.compute_bind_group_3 = create_bind_group_3(
+            device,
+            &self.compute_bind_group_layout_3,
+            &self.neighbor_search,
+            &self.colliders,
+            &self.boundary,
+        );
+        self.rigid_bodies.set_colliders(device, &self.colliders);
+    }
+
+    /// Replaces the boundary particles, which depend on the obstacles and rigid bodies
+    /// as well as the domain and smoothing radius in `simulation_params`. Keeps the
+    /// current ones if none of those changed.
+    pub fn set_boundary(
+        &mut self,
+        device: &wgpu::Device,
+        simulation_params: &SimulationParams,
+        obstacles: &[Obstacle],
+        rigid_bodies: &[RigidBody],
+    ) {
+        if self
+            .boundary_particles
+            .is_sampled_for(simulation_params, obstacles, rigid_bodies)
+        {
+            return;
+        }
+
+        self.boundary_particles =
+            BoundaryParticles::new(simulation_params, obstacles, rigid_bodies);
+        self.boundary = BoundaryBuffers::new(device, &self.boundary_particles);
+
+        self.compute_bind_group_3 = create_bind_group_3(
+            device,
+            &self.compute_bind_group_layout_3,
+            &self.neighbor_search,
+            &self.colliders,
+            &self.boundary,
+        );
+        self.pcisph.set_boundary(
+            device,
+            &self.neighbor_search,
+            &self.boundary,
+            &self.pressure_solve_state_buffer,
+        );
+        self.dfsph.set_boundary(
+            device,
+            &self.neighbor_search,
+            &self.boundary,
+            &self.pressure_solve_state_buffer,
+        );
+    }
+
+    /// Zeroes the reported [`PressureSolveState`], e.g. after switching back to the
+    /// equation of state.
+    pub fn clear_pressure_solve_state(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.pressure_solve_state_buffer,
+            0,
+            bytemuck::bytes_of(&PressureSolveState::default()),
+        );
+    }
+
+    /// Records one solver step: choosing its length, then the pass sequence of the
+    /// pressure solver and integrator in `simulation_params`.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, simulation_params: &SimulationParams) {
+        self.time_step
+            .encode(encoder, &self.simulation_params_buffer);
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Simulation Step Pass"),
+            timestamp_writes: None,
+        });
+        self.dispatch(&mut compute_pass, simulation_params);
+    }
+
+    /// Every kernel runs as its own dispatch, so each one sees the complete results of
+    /// the previous one regardless of invocation order. The rigid bodies move last,
+    /// with the impulses of every particle collision of the step.
+    pub fn dispatch(
+        &self,
+        compute_pass: &mut wgpu::ComputePass<'_>,
+        simulation_params: &SimulationParams,
+    ) {
+        match (
+            simulation_params.pressure_solver(),
+            simulation_params.integrator(),
+        ) {
+            (PressureSolver::Pcisph, _) => {
+                self.bind_physics(compute_pass);
+                self.dispatch_particles(compute_pass, &self.compute_densities_pipeline);
+                self.dispatch_particles(
+                    compute_pass,
+                    &self.compute_non_pressure_accelerations_pipeline,
+                );
+
+                self.pcisph
+                    .dispatch(compute_pass, simulation_params.max_pressure_iterations());
+
+                compute_pass.set_bind_group(3, &self.compute_bind_group_3, &[]);
+                self.dispatch_particles(compute_pass, &self.integrate_pipeline);
+            }
+            (PressureSolver::Dfsph, _) => {
+                let max_iterations = simulation_params.max_pressure_iterations();
+
+                self.bind_physics(compute_pass);
+                self.dispatch_particles(compute_pass, &self.compute_densities_pipeline);
+                self.dfsph
+                    .dispatch_divergence_solve(compute_pass, max_iterations);
+
+                compute_pass.set_bind_group(3, &self.compute_bind_group_3, &[]);
+                self.dispatch_particles(
+                    compute_pass,
+                    &self.compute_non_pressure_accelerations_pipeline,
+                );
+
+                self.dfsph
+                    .dispatch_density_solve(compute_pass, max_iterations);
+
+                compute_pass.set_bind_group(3, &self.compute_bind_group_3, &[]);
+                self.dispatch_particles(compute_pass, &self.drift_pipeline);
+            }
+            (PressureSolver::Wcsph, Integrator::SymplecticEuler) => {
+                self.dispatch_accelerations(compute_pass);
+                self.dispatch_particles(compute_pass, &self.integrate_pipeline);
+            }
+            (PressureSolver::Wcsph, Integrator::Leapfrog) => {
+                self.dispatch_accelerations(compute_pass);
+                self.dispatch_particles(compute_pass, &self.half_kick_pipeline);
+                self.dispatch_particles(compute_pass, &self.drift_pipeline);
+                self.dispatch_accelerations(compute_pass);
+                self.dispatch_particles(compute_pass, &self.half_kick_pipeline);
+            }
+            (PressureSolver::Wcsph, Integrator::VelocityVerlet) => {
+                self.dispatch_accelerations(compute_pass);
+                self.dispatch_particles(compute_pass, &self.verlet_predict_pipeline);
+                self.dispatch_accelerations(compute_pass);
+                self.dispatch_particles(compute_pass, &self.verlet_correct_pipeline);
+            }
+        }
+
+        self.rigid_bodies
+            .dispatch(compute_pass, self.rigid_bodies_len);
+    }
+
+    /// Neighbor search, densities, pressures and accelerations at the current positions
+    /// and velocities.
+    fn dispatch_accelerations(&self, compute_pass: &mut wgpu::ComputePass<'_>) {
+        self.bind_physics(compute_pass);
+
+        self.dispatch_particles(compute_pass, &self.compute_densities_pipeline);
+        self.dispatch_particles(compute_pass, &self.compute_pressures_pipeline);
+        self.dispatch_particles(compute_pass, &self.compute_accelerations_pipeline);
+    }
+
+    /// Neighbor search at the current positions, then the physics bind groups.
+    fn bind_physics(&self, compute_pass: &mut wgpu::ComputePass<'_>) {
+        self.neighbor_search.dispatch(compute_pass);
+
+        compute_pass.set_bind_group(0, &self.compute_bind_group_0, &[]);
+        compute_pass.set_bind_group(1, &self.compute_bind_group_1, &[]);
+        compute_pass.set_bind_group(2, &self.compute_bind_group_2, &[]);
+        compute_pass.set_bind_group(3, &self.compute_bind_group_3, &[]);
+    }
+
+    fn dispatch_particles(
+        &self,
+        compute_pass: &mut wgpu::ComputePass<'_>,
+        pipeline: &wgpu::ComputePipeline,
+    ) {
+        compute_pass.set_pipeline(pipeline);
+        compute_pass.dispatch_workgroups(self.particles_len.div_ceil(64), 1, 1);
+    }
+}
+
+fn create_bind_group_3(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    neighbor_search: &NeighborSearchPipelineState,
+    colliders: &ColliderBuffers,
+    boundary: &BoundaryBuffers,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Bind Group 3"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: neighbor_search.sorted_indices_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: neighbor_search.cell_ranges_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: colliders.obstacles_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: colliders.obstacle_vertices_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: colliders.rigid_bodies_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: colliders.rigid_body_impulses_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: boundary.boundary_particles_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 7,
+                resource: boundary.boundary_cell_ranges_buffer.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+impl ColliderBuffers {
+    /// Uploads the packed obstacles and rigid bodies. Rigid bodies are expected to be
+    /// [`RigidBody::centered`].
+    pub fn new(device: &wgpu::Device, obstacles: &[Obstacle], rigid_bodies: &[RigidBody]) -> Self {
+        let (mut obstacle_records, mut vertices) = Obstacle::pack(obstacles);
+        let mut rigid_body_records = RigidBody::pack(rigid_bodies, &mut vertices);
+        let impulses = vec![RigidBodyImpulse::default(); rigid_bodies.len().max(1)];
+
+        if obstacle_records.is_empty() {
+            obstacle_records.push(Default::default());
+        }
+        if vertices.is_empty() {
+            vertices.push([0.0; 2]);
+        }
+        if rigid_body_records.is_empty() {
+            rigid_body_records.push(Default::default());
+        }
+
+        let create_buffer = |label, contents, usage| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | usage,
+            })
+        };
+
+        Self {
+            obstacles_buffer: create_buffer(
+                "Obstacles Buffer",
+                bytemuck::cast_slice(&obstacle_records),
+                wgpu::BufferUsages::empty(),
+            ),
+            obstacle_vertices_buffer: create_buffer(
+                "Obstacle Vertices Buffer",
+                bytemuck::cast_slice(&vertices),
+                wgpu::BufferUsages::empty(),
+            ),
+            rigid_bodies_buffer: create_buffer(
+                "Rigid Bodies Buffer",
+                bytemuck::cast_slice(&rigid_body_records),
+                wgpu::BufferUsages::COPY_SRC,
+            ),
+            rigid_body_impulses_buffer: create_buffer(
+                "Rigid Body Impulses Buffer",
+                bytemuck::cast_slice(&impulses),
+                wgpu::BufferUsages::empty(),
+            ),
+        }
+    }
+}
+
+impl BoundaryBuffers {
+    pub fn new(device: &wgpu::Device, boundary_particles: &BoundaryParticles) -> Self {
+        let mut particles = boundary_particles.particles.clone();
+        let mut cell_ranges = boundary_particles.cell_ranges.clone();
+
+        for samples in &boundary_particles.rigid_body_samples {
+            let start = particles.len() as u32;
+            particles.extend(samples);
+            cell_ranges.push([start, particles.len() as u32]);
+        }
+
+        if particles.is_empty() {
+            particles.push(Default::default());
+        }
+        if cell_ranges.is_empty() {
+            cell_ranges.push([0; 2]);
+        }
+
+        Self {
+            boundary_particles_buffer: device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("Boundary Particles Buffer"),
+                    contents: bytemuck::cast_slice(&particles),
+                    usage: wgpu::BufferUsages::STORAGE,
+                },
+            ),
+            boundary_cell_ranges_buffer: device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("Boundary Cell Ranges Buffer"),
+                    contents: bytemuck::cast_slice(&cell_ranges),
+                    usage: wgpu::BufferUsages::STORAGE,
+                },
+            ),
+        }
+    }
+}
+
+pub fn create_shader_module(
+    device: &wgpu::Device,
+    label: &str,
+    source: &str,
+) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(format!("{COMMON_SHADER}\n{source}").into()),
+    })
+}
+
+pub fn storage_buffer(device: &wgpu::Device, label: &str, size: u64) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size,
+        usage: wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::COPY_DST
+            | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    })
+}
+
+pub fn storage_layout_entry(binding: u32, read_only: bool) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+pub fn uniform_layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
 }
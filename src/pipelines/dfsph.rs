@@ -0,0 +1,287 @@
+use crate::pipelines::compute::{
+    BoundaryBuffers, SPH_SHADER, create_shader_module, storage_buffer, storage_layout_entry,
+};
+use crate::pipelines::neighbor_search::NeighborSearchPipelineState;
+
+/// Divergence-free and constant-density solves of the DFSPH solver.
+///
+/// Shares bind groups 0 to 2 with the physics pipelines and brings its own group 3 with
+/// the neighbor grid, boundary particles, per-particle factors, pressure changes and the
+/// shared [`PressureSolveState`](crate::pipelines::compute::PressureSolveState). Like
+/// the PCISPH solver, every iteration is recorded up front and skipped on the GPU once
+/// the error is within the tolerance.
+pub struct DfsphPipelineState {
+    compute_factors_pipeline: wgpu::ComputePipeline,
+    compute_divergence_errors_pipeline: wgpu::ComputePipeline,
+    compute_density_errors_pipeline: wgpu::ComputePipeline,
+    check_divergence_convergence_pipeline: wgpu::ComputePipeline,
+    check_density_convergence_pipeline: wgpu::ComputePipeline,
+    update_divergence_kappas_pipeline: wgpu::ComputePipeline,
+    update_density_kappas_pipeline: wgpu::ComputePipeline,
+    apply_kappa_changes_pipeline: wgpu::ComputePipeline,
+    predict_velocities_pipeline: wgpu::ComputePipeline,
+
+    pub factors_buffer: wgpu::Buffer,
+    pub kappa_changes_buffer: wgpu::Buffer,
+
+    bind_group_layout_3: wgpu::BindGroupLayout,
+    bind_group_3: wgpu::BindGroup,
+    particles_len: u32,
+}
+
+impl DfsphPipelineState {
+    /// `shared_layouts` are the physics bind group layouts 0 to 2.
+    pub fn new(
+        device: &wgpu::Device,
+        shared_layouts: [&wgpu::BindGroupLayout; 3],
+        neighbor_search: &NeighborSearchPipelineState,
+        boundary: &BoundaryBuffers,
+        state_buffer: &wgpu::Buffer,
+        particles_len: u32,
+    ) -> Self {
+        let shader = create_shader_module(
+            device,
+            "DFSPH Shader",
+            &format!("{SPH_SHADER}\n{}", include_str!("../shaders/dfsph.wgsl")),
+        );
+
+        let factors_buffer = storage_buffer(device, "Factors Buffer", particles_len as u64 * 4);
+        let kappa_changes_buffer =
+            storage_buffer(device, "Kappa Changes Buffer", particles_len as u64 * 4);
+
+        let bind_group_layout_3 =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("DFSPH Bind Group Layout 3"),
+                entries: &[
+                    storage_layout_entry(0, true),
+                    storage_layout_entry(1, true),
+                    storage_layout_entry(2, false),
+                    storage_layout_entry(3, false),
+                    storage_layout_entry(4, false),
+                    storage_layout_entry(6, true),
+                    storage_layout_entry(7, true),
+                ],
+            });
+
+        let [layout_0, layout_1, layout_2] = shared_layouts;
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("DFSPH Pipeline Layout"),
+            bind_group_layouts: &[layout_0, layout_1, layout_2, &bind_group_layout_3],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label: &str, entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: Default::default(),
+            })
+        };
+
+        let compute_factors_pipeline =
+            create_pipeline("DFSPH Compute Factors Pipeline", "compute_factors");
+        let compute_divergence_errors_pipeline = create_pipeline(
+            "DFSPH Compute Divergence Errors Pipeline",
+            "compute_divergence_errors",
+        );
+        let compute_density_errors_pipeline = create_pipeline(
+            "DFSPH Compute Density Errors Pipeline",
+            "compute_density_errors",
+        );
+        let check_divergence_convergence_pipeline = create_pipeline(
+            "DFSPH Check Divergence Convergence Pipeline",
+            "check_divergence_convergence",
+        );
+        let check_density_convergence_pipeline = create_pipeline(
+            "DFSPH Check Density Convergence Pipeline",
+            "check_density_convergence",
+        );
+        let update_divergence_kappas_pipeline = create_pipeline(
+            "DFSPH Update Divergence Kappas Pipeline",
+            "update_divergence_kappas",
+        );
+        let update_density_kappas_pipeline = create_pipeline(
+            "DFSPH Update Density Kappas Pipeline",
+            "update_density_kappas",
+        );
+        let apply_kappa_changes_pipeline =
+            create_pipeline("DFSPH Apply Kappa Changes Pipeline", "apply_kappa_changes");
+        let predict_velocities_pipeline =
+            create_pipeline("DFSPH Predict Velocities Pipeline", "predict_velocities");
+
+        let bind_group_3 = create_bind_group_3(
+            device,
+            &bind_group_layout_3,
+            neighbor_search,
+            boundary,
+            &factors_buffer,
+            &kappa_changes_buffer,
+            state_buffer,
+        );
+
+        Self {
+            compute_factors_pipeline,
+            compute_divergence_errors_pipeline,
+            compute_density_errors_pipeline,
+            check_divergence_convergence_pipeline,
+            check_density_convergence_pipeline,
+            update_divergence_kappas_pipeline,
+            update_density_kappas_pipeline,
+            apply_kappa_changes_pipeline,
+            predict_velocities_pipeline,
+
+            factors_buffer,
+            kappa_changes_buffer,
+
+            bind_group_layout_3,
+            bind_group_3,
+            particles_len,
+        }
+    }
+
+    /// Rebinds the buffers after [`BoundaryBuffers`] have been recreated.
+    pub fn set_boundary(
+        &mut self,
+        device: &wgpu::Device,
+        neighbor_search: &NeighborSearchPipelineState,
+        boundary: &BoundaryBuffers,
+        state_buffer: &wgpu::Buffer,
+    ) {
+        self.bind_group_3 = create_bind_group_3(
+            device,
+            &self.bind_group_layout_3,
+            neighbor_search,
+            boundary,
+            &self.factors_buffer,
+            &self.kappa_changes_buffer,
+            state_buffer,
+        );
+    }
+
+    /// Computes the factors, then records up to `max_iterations` corrections of the
+    /// current velocities and a final check of the divergence error. Expects the
+    /// neighbor grid and densities of the solver step, with the physics bind groups 0
+    /// to 2 set. Leaves its own group 3 bound.
+    pub fn dispatch_divergence_solve(
+        &self,
+        compute_pass: &mut wgpu::ComputePass<'_>,
+        max_iterations: u32,
+    ) {
+        compute_pass.set_bind_group(3, &self.bind_group_3, &[]);
+
+        self.dispatch_particles(compute_pass, &self.compute_factors_pipeline);
+
+        for _ in 0..max_iterations {
+            self.dispatch_check(
+                compute_pass,
+                &self.compute_divergence_errors_pipeline,
+                &self.check_divergence_convergence_pipeline,
+            );
+            self.dispatch_particles(compute_pass, &self.update_divergence_kappas_pipeline);
+            self.dispatch_particles(compute_pass, &self.apply_kappa_changes_pipeline);
+        }
+        self.dispatch_check(
+            compute_pass,
+            &self.compute_divergence_errors_pipeline,
+            &self.check_divergence_convergence_pipeline,
+        );
+    }
+
+    /// Adds the non-pressure accelerations to the velocities, then records up to
+    /// `max_iterations` corrections and a final check of the density error. Expects
+    /// the state left by [`Self::dispatch_divergence_solve`] and the non-pressure
+    /// accelerations. Leaves its own group 3 bound.
+    pub fn dispatch_density_solve(
+        &self,
+        compute_pass: &mut wgpu::ComputePass<'_>,
+        max_iterations: u32,
+    ) {
+        compute_pass.set_bind_group(3, &self.bind_group_3, &[]);
+
+        self.dispatch_particles(compute_pass, &self.predict_velocities_pipeline);
+
+        for _ in 0..max_iterations {
+            self.dispatch_check(
+                compute_pass,
+                &self.compute_density_errors_pipeline,
+                &self.check_density_convergence_pipeline,
+            );
+            self.dispatch_particles(compute_pass, &self.update_density_kappas_pipeline);
+            self.dispatch_particles(compute_pass, &self.apply_kappa_changes_pipeline);
+        }
+        self.dispatch_check(
+            compute_pass,
+            &self.compute_density_errors_pipeline,
+            &self.check_density_convergence_pipeline,
+        );
+    }
+
+    fn dispatch_check(
+        &self,
+        compute_pass: &mut wgpu::ComputePass<'_>,
+        compute_errors_pipeline: &wgpu::ComputePipeline,
+        check_convergence_pipeline: &wgpu::ComputePipeline,
+    ) {
+        self.dispatch_particles(compute_pass, compute_errors_pipeline);
+
+        compute_pass.set_pipeline(check_convergence_pipeline);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+    }
+
+    fn dispatch_particles(
+        &self,
+        compute_pass: &mut wgpu::ComputePass<'_>,
+        pipeline: &wgpu::ComputePipeline,
+    ) {
+        compute_pass.set_pipeline(pipeline);
+        compute_pass.dispatch_workgroups(self.particles_len.div_ceil(64), 1, 1);
+    }
+}
+
+fn create_bind_group_3(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    neighbor_search: &NeighborSearchPipelineState,
+    boundary: &BoundaryBuffers,
+    factors_buffer: &wgpu::Buffer,
+    kappa_changes_buffer: &wgpu::Buffer,
+    state_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("DFSPH Bind Group 3"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: neighbor_search.sorted_indices_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: neighbor_search.cell_ranges_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: factors_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: kappa_changes_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: state_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: boundary.boundary_particles_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 7,
+                resource: boundary.boundary_cell_ranges_buffer.as_entire_binding(),
+            },
+        ],
+    })
+}
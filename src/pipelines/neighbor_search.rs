@@ -0,0 +1,200 @@
+use crate::pipelines::compute::{
+    create_shader_module, storage_buffer, storage_layout_entry, uniform_layout_entry,
+};
+use crate::pipelines::radix_sort::RadixSort;
+use crate::simulation::SimulationParams;
+
+const WORKGROUP_SIZE: u32 = 256;
+
+pub struct NeighborSearchPipelineState {
+    calculate_hashes_pipeline: wgpu::ComputePipeline,
+    clear_cell_ranges_pipeline: wgpu::ComputePipeline,
+    find_cell_ranges_pipeline: wgpu::ComputePipeline,
+
+    radix_sort: RadixSort,
+
+    pub sorted_indices_buffer: wgpu::Buffer,
+    pub cell_ranges_buffer: wgpu::Buffer,
+
+    bind_group_0: wgpu::BindGroup,
+    bind_group_1: wgpu::BindGroup,
+    bind_group_2: wgpu::BindGroup,
+
+    particles_len: u32,
+    hash_table_size: u32,
+}
+
+impl NeighborSearchPipelineState {
+    pub fn new(
+        device: &wgpu::Device,
+        position_x_buffer: &wgpu::Buffer,
+        position_y_buffer: &wgpu::Buffer,
+        simulation_params_buffer: &wgpu::Buffer,
+        simulation_params: &SimulationParams,
+    ) -> Self {
+        let shader = create_shader_module(
+            device,
+            "Neighbor Search Shader",
+            include_str!("../shaders/neighbor_search.wgsl"),
+        );
+
+        let particles_len = simulation_params.particles_len;
+        let hash_table_size = simulation_params.hash_table_size;
+
+        let cell_keys_buffer = storage_buffer(device, "Cell Keys Buffer", particles_len as u64 * 4);
+        let sorted_indices_buffer =
+            storage_buffer(device, "Sorted Indices Buffer", particles_len as u64 * 4);
+        let cell_ranges_buffer =
+            storage_buffer(device, "Cell Ranges Buffer", hash_table_size as u64 * 8);
+
+        let radix_sort = RadixSort::new(
+            device,
+            &cell_keys_buffer,
+            &sorted_indices_buffer,
+            particles_len,
+            hash_table_size.trailing_zeros(),
+        );
+
+        let bind_group_layout_0 =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Neighbor Search Bind Group Layout 0"),
+                entries: &[storage_layout_entry(0, true), storage_layout_entry(1, true)],
+            });
+
+        let bind_group_layout_1 =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Neighbor Search Bind Group Layout 1"),
+                entries: &[
+                    storage_layout_entry(0, false),
+                    storage_layout_entry(1, false),
+                    storage_layout_entry(2, false),
+                ],
+            });
+
+        let bind_group_layout_2 =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Neighbor Search Bind Group Layout 2"),
+                entries: &[uniform_layout_entry(0)],
+            });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Neighbor Search Pipeline Layout"),
+            bind_group_layouts: &[
+                &bind_group_layout_0,
+                &bind_group_layout_1,
+                &bind_group_layout_2,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label: &str, entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: Default::default(),
+            })
+        };
+
+        let calculate_hashes_pipeline = create_pipeline(
+            "Neighbor Search Calculate Hashes Pipeline",
+            "calculate_hashes",
+        );
+        let clear_cell_ranges_pipeline = create_pipeline(
+            "Neighbor Search Clear Cell Ranges Pipeline",
+            "clear_cell_ranges",
+        );
+        let find_cell_ranges_pipeline = create_pipeline(
+            "Neighbor Search Find Cell Ranges Pipeline",
+            "find_cell_ranges",
+        );
+
+        let bind_group_0 = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Neighbor Search Bind Group 0"),
+            layout: &bind_group_layout_0,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: position_x_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: position_y_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let bind_group_1 = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Neighbor Search Bind Group 1"),
+            layout: &bind_group_layout_1,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: cell_keys_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: sorted_indices_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: cell_ranges_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let bind_group_2 = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Neighbor Search Bind Group 2"),
+            layout: &bind_group_layout_2,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: simulation_params_buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            calculate_hashes_pipeline,
+            clear_cell_ranges_pipeline,
+            find_cell_ranges_pipeline,
+
+            radix_sort,
+
+            sorted_indices_buffer,
+            cell_ranges_buffer,
+
+            bind_group_0,
+            bind_group_1,
+            bind_group_2,
+
+            particles_len,
+            hash_table_size,
+        }
+    }
+
+    pub fn dispatch(&self, compute_pass: &mut wgpu::ComputePass<'_>) {
+        let particle_workgroups = self.particles_len.div_ceil(WORKGROUP_SIZE);
+        let cell_workgroups = self.hash_table_size.div_ceil(WORKGROUP_SIZE);
+
+        self.set_bind_groups(compute_pass);
+
+        compute_pass.set_pipeline(&self.calculate_hashes_pipeline);
+        compute_pass.dispatch_workgroups(particle_workgroups, 1, 1);
+
+        compute_pass.set_pipeline(&self.clear_cell_ranges_pipeline);
+        compute_pass.dispatch_workgroups(cell_workgroups, 1, 1);
+
+        self.radix_sort.dispatch(compute_pass);
+        self.set_bind_groups(compute_pass);
+
+        compute_pass.set_pipeline(&self.find_cell_ranges_pipeline);
+        compute_pass.dispatch_workgroups(particle_workgroups, 1, 1);
+    }
+
+    fn set_bind_groups(&self, compute_pass: &mut wgpu::ComputePass<'_>) {
+        compute_pass.set_bind_group(0, &self.bind_group_0, &[]);
+        compute_pass.set_bind_group(1, &self.bind_group_1, &[]);
+        compute_pass.set_bind_group(2, &self.bind_group_2, &[]);
+    }
+}
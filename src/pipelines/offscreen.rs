@@ -0,0 +1,136 @@
+use crate::camera::Camera;
+use crate::pipelines::render::RenderPipelineState;
+use crate::simulation::Simulation;
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+const BYTES_PER_PIXEL: u32 = 4;
+
+/// RGBA8 image read back from an [`OffscreenRenderer`], rows top to bottom.
+pub struct CapturedImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Renders the particles into a texture of fixed size instead of a swapchain image,
+/// so captures do not depend on the window size or on presentation timing.
+pub struct OffscreenRenderer {
+    render_pipeline_state: RenderPipelineState,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    readback_buffer: wgpu::Buffer,
+    padded_bytes_per_row: u32,
+}
+
+impl OffscreenRenderer {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> anyhow::Result<Self> {
+        let max_dimension = device.limits().max_texture_dimension_2d;
+        if width == 0 || height == 0 || width > max_dimension || height > max_dimension {
+            anyhow::bail!(
+                "Capture size {width}x{height} must be between 1x1 and {max_dimension}x{max_dimension}"
+            );
+        }
+
+        let render_pipeline_state = RenderPipelineState::new(device, FORMAT);
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let padded_bytes_per_row =
+            (width * BYTES_PER_PIXEL).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Offscreen Readback Buffer"),
+            size: padded_bytes_per_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Ok(Self {
+            render_pipeline_state,
+            texture,
+            view,
+            readback_buffer,
+            padded_bytes_per_row,
+        })
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.texture.width(), self.texture.height())
+    }
+
+    /// Draws the current state of `simulation` and reads the image back, blocking until
+    /// the GPU is done.
+    pub fn capture(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        simulation: &Simulation,
+    ) -> anyhow::Result<CapturedImage> {
+        let (width, height) = self.size();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Offscreen Render Encoder"),
+        });
+
+        let camera = Camera::new(simulation.params(), width, height);
+
+        self.render_pipeline_state
+            .write_view_transform(queue, &camera.view_transform());
+        self.render_pipeline_state
+            .draw(device, &mut encoder, &self.view, simulation);
+
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &self.readback_buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            self.texture.size(),
+        );
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = self.readback_buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::PollType::Wait)?;
+        receiver.recv()??;
+
+        let row_size = (width * BYTES_PER_PIXEL) as usize;
+        let data = slice.get_mapped_range();
+        let pixels = data
+            .chunks_exact(self.padded_bytes_per_row as usize)
+            .flat_map(|row| &row[..row_size])
+            .copied()
+            .collect();
+
+        drop(data);
+        self.readback_buffer.unmap();
+
+        Ok(CapturedImage {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
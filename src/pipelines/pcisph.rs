@@ -0,0 +1,250 @@
+use crate::pipelines::compute::{
+    BoundaryBuffers, SPH_SHADER, create_shader_module, storage_buffer, storage_layout_entry,
+};
+use crate::pipelines::neighbor_search::NeighborSearchPipelineState;
+
+/// Pressure correction loop of the PCISPH solver.
+///
+/// Shares bind groups 0 to 2 with the physics pipelines and brings its own group 3 with
+/// the neighbor grid, boundary particles, predicted positions and densities, pressure
+/// accelerations and the shared [`PressureSolveState`](crate::pipelines::compute::PressureSolveState).
+/// Every iteration is recorded up front; the kernels
+/// skip their work on the GPU once the density error is within the tolerance.
+pub struct PcisphPipelineState {
+    initialize_pipeline: wgpu::ComputePipeline,
+    predict_positions_pipeline: wgpu::ComputePipeline,
+    predict_densities_pipeline: wgpu::ComputePipeline,
+    check_convergence_pipeline: wgpu::ComputePipeline,
+    update_pressures_pipeline: wgpu::ComputePipeline,
+    compute_pressure_accelerations_pipeline: wgpu::ComputePipeline,
+    apply_pressure_accelerations_pipeline: wgpu::ComputePipeline,
+
+    pub predicted_positions_buffer: wgpu::Buffer,
+    pub pressure_accelerations_buffer: wgpu::Buffer,
+    pub predicted_densities_buffer: wgpu::Buffer,
+
+    bind_group_layout_3: wgpu::BindGroupLayout,
+    bind_group_3: wgpu::BindGroup,
+    particles_len: u32,
+}
+
+impl PcisphPipelineState {
+    /// `shared_layouts` are the physics bind group layouts 0 to 2.
+    pub fn new(
+        device: &wgpu::Device,
+        shared_layouts: [&wgpu::BindGroupLayout; 3],
+        neighbor_search: &NeighborSearchPipelineState,
+        boundary: &BoundaryBuffers,
+        state_buffer: &wgpu::Buffer,
+        particles_len: u32,
+    ) -> Self {
+        let shader = create_shader_module(
+            device,
+            "PCISPH Shader",
+            &format!("{SPH_SHADER}\n{}", include_str!("../shaders/pcisph.wgsl")),
+        );
+
+        let predicted_positions_buffer = storage_buffer(
+            device,
+            "Predicted Positions Buffer",
+            particles_len as u64 * 8,
+        );
+        let pressure_accelerations_buffer = storage_buffer(
+            device,
+            "Pressure Accelerations Buffer",
+            particles_len as u64 * 8,
+        );
+        let predicted_densities_buffer = storage_buffer(
+            device,
+            "Predicted Densities Buffer",
+            particles_len as u64 * 4,
+        );
+
+        let bind_group_layout_3 =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("PCISPH Bind Group Layout 3"),
+                entries: &[
+                    storage_layout_entry(0, true),
+                    storage_layout_entry(1, true),
+                    storage_layout_entry(2, false),
+                    storage_layout_entry(3, false),
+                    storage_layout_entry(4, false),
+                    storage_layout_entry(5, false),
+                    storage_layout_entry(6, true),
+                    storage_layout_entry(7, true),
+                ],
+            });
+
+        let [layout_0, layout_1, layout_2] = shared_layouts;
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("PCISPH Pipeline Layout"),
+            bind_group_layouts: &[layout_0, layout_1, layout_2, &bind_group_layout_3],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label: &str, entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: Default::default(),
+            })
+        };
+
+        let initialize_pipeline = create_pipeline("PCISPH Initialize Pipeline", "initialize");
+        let predict_positions_pipeline =
+            create_pipeline("PCISPH Predict Positions Pipeline", "predict_positions");
+        let predict_densities_pipeline =
+            create_pipeline("PCISPH Predict Densities Pipeline", "predict_densities");
+        let check_convergence_pipeline =
+            create_pipeline("PCISPH Check Convergence Pipeline", "check_convergence");
+        let update_pressures_pipeline =
+            create_pipeline("PCISPH Update Pressures Pipeline", "update_pressures");
+        let compute_pressure_accelerations_pipeline = create_pipeline(
+            "PCISPH Compute Pressure Accelerations Pipeline",
+            "compute_pressure_accelerations",
+        );
+        let apply_pressure_accelerations_pipeline = create_pipeline(
+            "PCISPH Apply Pressure Accelerations Pipeline",
+            "apply_pressure_accelerations",
+        );
+
+        let bind_group_3 = create_bind_group_3(
+            device,
+            &bind_group_layout_3,
+            neighbor_search,
+            boundary,
+            &predicted_positions_buffer,
+            &pressure_accelerations_buffer,
+            &predicted_densities_buffer,
+            state_buffer,
+        );
+
+        Self {
+            initialize_pipeline,
+            predict_positions_pipeline,
+            predict_densities_pipeline,
+            check_convergence_pipeline,
+            update_pressures_pipeline,
+            compute_pressure_accelerations_pipeline,
+            apply_pressure_accelerations_pipeline,
+
+            predicted_positions_buffer,
+            pressure_accelerations_buffer,
+            predicted_densities_buffer,
+
+            bind_group_layout_3,
+            bind_group_3,
+            particles_len,
+        }
+    }
+
+    /// Rebinds the buffers after [`BoundaryBuffers`] have been recreated.
+    pub fn set_boundary(
+        &mut self,
+        device: &wgpu::Device,
+        neighbor_search: &NeighborSearchPipelineState,
+        boundary: &BoundaryBuffers,
+        state_buffer: &wgpu::Buffer,
+    ) {
+        self.bind_group_3 = create_bind_group_3(
+            device,
+            &self.bind_group_layout_3,
+            neighbor_search,
+            boundary,
+            &self.predicted_positions_buffer,
+            &self.pressure_accelerations_buffer,
+            &self.predicted_densities_buffer,
+            state_buffer,
+        );
+    }
+
+    /// Records up to `max_iterations` corrections and a final check of the density
+    /// error, then adds the pressure accelerations to `accelerations`. Expects the
+    /// neighbor grid, densities and non-pressure accelerations of the solver step, with
+    /// the physics bind groups 0 to 2 set. Leaves its own group 3 bound.
+    pub fn dispatch(&self, compute_pass: &mut wgpu::ComputePass<'_>, max_iterations: u32) {
+        compute_pass.set_bind_group(3, &self.bind_group_3, &[]);
+
+        self.dispatch_particles(compute_pass, &self.initialize_pipeline);
+
+        for _ in 0..max_iterations {
+            self.dispatch_prediction(compute_pass);
+            self.dispatch_particles(compute_pass, &self.update_pressures_pipeline);
+            self.dispatch_particles(compute_pass, &self.compute_pressure_accelerations_pipeline);
+        }
+        self.dispatch_prediction(compute_pass);
+
+        self.dispatch_particles(compute_pass, &self.apply_pressure_accelerations_pipeline);
+    }
+
+    fn dispatch_prediction(&self, compute_pass: &mut wgpu::ComputePass<'_>) {
+        self.dispatch_particles(compute_pass, &self.predict_positions_pipeline);
+        self.dispatch_particles(compute_pass, &self.predict_densities_pipeline);
+
+        compute_pass.set_pipeline(&self.check_convergence_pipeline);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+    }
+
+    fn dispatch_particles(
+        &self,
+        compute_pass: &mut wgpu::ComputePass<'_>,
+        pipeline: &wgpu::ComputePipeline,
+    ) {
+        compute_pass.set_pipeline(pipeline);
+        compute_pass.dispatch_workgroups(self.particles_len.div_ceil(64), 1, 1);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_bind_group_3(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    neighbor_search: &NeighborSearchPipelineState,
+    boundary: &BoundaryBuffers,
+    predicted_positions_buffer: &wgpu::Buffer,
+    pressure_accelerations_buffer: &wgpu::Buffer,
+    predicted_densities_buffer: &wgpu::Buffer,
+    state_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("PCISPH Bind Group 3"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: neighbor_search.sorted_indices_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: neighbor_search.cell_ranges_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: predicted_positions_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: pressure_accelerations_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: state_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: predicted_densities_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: boundary.boundary_particles_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 7,
+                resource: boundary.boundary_cell_ranges_buffer.as_entire_binding(),
+            },
+        ],
+    })
+}
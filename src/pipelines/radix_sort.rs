@@ -0,0 +1,317 @@
+use std::num::NonZeroU64;
+
+use wgpu::util::DeviceExt;
+
+use crate::pipelines::compute::{storage_buffer, storage_layout_entry};
+
+const WORKGROUP_SIZE: u32 = 256;
+const BLOCK_SIZE: u32 = 512;
+const BITS_PER_PASS: u32 = 2;
+const MAX_PASSES: u32 = u32::BITS / BITS_PER_PASS;
+const PARAMS_STRIDE: u64 = 256;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct RadixSortParams {
+    radix_mask_id: u32,
+    len: u32,
+    num_blocks: u32,
+    _padding: u32,
+}
+
+/// Stable LSD radix sort of `u32` keys with a `u32` payload, two bits per pass.
+///
+/// The sorter is bound to one pair of key/value buffers and a fixed length at
+/// construction. [`RadixSort::dispatch`] records every pass into an existing compute
+/// pass and leaves the sorted keys and values in the original buffers, so callers can
+/// keep their own bind groups pointing at them.
+pub struct RadixSort {
+    compute_local_digit_scans_pipeline: wgpu::ComputePipeline,
+    compute_local_prefix_sum_pipeline: wgpu::ComputePipeline,
+    compute_block_sums_pipeline: wgpu::ComputePipeline,
+    add_block_sums_pipeline: wgpu::ComputePipeline,
+    scatter_pipeline: wgpu::ComputePipeline,
+
+    bind_groups_0: [wgpu::BindGroup; 2],
+    bind_group_1: wgpu::BindGroup,
+    bind_group_2: wgpu::BindGroup,
+
+    len: u32,
+    num_blocks: u32,
+    scan_blocks: u32,
+    passes: u32,
+}
+
+impl RadixSort {
+    /// Largest `len` supported, bounded by the per-dimension workgroup count limit.
+    pub const MAX_LEN: u32 = 65535 * WORKGROUP_SIZE;
+
+    /// Creates a sorter for the first `len` entries of `keys_buffer` and `values_buffer`.
+    ///
+    /// Only the low `key_bits` bits of each key are compared; pass `u32::BITS` to sort
+    /// full keys. Both buffers need `STORAGE` usage and room for `len` elements.
+    pub fn new(
+        device: &wgpu::Device,
+        keys_buffer: &wgpu::Buffer,
+        values_buffer: &wgpu::Buffer,
+        len: u32,
+        key_bits: u32,
+    ) -> Self {
+        assert!(len <= Self::MAX_LEN, "radix sort length {len} is too large");
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/radix_sort.wgsl"));
+
+        let num_blocks = len.div_ceil(BLOCK_SIZE).max(1);
+        let scan_blocks = (4 * num_blocks).div_ceil(BLOCK_SIZE);
+        // An even number of passes brings the ping-pong back to the caller's buffers.
+        let passes = key_bits
+            .min(u32::BITS)
+            .div_ceil(BITS_PER_PASS)
+            .next_multiple_of(2)
+            .max(2);
+
+        let element_size = len.max(1) as u64 * 4;
+        let keys_temp_buffer = storage_buffer(device, "Radix Sort Keys Temp Buffer", element_size);
+        let values_temp_buffer =
+            storage_buffer(device, "Radix Sort Values Temp Buffer", element_size);
+        let local_scans_buffer = storage_buffer(
+            device,
+            "Radix Sort Local Scans Buffer",
+            (num_blocks * BLOCK_SIZE) as u64 * 16,
+        );
+        let sums_buffer = storage_buffer(
+            device,
+            "Radix Sort Sums Buffer",
+            (scan_blocks * BLOCK_SIZE) as u64 * 4,
+        );
+        let block_sums_buffer = storage_buffer(
+            device,
+            "Radix Sort Block Sums Buffer",
+            BLOCK_SIZE as u64 * 4,
+        );
+
+        let params: Vec<u8> = (0..MAX_PASSES)
+            .flat_map(|radix_mask_id| {
+                let mut bytes = bytemuck::bytes_of(&RadixSortParams {
+                    radix_mask_id,
+                    len,
+                    num_blocks,
+                    _padding: 0,
+                })
+                .to_vec();
+                bytes.resize(PARAMS_STRIDE as usize, 0);
+                bytes
+            })
+            .collect();
+
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Radix Sort Params Buffer"),
+            contents: &params,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let bind_group_layout_0 =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Radix Sort Bind Group Layout 0"),
+                entries: &[
+                    storage_layout_entry(0, true),
+                    storage_layout_entry(1, true),
+                    storage_layout_entry(2, false),
+                    storage_layout_entry(3, false),
+                ],
+            });
+
+        let bind_group_layout_1 =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Radix Sort Bind Group Layout 1"),
+                entries: &[
+                    storage_layout_entry(0, false),
+                    storage_layout_entry(1, false),
+                    storage_layout_entry(2, false),
+                ],
+            });
+
+        let params_size = NonZeroU64::new(std::mem::size_of::<RadixSortParams>() as u64);
+
+        let bind_group_layout_2 =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Radix Sort Bind Group Layout 2"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: params_size,
+                    },
+                    count: None,
+                }],
+            });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Radix Sort Pipeline Layout"),
+            bind_group_layouts: &[
+                &bind_group_layout_0,
+                &bind_group_layout_1,
+                &bind_group_layout_2,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label: &str, entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: Default::default(),
+            })
+        };
+
+        let compute_local_digit_scans_pipeline = create_pipeline(
+            "Radix Sort Local Digit Scans Pipeline",
+            "compute_local_digit_scans",
+        );
+        let compute_local_prefix_sum_pipeline = create_pipeline(
+            "Radix Sort Local Prefix Sum Pipeline",
+            "compute_local_prefix_sum",
+        );
+        let compute_block_sums_pipeline =
+            create_pipeline("Radix Sort Block Sums Pipeline", "compute_block_sums");
+        let add_block_sums_pipeline = create_pipeline(
+            "Radix Sort Add Block Sums Pipeline",
+            "add_block_sums_to_prefix_sum_output",
+        );
+        let scatter_pipeline = create_pipeline("Radix Sort Scatter Pipeline", "scatter");
+
+        let create_bind_group_0 = |label: &str,
+                                   keys_input: &wgpu::Buffer,
+                                   values_input: &wgpu::Buffer,
+                                   keys_output: &wgpu::Buffer,
+                                   values_output: &wgpu::Buffer| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout: &bind_group_layout_0,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: keys_input.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: values_input.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: keys_output.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: values_output.as_entire_binding(),
+                    },
+                ],
+            })
+        };
+
+        let bind_groups_0 = [
+            create_bind_group_0(
+                "Radix Sort Bind Group 0 Forward",
+                keys_buffer,
+                values_buffer,
+                &keys_temp_buffer,
+                &values_temp_buffer,
+            ),
+            create_bind_group_0(
+                "Radix Sort Bind Group 0 Backward",
+                &keys_temp_buffer,
+                &values_temp_buffer,
+                keys_buffer,
+                values_buffer,
+            ),
+        ];
+
+        let bind_group_1 = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Radix Sort Bind Group 1"),
+            layout: &bind_group_layout_1,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: local_scans_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: sums_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: block_sums_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let bind_group_2 = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Radix Sort Bind Group 2"),
+            layout: &bind_group_layout_2,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &params_buffer,
+                    offset: 0,
+                    size: params_size,
+                }),
+            }],
+        });
+
+        Self {
+            compute_local_digit_scans_pipeline,
+            compute_local_prefix_sum_pipeline,
+            compute_block_sums_pipeline,
+            add_block_sums_pipeline,
+            scatter_pipeline,
+
+            bind_groups_0,
+            bind_group_1,
+            bind_group_2,
+
+            len,
+            num_blocks,
+            scan_blocks,
+            passes,
+        }
+    }
+
+    /// Records the full sort into `compute_pass`.
+    ///
+    /// Bind groups 0..=2 are overwritten, so callers must rebind their own groups
+    /// before dispatching anything else in the same pass.
+    pub fn dispatch(&self, compute_pass: &mut wgpu::ComputePass<'_>) {
+        if self.len == 0 {
+            return;
+        }
+
+        compute_pass.set_bind_group(1, &self.bind_group_1, &[]);
+
+        for pass in 0..self.passes {
+            let params_offset = (pass as u64 * PARAMS_STRIDE) as wgpu::DynamicOffset;
+
+            compute_pass.set_bind_group(0, &self.bind_groups_0[pass as usize % 2], &[]);
+            compute_pass.set_bind_group(2, &self.bind_group_2, &[params_offset]);
+
+            compute_pass.set_pipeline(&self.compute_local_digit_scans_pipeline);
+            compute_pass.dispatch_workgroups(self.num_blocks, 1, 1);
+
+            compute_pass.set_pipeline(&self.compute_local_prefix_sum_pipeline);
+            compute_pass.dispatch_workgroups(self.scan_blocks, 1, 1);
+
+            compute_pass.set_pipeline(&self.compute_block_sums_pipeline);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+
+            compute_pass.set_pipeline(&self.add_block_sums_pipeline);
+            compute_pass.dispatch_workgroups(self.scan_blocks, 1, 1);
+
+            compute_pass.set_pipeline(&self.scatter_pipeline);
+            compute_pass.dispatch_workgroups(self.len.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+    }
+}
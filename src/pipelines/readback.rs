@@ -0,0 +1,235 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::pipelines::compute::{ComputePipelineState, PressureSolveState};
+use crate::pipelines::time_step::TimeStepState;
+use crate::simulation::Particle;
+
+const SLOT_FREE: u8 = 0;
+const SLOT_PENDING: u8 = 1;
+const SLOT_READY: u8 = 2;
+const SLOT_FAILED: u8 = 3;
+
+const FIELDS: u64 = 6;
+const STATE_SIZE: u64 = std::mem::size_of::<TimeStepState>() as u64;
+const SOLVE_STATE_SIZE: u64 = std::mem::size_of::<PressureSolveState>() as u64;
+
+/// Particle state copied back from the GPU after `step` simulation steps, `time`
+/// seconds into the simulation. `time_step` is the length of the last solver step.
+///
+/// `density_error` and `pressure_iterations` describe the pressure solve of the last
+/// solver step; both stay zero with `PressureSolver::Wcsph`. `divergence_error` and
+/// `divergence_iterations` do the same for the divergence solve of `PressureSolver::Dfsph`.
+#[derive(Clone, Debug)]
+pub struct ParticleFrame {
+    pub step: u64,
+    pub time: f64,
+    pub time_step: f32,
+    pub density_error: f32,
+    pub pressure_iterations: u32,
+    pub divergence_error: f32,
+    pub divergence_iterations: u32,
+    pub particles: Vec<Particle>,
+    pub densities: Vec<f32>,
+    pub pressures: Vec<f32>,
+}
+
+struct ReadbackSlot {
+    buffer: wgpu::Buffer,
+    state: Arc<AtomicU8>,
+    ticket: u64,
+    step: u64,
+}
+
+impl ReadbackSlot {
+    fn is_mapped(&self) -> bool {
+        matches!(self.state.load(Ordering::Acquire), SLOT_READY | SLOT_FAILED)
+    }
+}
+
+/// Ring of staging buffers used to copy particle buffers back without stalling the GPU.
+///
+/// Each request copies the SoA particle buffers, densities, pressures, the time step
+/// state and the pressure solve state into a free staging buffer and maps it
+/// asynchronously. Mapped frames are collected with [`ParticleReadback::take`] once the
+/// device has been polled.
+pub struct ParticleReadback {
+    slots: Vec<ReadbackSlot>,
+    particles_len: usize,
+    next_ticket: u64,
+}
+
+impl ParticleReadback {
+    pub fn new(device: &wgpu::Device, particles_len: u32, slots: usize) -> Self {
+        let field_size = particles_len as u64 * std::mem::size_of::<f32>() as u64;
+
+        let slots = (0..slots)
+            .map(|i| ReadbackSlot {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&format!("Particle Readback Buffer {i}")),
+                    size: field_size * FIELDS + STATE_SIZE + SOLVE_STATE_SIZE,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                state: Arc::new(AtomicU8::new(SLOT_FREE)),
+                ticket: 0,
+                step: 0,
+            })
+            .collect();
+
+        Self {
+            slots,
+            particles_len: particles_len as usize,
+            next_ticket: 0,
+        }
+    }
+
+    /// Copies the current particle state into a free slot and starts mapping it.
+    ///
+    /// Returns a ticket identifying the request, or `None` when every slot is still in
+    /// flight or holds a frame that has not been taken yet.
+    pub fn request(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        compute_pipeline_state: &ComputePipelineState,
+        step: u64,
+    ) -> Option<u64> {
+        let slot = self
+            .slots
+            .iter_mut()
+            .find(|slot| slot.state.load(Ordering::Acquire) == SLOT_FREE)?;
+
+        let field_size = (self.particles_len * std::mem::size_of::<f32>()) as u64;
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Particle Readback Encoder"),
+        });
+
+        for (i, buffer) in [
+            &compute_pipeline_state.position_x_buffer,
+            &compute_pipeline_state.position_y_buffer,
+            &compute_pipeline_state.velocity_x_buffer,
+            &compute_pipeline_state.velocity_y_buffer,
+            &compute_pipeline_state.densities_buffer,
+            &compute_pipeline_state.pressures_buffer,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            encoder.copy_buffer_to_buffer(
+                buffer,
+                0,
+                &slot.buffer,
+                field_size * i as u64,
+                field_size,
+            );
+        }
+
+        encoder.copy_buffer_to_buffer(
+            &compute_pipeline_state.time_step.state_buffer,
+            0,
+            &slot.buffer,
+            field_size * FIELDS,
+            STATE_SIZE,
+        );
+
+        encoder.copy_buffer_to_buffer(
+            &compute_pipeline_state.pressure_solve_state_buffer,
+            0,
+            &slot.buffer,
+            field_size * FIELDS + STATE_SIZE,
+            SOLVE_STATE_SIZE,
+        );
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        slot.state.store(SLOT_PENDING, Ordering::Release);
+        slot.ticket = self.next_ticket;
+        slot.step = step;
+        self.next_ticket += 1;
+
+        let state = Arc::clone(&slot.state);
+        slot.buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let new_state = if result.is_ok() {
+                    SLOT_READY
+                } else {
+                    SLOT_FAILED
+                };
+                state.store(new_state, Ordering::Release);
+            });
+
+        Some(slot.ticket)
+    }
+
+    /// Takes the oldest mapped frame, if any. The device must have been polled first.
+    pub fn take(&mut self) -> anyhow::Result<Option<ParticleFrame>> {
+        let oldest = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.is_mapped())
+            .min_by_key(|(_, slot)| slot.ticket)
+            .map(|(i, _)| i);
+
+        match oldest {
+            Some(i) => self.take_slot(i).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Takes the frame for `ticket` if it has been mapped.
+    pub fn take_ticket(&mut self, ticket: u64) -> anyhow::Result<Option<ParticleFrame>> {
+        let slot = self
+            .slots
+            .iter()
+            .position(|slot| slot.ticket == ticket && slot.is_mapped());
+
+        match slot {
+            Some(i) => self.take_slot(i).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn take_slot(&mut self, i: usize) -> anyhow::Result<ParticleFrame> {
+        let slot = &self.slots[i];
+
+        if slot.state.load(Ordering::Acquire) == SLOT_FAILED {
+            slot.state.store(SLOT_FREE, Ordering::Release);
+            anyhow::bail!("Mapping particle readback buffer {i} failed");
+        }
+
+        let len = self.particles_len;
+        let data = slot.buffer.slice(..).get_mapped_range();
+        let (fields, states) = data.split_at(len * FIELDS as usize * std::mem::size_of::<f32>());
+        let (state, solve_state) = states.split_at(STATE_SIZE as usize);
+        let values: &[f32] = bytemuck::cast_slice(fields);
+        let field = |index: usize| &values[index * len..(index + 1) * len];
+        let state: TimeStepState = bytemuck::pod_read_unaligned(state);
+        let solve_state: PressureSolveState = bytemuck::pod_read_unaligned(solve_state);
+
+        let particles = (0..len)
+            .map(|j| Particle::new([field(0)[j], field(1)[j]], [field(2)[j], field(3)[j]]))
+            .collect();
+
+        let frame = ParticleFrame {
+            step: slot.step,
+            time: state.time(),
+            time_step: state.time_step,
+            density_error: solve_state.density_error,
+            pressure_iterations: solve_state.iterations,
+            divergence_error: solve_state.divergence_error,
+            divergence_iterations: solve_state.divergence_iterations,
+            particles,
+            densities: field(4).to_vec(),
+            pressures: field(5).to_vec(),
+        };
+
+        drop(data);
+        slot.buffer.unmap();
+        slot.state.store(SLOT_FREE, Ordering::Release);
+
+        Ok(frame)
+    }
+}
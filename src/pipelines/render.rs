@@ -1,5 +1,6 @@
-use crate::constants::{INDICES, VERTICES};
-use crate::simulation::Particle;
+use crate::constants::{BACKGROUND_COLOR, INDICES, VERTICES};
+use crate::pipelines::compute::{OBSTACLES_SHADER, create_shader_module};
+use crate::simulation::{Particle, Simulation};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -11,10 +12,6 @@ impl Vertex {
         Self { position }
     }
 
-    pub fn new(position: [f32; 2]) -> Self {
-        Self { position }
-    }
-
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         const ATTRIBUTES: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![
             0 => Float32x2,
@@ -28,21 +25,87 @@ impl Vertex {
 }
 use wgpu::util::DeviceExt;
 
+/// Camera uniform of the render pipeline: maps world-space positions to clip space as
+/// `world * scale + offset`. Built by [`crate::camera::Camera`].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ViewTransform {
+    pub scale: [f32; 2],
+    pub offset: [f32; 2],
+    /// Diameter of a drawn particle in world units.
+    pub particle_size: f32,
+    _padding: [f32; 3],
+}
+
+impl ViewTransform {
+    pub fn new(scale: [f32; 2], offset: [f32; 2], particle_size: f32) -> Self {
+        Self {
+            scale,
+            offset,
+            particle_size,
+            _padding: [0.0; 3],
+        }
+    }
+
+    pub fn clip_to_world(&self, clip: [f32; 2]) -> [f32; 2] {
+        [
+            (clip[0] - self.offset[0]) / self.scale[0],
+            (clip[1] - self.offset[1]) / self.scale[1],
+        ]
+    }
+}
+
 pub struct RenderPipelineState {
     pub render_pipeline: wgpu::RenderPipeline,
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub num_indices: u32,
+    pub view_transform_buffer: wgpu::Buffer,
+    view_transform_bind_group: wgpu::BindGroup,
+    obstacle_pipeline: wgpu::RenderPipeline,
+    obstacle_bind_group_layout: wgpu::BindGroupLayout,
+    obstacle_params_bind_group_layout: wgpu::BindGroupLayout,
 }
 
 impl RenderPipelineState {
-    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
         let shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/shader.wgsl"));
 
+        let view_transform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("View Transform Buffer"),
+            size: std::mem::size_of::<ViewTransform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let view_transform_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("View Transform Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let view_transform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("View Transform Bind Group"),
+            layout: &view_transform_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: view_transform_buffer.as_entire_binding(),
+            }],
+        });
+
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[],
+                bind_group_layouts: &[&view_transform_bind_group_layout],
                 push_constant_ranges: &[],
             });
 
@@ -62,7 +125,7 @@ impl RenderPipelineState {
                 module: &shader,
                 entry_point: Some("fs_main"),
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
+                    format,
                     blend: Some(wgpu::BlendState::REPLACE),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
@@ -83,6 +146,89 @@ impl RenderPipelineState {
             cache: None,
         });
 
+        // Prefixed with the common declarations for the obstacle and rigid body counts.
+        let obstacle_shader = create_shader_module(
+            device,
+            "Obstacle Render Shader",
+            &format!(
+                "{OBSTACLES_SHADER}\n{}",
+                include_str!("../shaders/obstacles_render.wgsl")
+            ),
+        );
+
+        let obstacle_storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let obstacle_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Obstacle Bind Group Layout"),
+                entries: &[
+                    obstacle_storage_entry(0),
+                    obstacle_storage_entry(1),
+                    obstacle_storage_entry(2),
+                ],
+            });
+
+        let obstacle_params_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Obstacle Params Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let obstacle_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Obstacle Render Pipeline Layout"),
+                bind_group_layouts: &[
+                    &view_transform_bind_group_layout,
+                    &obstacle_bind_group_layout,
+                    &obstacle_params_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let obstacle_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Obstacle Render Pipeline"),
+            layout: Some(&obstacle_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &obstacle_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &obstacle_shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
         let num_indices = INDICES.len() as u32;
 
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -102,6 +248,108 @@ impl RenderPipelineState {
             vertex_buffer,
             index_buffer,
             num_indices,
+            view_transform_buffer,
+            view_transform_bind_group,
+            obstacle_pipeline,
+            obstacle_bind_group_layout,
+            obstacle_params_bind_group_layout,
         }
     }
+
+    /// Sets the transform used by the following draws.
+    pub fn write_view_transform(&self, queue: &wgpu::Queue, view_transform: &ViewTransform) {
+        queue.write_buffer(
+            &self.view_transform_buffer,
+            0,
+            bytemuck::cast_slice(&[*view_transform]),
+        );
+    }
+
+    /// Records a pass that clears `view` and draws every particle of `simulation`, then
+    /// its obstacles and rigid bodies on top.
+    ///
+    /// `view` must use the texture format the pipeline was created with.
+    pub fn draw(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        simulation: &Simulation,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: BACKGROUND_COLOR[0],
+                        g: BACKGROUND_COLOR[1],
+                        b: BACKGROUND_COLOR[2],
+                        a: 1.0,
+                    }),
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.view_transform_bind_group, &[]);
+
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        let compute_pipeline_state = simulation.compute_pipeline_state();
+        render_pass.set_vertex_buffer(1, compute_pipeline_state.position_x_buffer.slice(..));
+        render_pass.set_vertex_buffer(2, compute_pipeline_state.position_y_buffer.slice(..));
+        render_pass.set_vertex_buffer(3, compute_pipeline_state.velocity_x_buffer.slice(..));
+        render_pass.set_vertex_buffer(4, compute_pipeline_state.velocity_y_buffer.slice(..));
+
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+
+        render_pass.draw_indexed(0..self.num_indices, 0, 0..simulation.particles_len());
+
+        if simulation.obstacles().is_empty() && simulation.rigid_bodies().is_empty() {
+            return;
+        }
+
+        // Bound per frame, since restoring a checkpoint may replace the collider buffers.
+        let colliders = &compute_pipeline_state.colliders;
+        let obstacle_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Obstacle Bind Group"),
+            layout: &self.obstacle_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: colliders.obstacles_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: colliders.obstacle_vertices_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: colliders.rigid_bodies_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let obstacle_params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Obstacle Params Bind Group"),
+            layout: &self.obstacle_params_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: compute_pipeline_state
+                    .simulation_params_buffer
+                    .as_entire_binding(),
+            }],
+        });
+
+        render_pass.set_pipeline(&self.obstacle_pipeline);
+        render_pass.set_bind_group(1, &obstacle_bind_group, &[]);
+        render_pass.set_bind_group(2, &obstacle_params_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
 }
@@ -0,0 +1,161 @@
+use crate::pipelines::compute::{
+    ColliderBuffers, OBSTACLES_SHADER, create_shader_module, storage_layout_entry,
+    uniform_layout_entry,
+};
+
+/// Advances the rigid bodies on the GPU once per solver step.
+///
+/// The physics kernels accumulate the impulses particles exert on every body; the
+/// `integrate_rigid_bodies` kernel applies and clears them, adds gravity, moves the
+/// bodies and resolves their contacts with the domain walls.
+pub struct RigidBodyPipelineState {
+    integrate_rigid_bodies_pipeline: wgpu::ComputePipeline,
+
+    bind_group_layout_0: wgpu::BindGroupLayout,
+    bind_group_layout_1: wgpu::BindGroupLayout,
+
+    bind_group_0: wgpu::BindGroup,
+    bind_group_1: wgpu::BindGroup,
+    bind_group_2: wgpu::BindGroup,
+}
+
+impl RigidBodyPipelineState {
+    pub fn new(
+        device: &wgpu::Device,
+        colliders: &ColliderBuffers,
+        simulation_params_buffer: &wgpu::Buffer,
+    ) -> Self {
+        let shader = create_shader_module(
+            device,
+            "Rigid Body Shader",
+            &format!(
+                "{OBSTACLES_SHADER}\n{}",
+                include_str!("../shaders/rigid_bodies.wgsl")
+            ),
+        );
+
+        let bind_group_layout_0 =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Rigid Body Bind Group Layout 0"),
+                entries: &[
+                    storage_layout_entry(0, false),
+                    storage_layout_entry(1, false),
+                ],
+            });
+
+        let bind_group_layout_1 =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Rigid Body Bind Group Layout 1"),
+                entries: &[storage_layout_entry(0, true)],
+            });
+
+        let bind_group_layout_2 =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Rigid Body Bind Group Layout 2"),
+                entries: &[uniform_layout_entry(0)],
+            });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Rigid Body Pipeline Layout"),
+            bind_group_layouts: &[
+                &bind_group_layout_0,
+                &bind_group_layout_1,
+                &bind_group_layout_2,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let integrate_rigid_bodies_pipeline =
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Integrate Rigid Bodies Pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some("integrate_rigid_bodies"),
+                compilation_options: Default::default(),
+                cache: Default::default(),
+            });
+
+        let (bind_group_0, bind_group_1) = create_collider_bind_groups(
+            device,
+            &bind_group_layout_0,
+            &bind_group_layout_1,
+            colliders,
+        );
+
+        let bind_group_2 = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Rigid Body Bind Group 2"),
+            layout: &bind_group_layout_2,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: simulation_params_buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            integrate_rigid_bodies_pipeline,
+
+            bind_group_layout_0,
+            bind_group_layout_1,
+
+            bind_group_0,
+            bind_group_1,
+            bind_group_2,
+        }
+    }
+
+    /// Rebinds the buffers after [`ColliderBuffers`] have been recreated.
+    pub fn set_colliders(&mut self, device: &wgpu::Device, colliders: &ColliderBuffers) {
+        (self.bind_group_0, self.bind_group_1) = create_collider_bind_groups(
+            device,
+            &self.bind_group_layout_0,
+            &self.bind_group_layout_1,
+            colliders,
+        );
+    }
+
+    /// Must come after the dispatch that moves the particles of the solver step.
+    pub fn dispatch(&self, compute_pass: &mut wgpu::ComputePass<'_>, rigid_bodies_len: u32) {
+        if rigid_bodies_len == 0 {
+            return;
+        }
+
+        compute_pass.set_pipeline(&self.integrate_rigid_bodies_pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group_0, &[]);
+        compute_pass.set_bind_group(1, &self.bind_group_1, &[]);
+        compute_pass.set_bind_group(2, &self.bind_group_2, &[]);
+        compute_pass.dispatch_workgroups(rigid_bodies_len.div_ceil(64), 1, 1);
+    }
+}
+
+fn create_collider_bind_groups(
+    device: &wgpu::Device,
+    layout_0: &wgpu::BindGroupLayout,
+    layout_1: &wgpu::BindGroupLayout,
+    colliders: &ColliderBuffers,
+) -> (wgpu::BindGroup, wgpu::BindGroup) {
+    let bind_group_0 = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Rigid Body Bind Group 0"),
+        layout: layout_0,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: colliders.rigid_bodies_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: colliders.rigid_body_impulses_buffer.as_entire_binding(),
+            },
+        ],
+    });
+
+    let bind_group_1 = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Rigid Body Bind Group 1"),
+        layout: layout_1,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: colliders.obstacle_vertices_buffer.as_entire_binding(),
+        }],
+    });
+
+    (bind_group_0, bind_group_1)
+}
@@ -0,0 +1,198 @@
+use crate::pipelines::compute::{
+    create_shader_module, storage_buffer, storage_layout_entry, uniform_layout_entry,
+};
+use crate::simulation::SimulationParams;
+
+const NANOS_PER_SECOND: f64 = 1.0e9;
+
+/// Mirror of `TimeStepState` in `time_step.wgsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TimeStepState {
+    pub time_step: f32,
+    pub max_speed: f32,
+    pub max_acceleration: f32,
+    _padding: u32,
+    elapsed_nanos: [u32; 2],
+}
+
+impl TimeStepState {
+    pub fn new(time: f64) -> Self {
+        let nanos = (time * NANOS_PER_SECOND).round() as u64;
+
+        Self {
+            elapsed_nanos: [nanos as u32, (nanos >> 32) as u32],
+            ..Default::default()
+        }
+    }
+
+    /// Simulated time in seconds, summed over every solver step on the GPU.
+    pub fn time(&self) -> f64 {
+        let [low, high] = self.elapsed_nanos;
+        ((high as u64) << 32 | low as u64) as f64 / NANOS_PER_SECOND
+    }
+}
+
+/// Picks the length of the next solver step on the GPU.
+///
+/// A single workgroup reduces the largest particle speed and acceleration, derives the
+/// step from them and advances the simulated time. [`TimeStepPipelineState::encode`]
+/// then copies the step into `SimulationParams::solver_time_step`, so the physics
+/// kernels never wait for the CPU.
+pub struct TimeStepPipelineState {
+    compute_time_step_pipeline: wgpu::ComputePipeline,
+
+    pub state_buffer: wgpu::Buffer,
+
+    bind_group_0: wgpu::BindGroup,
+    bind_group_1: wgpu::BindGroup,
+    bind_group_2: wgpu::BindGroup,
+}
+
+impl TimeStepPipelineState {
+    pub fn new(
+        device: &wgpu::Device,
+        velocity_x_buffer: &wgpu::Buffer,
+        velocity_y_buffer: &wgpu::Buffer,
+        accelerations_buffer: &wgpu::Buffer,
+        simulation_params_buffer: &wgpu::Buffer,
+    ) -> Self {
+        let shader = create_shader_module(
+            device,
+            "Time Step Shader",
+            include_str!("../shaders/time_step.wgsl"),
+        );
+
+        let state_buffer = storage_buffer(
+            device,
+            "Time Step State Buffer",
+            std::mem::size_of::<TimeStepState>() as u64,
+        );
+
+        let bind_group_layout_0 =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Time Step Bind Group Layout 0"),
+                entries: &[
+                    storage_layout_entry(0, true),
+                    storage_layout_entry(1, true),
+                    storage_layout_entry(2, true),
+                ],
+            });
+
+        let bind_group_layout_1 =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Time Step Bind Group Layout 1"),
+                entries: &[storage_layout_entry(0, false)],
+            });
+
+        let bind_group_layout_2 =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Time Step Bind Group Layout 2"),
+                entries: &[uniform_layout_entry(0)],
+            });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Time Step Pipeline Layout"),
+            bind_group_layouts: &[
+                &bind_group_layout_0,
+                &bind_group_layout_1,
+                &bind_group_layout_2,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let compute_time_step_pipeline =
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Compute Time Step Pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some("compute_time_step"),
+                compilation_options: Default::default(),
+                cache: Default::default(),
+            });
+
+        let bind_group_0 = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Time Step Bind Group 0"),
+            layout: &bind_group_layout_0,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: velocity_x_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: velocity_y_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: accelerations_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let bind_group_1 = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Time Step Bind Group 1"),
+            layout: &bind_group_layout_1,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: state_buffer.as_entire_binding(),
+            }],
+        });
+
+        let bind_group_2 = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Time Step Bind Group 2"),
+            layout: &bind_group_layout_2,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: simulation_params_buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            compute_time_step_pipeline,
+
+            state_buffer,
+
+            bind_group_0,
+            bind_group_1,
+            bind_group_2,
+        }
+    }
+
+    /// Records the reduction and the copy of the chosen step into the uniform buffer.
+    /// Must run in its own pass before the solver step that uses it.
+    pub fn encode(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        simulation_params_buffer: &wgpu::Buffer,
+    ) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Time Step Pass"),
+            timestamp_writes: None,
+        });
+
+        compute_pass.set_pipeline(&self.compute_time_step_pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group_0, &[]);
+        compute_pass.set_bind_group(1, &self.bind_group_1, &[]);
+        compute_pass.set_bind_group(2, &self.bind_group_2, &[]);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+        drop(compute_pass);
+
+        encoder.copy_buffer_to_buffer(
+            &self.state_buffer,
+            std::mem::offset_of!(TimeStepState, time_step) as u64,
+            simulation_params_buffer,
+            SimulationParams::SOLVER_TIME_STEP_OFFSET,
+            std::mem::size_of::<f32>() as u64,
+        );
+    }
+
+    /// Overwrites the simulated time, e.g. when restoring a checkpoint.
+    pub fn write_time(&self, queue: &wgpu::Queue, time: f64) {
+        queue.write_buffer(
+            &self.state_buffer,
+            0,
+            bytemuck::cast_slice(&[TimeStepState::new(time)]),
+        );
+    }
+}
@@ -0,0 +1,210 @@
+/// Pause, single-step and time-scale state for driving a simulation in real time.
+///
+/// Wall-clock time is accumulated and converted into whole steps of the fixed
+/// simulation time step, so the simulated result does not depend on the frame rate.
+/// The time scale never changes the solver time step either; it only scales the wall
+/// time fed into the accumulator.
+#[derive(Clone, Debug)]
+pub struct Playback {
+    is_paused: bool,
+    time_scale: f32,
+    queued_steps: u32,
+    accumulator: f64,
+    max_steps_per_frame: u32,
+    has_dropped_steps: bool,
+}
+
+impl Playback {
+    /// Speeds offered by [`Playback::slower`] and [`Playback::faster`].
+    pub const TIME_SCALES: [f32; 9] = [0.0625, 0.125, 0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+
+    pub const DEFAULT_MAX_STEPS_PER_FRAME: u32 = 4;
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    pub fn set_paused(&mut self, is_paused: bool) {
+        self.is_paused = is_paused;
+        self.accumulator = 0.0;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.set_paused(!self.is_paused);
+    }
+
+    /// Pauses and queues exactly one step for the next frame.
+    pub fn step_once(&mut self) {
+        self.set_paused(true);
+        self.queued_steps += 1;
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    pub fn set_time_scale(&mut self, time_scale: f32) -> anyhow::Result<()> {
+        if !(time_scale.is_finite() && time_scale > 0.0) {
+            anyhow::bail!("Time scale must be a positive number, got {time_scale}");
+        }
+
+        self.time_scale = time_scale;
+
+        Ok(())
+    }
+
+    /// Switches to the next slower entry of [`Playback::TIME_SCALES`].
+    pub fn slower(&mut self) {
+        if let Some(time_scale) = Self::TIME_SCALES
+            .iter()
+            .rev()
+            .find(|time_scale| **time_scale < self.time_scale)
+        {
+            self.time_scale = *time_scale;
+        }
+    }
+
+    /// Switches to the next faster entry of [`Playback::TIME_SCALES`].
+    pub fn faster(&mut self) {
+        if let Some(time_scale) = Self::TIME_SCALES
+            .iter()
+            .find(|time_scale| **time_scale > self.time_scale)
+        {
+            self.time_scale = *time_scale;
+        }
+    }
+
+    pub fn max_steps_per_frame(&self) -> u32 {
+        self.max_steps_per_frame
+    }
+
+    /// Cap on the steps of one frame at the current time scale. Speeding up raises it
+    /// along with the steps a frame needs, so a fast time scale is only limited by how
+    /// quickly the steps actually run.
+    pub fn scaled_max_steps_per_frame(&self) -> u32 {
+        (self.max_steps_per_frame as f32 * self.time_scale.max(1.0)).ceil() as u32
+    }
+
+    /// Caps how many steps a single frame may run at a time scale of 1. Once the
+    /// simulation falls further behind than that, the backlog is dropped instead of
+    /// growing without bound.
+    pub fn set_max_steps_per_frame(&mut self, max_steps_per_frame: u32) {
+        self.max_steps_per_frame = max_steps_per_frame.max(1);
+    }
+
+    /// Accumulates `elapsed` wall-clock seconds and returns how many steps of
+    /// `time_step` simulated seconds to run for the current frame.
+    pub fn advance(&mut self, elapsed: f64, time_step: f32) -> u32 {
+        let queued_steps = std::mem::take(&mut self.queued_steps);
+
+        if self.is_paused {
+            return queued_steps;
+        }
+
+        let time_step = time_step as f64;
+        self.accumulator += elapsed * self.time_scale as f64;
+
+        let max_steps = self.scaled_max_steps_per_frame();
+        let steps = (self.accumulator / time_step).floor();
+        if steps > max_steps as f64 {
+            if self.has_dropped_steps {
+                log::debug!("Simulation is {steps} steps behind, dropping all but {max_steps}");
+            } else {
+                log::warn!(
+                    "Simulation is {steps} steps behind, dropping all but {max_steps}; \
+                     playback is slower than {}x real time while it cannot keep up",
+                    self.time_scale
+                );
+                self.has_dropped_steps = true;
+            }
+            self.accumulator = 0.0;
+            return max_steps + queued_steps;
+        }
+
+        self.accumulator -= steps * time_step;
+
+        steps as u32 + queued_steps
+    }
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self {
+            is_paused: false,
+            time_scale: 1.0,
+            queued_steps: 0,
+            accumulator: 0.0,
+            max_steps_per_frame: Self::DEFAULT_MAX_STEPS_PER_FRAME,
+            has_dropped_steps: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A power of two, so the accumulated times below are exact.
+    const TIME_STEP: f32 = 1.0 / 16.0;
+
+    fn playback(time_scale: f32) -> Playback {
+        let mut playback = Playback::default();
+        playback.set_time_scale(time_scale).unwrap();
+        playback
+    }
+
+    #[test]
+    fn runs_whole_steps_and_carries_the_remainder() {
+        let mut playback = playback(1.0);
+
+        assert_eq!(playback.advance(0.1875, TIME_STEP), 3);
+        assert_eq!(playback.advance(0.03125, TIME_STEP), 0);
+        assert_eq!(playback.advance(0.03125, TIME_STEP), 1);
+    }
+
+    #[test]
+    fn scales_elapsed_time() {
+        let mut slow = playback(0.25);
+        assert_eq!(slow.advance(0.125, TIME_STEP), 0);
+        assert_eq!(slow.advance(0.125, TIME_STEP), 1);
+
+        let mut fast = playback(4.0);
+        assert_eq!(fast.advance(0.125, TIME_STEP), 8);
+    }
+
+    #[test]
+    fn cap_scales_with_the_time_scale() {
+        for (time_scale, max_steps) in [(0.25, 4), (1.0, 4), (2.0, 8), (16.0, 64)] {
+            assert_eq!(playback(time_scale).scaled_max_steps_per_frame(), max_steps);
+        }
+
+        // 32 steps fit under the cap at 16x, 128 do not.
+        let mut playback = playback(16.0);
+        assert_eq!(playback.advance(0.125, TIME_STEP), 32);
+        assert!(!playback.has_dropped_steps);
+        assert_eq!(playback.advance(0.5, TIME_STEP), 64);
+        assert!(playback.has_dropped_steps);
+    }
+
+    #[test]
+    fn drops_the_backlog_beyond_the_cap() {
+        let mut playback = playback(1.0);
+
+        assert_eq!(playback.advance(1.0, TIME_STEP), 4);
+        assert!(playback.has_dropped_steps);
+        assert_eq!(playback.advance(0.0, TIME_STEP), 0);
+        assert_eq!(playback.advance(0.0625, TIME_STEP), 1);
+    }
+
+    #[test]
+    fn paused_playback_only_runs_queued_steps() {
+        let mut playback = playback(1.0);
+
+        playback.step_once();
+        assert_eq!(playback.advance(1.0, TIME_STEP), 1);
+        assert_eq!(playback.advance(1.0, TIME_STEP), 0);
+
+        playback.set_paused(false);
+        assert_eq!(playback.advance(0.125, TIME_STEP), 2);
+    }
+}
@@ -0,0 +1,102 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::pipelines::offscreen::{CapturedImage, OffscreenRenderer};
+use crate::simulation::Simulation;
+
+/// Renders every `stride`-th simulation step offscreen and writes it as a numbered PNG.
+///
+/// Frames are numbered consecutively from zero (`frame_000000.png`, ...) regardless of
+/// the stride, which is what video encoders expect from an image sequence.
+pub struct FrameRecorder {
+    renderer: OffscreenRenderer,
+    dir: PathBuf,
+    stride: u64,
+    next_frame: u32,
+    last_recorded_step: Option<u64>,
+}
+
+impl FrameRecorder {
+    pub fn new(
+        device: &wgpu::Device,
+        dir: &Path,
+        size: (u32, u32),
+        stride: u32,
+    ) -> anyhow::Result<Self> {
+        if stride == 0 {
+            anyhow::bail!("Recording stride must be at least 1");
+        }
+
+        let renderer = OffscreenRenderer::new(device, size.0, size.1)?;
+
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create recording directory {}", dir.display()))?;
+
+        Ok(Self {
+            renderer,
+            dir: dir.to_path_buf(),
+            stride: stride as u64,
+            next_frame: 0,
+            last_recorded_step: None,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Captures `simulation` if it sits on a recorded step that has not been written yet.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        simulation: &Simulation,
+    ) -> anyhow::Result<()> {
+        let step = simulation.current_step();
+
+        if self.wants(step) && self.last_recorded_step != Some(step) {
+            self.record(device, queue, simulation)?;
+            self.last_recorded_step = Some(step);
+        }
+
+        Ok(())
+    }
+
+    /// Whether the frame after `step` simulation steps should be recorded.
+    pub fn wants(&self, step: u64) -> bool {
+        step.is_multiple_of(self.stride)
+    }
+
+    /// Captures `simulation` unconditionally and returns the path of the written frame.
+    pub fn record(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        simulation: &Simulation,
+    ) -> anyhow::Result<PathBuf> {
+        let image = self.renderer.capture(device, queue, simulation)?;
+        let path = self.dir.join(format!("frame_{:06}.png", self.next_frame));
+
+        write_png(&path, &image).with_context(|| format!("Failed to write {}", path.display()))?;
+        self.next_frame += 1;
+
+        Ok(path)
+    }
+}
+
+fn write_png(path: &Path, image: &CapturedImage) -> anyhow::Result<()> {
+    let file = File::create(path)?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), image.width, image.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image.pixels)?;
+    writer.finish()?;
+
+    Ok(())
+}
@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+
+use crate::obstacle::{GpuObstacle, Obstacle, dot, rotate, sub};
+
+/// Outline of a rigid body in its local frame, centred on the body position.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum BodyShape {
+    Disc {
+        radius: f32,
+    },
+    Box {
+        half_size: [f32; 2],
+    },
+    /// Convex polygon. The vertices are re-centred on its centroid when the body is
+    /// created, moving `position` along with them.
+    Polygon {
+        vertices: Vec<[f32; 2]>,
+    },
+}
+
+/// Dynamic rigid body, two-way coupled with the fluid.
+///
+/// The outline is sampled with boundary particles like the walls, which move with the
+/// body and push on nearby particles with their pressure and drag them along with the
+/// surface velocity. Particles that still hit the body are pushed out and reflected
+/// like at an obstacle, relative to the moving surface. The body takes the opposite
+/// momentum of both. Bodies fall with `gravity / rest_density` and collide with the
+/// domain walls; they pass through obstacles and each other.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RigidBody {
+    pub shape: BodyShape,
+    /// Mass per unit area, in the units of `rest_density`. Bodies lighter than the fluid
+    /// float and heavier ones sink.
+    pub density: f32,
+    pub position: [f32; 2],
+    /// Counter-clockwise rotation in radians.
+    #[serde(default)]
+    pub angle: f32,
+    #[serde(default)]
+    pub velocity: [f32; 2],
+    #[serde(default)]
+    pub angular_velocity: f32,
+}
+
+/// Mirror of `RigidBody` in `obstacles.wgsl`. `shape` is the outline in the local frame.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuRigidBody {
+    shape: GpuObstacle,
+    position: [f32; 2],
+    angle: f32,
+    inverse_mass: f32,
+    velocity: [f32; 2],
+    angular_velocity: f32,
+    inverse_inertia: f32,
+}
+
+/// Mirror of `RigidBodyImpulse` in `obstacles.wgsl`: the impulse accumulated since the
+/// last `integrate_rigid_bodies`, as x, y and angular components in 32.32 fixed point
+/// split over a fraction and a whole word.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct RigidBodyImpulse {
+    pub fractions: [u32; 3],
+    pub wholes: [i32; 3],
+}
+
+impl RigidBody {
+    /// Moves a polygon's centroid to the local origin, so the body turns about its
+    /// centre of mass. Other shapes are already centred.
+    pub fn centered(&self) -> Self {
+        let BodyShape::Polygon { vertices } = &self.shape else {
+            return self.clone();
+        };
+
+        let centroid = polygon_mass_properties(vertices).1;
+        let offset = rotate(centroid, self.angle);
+
+        Self {
+            shape: BodyShape::Polygon {
+                vertices: vertices
+                    .iter()
+                    .map(|vertex| sub(*vertex, centroid))
+                    .collect(),
+            },
+            position: [self.position[0] + offset[0], self.position[1] + offset[1]],
+            ..self.clone()
+        }
+    }
+
+    pub fn area(&self) -> f32 {
+        match &self.shape {
+            BodyShape::Disc { radius } => std::f32::consts::PI * radius * radius,
+            BodyShape::Box { half_size } => 4.0 * half_size[0] * half_size[1],
+            BodyShape::Polygon { vertices } => polygon_mass_properties(vertices).0,
+        }
+    }
+
+    pub fn mass(&self) -> f32 {
+        self.density * self.area()
+    }
+
+    /// Moment of inertia about the local origin.
+    pub fn inertia(&self) -> f32 {
+        let mass = self.mass();
+
+        match &self.shape {
+            BodyShape::Disc { radius } => 0.5 * mass * radius * radius,
+            BodyShape::Box { half_size } => {
+                mass * (half_size[0] * half_size[0] + half_size[1] * half_size[1]) / 3.0
+            }
+            BodyShape::Polygon { vertices } => self.density * polygon_mass_properties(vertices).2,
+        }
+    }
+
+    /// Outline as an obstacle in the local frame.
+    pub fn local_shape(&self) -> Obstacle {
+        match &self.shape {
+            BodyShape::Disc { radius } => Obstacle::Circle {
+                center: [0.0; 2],
+                radius: *radius,
+            },
+            BodyShape::Box { half_size } => Obstacle::Box {
+                center: [0.0; 2],
+                half_size: *half_size,
+                rotation: 0.0,
+            },
+            BodyShape::Polygon { vertices } => Obstacle::Polygon {
+                vertices: vertices.clone(),
+            },
+        }
+    }
+
+    /// Inverse of [`RigidBody::local_shape`], for reading packed bodies back.
+    pub fn shape_from_local(obstacle: Obstacle) -> anyhow::Result<BodyShape> {
+        match obstacle {
+            Obstacle::Circle { radius, .. } => Ok(BodyShape::Disc { radius }),
+            Obstacle::Box { half_size, .. } => Ok(BodyShape::Box { half_size }),
+            Obstacle::Polygon { vertices } => Ok(BodyShape::Polygon { vertices }),
+            Obstacle::Capsule { .. } => anyhow::bail!("Rigid bodies cannot be capsules"),
+        }
+    }
+
+    /// Converts a world-space point into the local frame.
+    pub fn to_local(&self, point: [f32; 2]) -> [f32; 2] {
+        rotate(sub(point, self.position), -self.angle)
+    }
+
+    /// Velocity of the material point of the body at world-space `point`.
+    pub fn velocity_at(&self, point: [f32; 2]) -> [f32; 2] {
+        let arm = sub(point, self.position);
+
+        [
+            self.velocity[0] - self.angular_velocity * arm[1],
+            self.velocity[1] + self.angular_velocity * arm[0],
+        ]
+    }
+
+    /// World-space point of the outline furthest along `direction`.
+    pub fn support(&self, direction: [f32; 2]) -> [f32; 2] {
+        let local_direction = rotate(direction, -self.angle);
+        let local = match &self.shape {
+            BodyShape::Disc { radius } => {
+                [radius * local_direction[0], radius * local_direction[1]]
+            }
+            BodyShape::Box { half_size } => [
+                if local_direction[0] >= 0.0 {
+                    half_size[0]
+                } else {
+                    -half_size[0]
+                },
+                if local_direction[1] >= 0.0 {
+                    half_size[1]
+                } else {
+                    -half_size[1]
+                },
+            ],
+            // First vertex of the furthest ones, like `body_support` in `rigid_bodies.wgsl`.
+            BodyShape::Polygon { vertices } => {
+                vertices.iter().skip(1).fold(vertices[0], |best, vertex| {
+                    if dot(*vertex, local_direction) > dot(best, local_direction) {
+                        *vertex
+                    } else {
+                        best
+                    }
+                })
+            }
+        };
+        let offset = rotate(local, self.angle);
+
+        [self.position[0] + offset[0], self.position[1] + offset[1]]
+    }
+
+    /// Packs `rigid_bodies`, appending their polygon vertices to `vertices`. Bodies are
+    /// expected to be [`RigidBody::centered`].
+    pub fn pack(rigid_bodies: &[RigidBody], vertices: &mut Vec<[f32; 2]>) -> Vec<GpuRigidBody> {
+        rigid_bodies
+            .iter()
+            .map(|rigid_body| GpuRigidBody {
+                shape: rigid_body.local_shape().pack_into(vertices),
+                position: rigid_body.position,
+                angle: rigid_body.angle,
+                inverse_mass: 1.0 / rigid_body.mass(),
+                velocity: rigid_body.velocity,
+                angular_velocity: rigid_body.angular_velocity,
+                inverse_inertia: 1.0 / rigid_body.inertia(),
+            })
+            .collect()
+    }
+
+    /// Takes over the position and velocity the GPU integrated into `record`.
+    pub fn update_from(&mut self, record: &GpuRigidBody) {
+        self.position = record.position;
+        self.angle = record.angle;
+        self.velocity = record.velocity;
+        self.angular_velocity = record.angular_velocity;
+    }
+}
+
+/// Area, centroid and moment of inertia about the origin at unit density, for either
+/// winding order.
+fn polygon_mass_properties(vertices: &[[f32; 2]]) -> (f32, [f32; 2], f32) {
+    let mut area = 0.0;
+    let mut centroid = [0.0; 2];
+    let mut inertia = 0.0;
+
+    for (i, vertex) in vertices.iter().enumerate() {
+        let next = vertices[(i + 1) % vertices.len()];
+        let cross = vertex[0] * next[1] - vertex[1] * next[0];
+
+        area += 0.5 * cross;
+        centroid[0] += (vertex[0] + next[0]) * cross;
+        centroid[1] += (vertex[1] + next[1]) * cross;
+        inertia += cross * (dot(*vertex, *vertex) + dot(*vertex, next) + dot(next, next)) / 12.0;
+    }
+
+    if area.abs() > 0.0 {
+        centroid = [centroid[0] / (6.0 * area), centroid[1] / (6.0 * area)];
+    }
+
+    (area.abs(), centroid, inertia.abs())
+}
@@ -0,0 +1,640 @@
+use std::path::Path;
+
+use anyhow::Context;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::obstacle::Obstacle;
+use crate::pipelines::radix_sort::RadixSort;
+use crate::rigid_body::{BodyShape, RigidBody};
+use crate::simulation::{EquationOfState, Integrator, Particle, PressureSolver, SimulationParams};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scene {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub simulation: SimulationConfig,
+    #[serde(default)]
+    pub fluid_blocks: Vec<FluidBlock>,
+    /// Static colliders. Particles spawned inside one are pushed out on the first step.
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+    /// Dynamic bodies pushed around by the fluid.
+    #[serde(default)]
+    pub rigid_bodies: Vec<RigidBody>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct SimulationConfig {
+    pub time_step: f32,
+    pub particle_mass: f32,
+    pub rest_density: f32,
+    pub stiffness: f32,
+    pub smoothing_radius: f32,
+    pub restitution: f32,
+    pub viscosity: f32,
+    pub gravity: [f32; 2],
+    pub particle_count: u32,
+    pub substeps: u32,
+    /// Shortens solver steps as needed from the CFL, force and viscous criteria.
+    pub adaptive_time_step: bool,
+    pub cfl_number: f32,
+    /// Lower bound for adaptive solver steps.
+    pub min_time_step: f32,
+    pub integrator: Integrator,
+    /// Width and height of the box centred on the origin that holds the fluid.
+    pub domain_size: [f32; 2],
+    /// Sample the domain walls and obstacle outlines with boundary particles, which
+    /// keep the density up next to them instead of relying on clamping alone.
+    pub boundary_particles: bool,
+    pub equation_of_state: EquationOfState,
+    /// Speed of sound of the Tait equation of state. The linear one derives it from
+    /// `stiffness` instead.
+    pub speed_of_sound: f32,
+    /// Clamp negative pressures to zero instead of letting particles attract.
+    pub clamp_negative_pressure: bool,
+    pub pressure_solver: PressureSolver,
+    /// Average relative compression the iterative pressure solvers stop at.
+    pub density_error_tolerance: f32,
+    /// Average relative density change over a step the DFSPH divergence solve stops at.
+    pub divergence_error_tolerance: f32,
+    /// Cap on the pressure corrections of one solver step.
+    pub max_pressure_iterations: u32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FluidBlock {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+    #[serde(default)]
+    pub velocity: [f32; 2],
+    #[serde(default)]
+    pub velocity_jitter: [f32; 2],
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            time_step: 1.0 / 60.0,      // увеличиваем шаг для большей текучести
+            particle_mass: 10.0,        // уменьшаем массу для легкости
+            rest_density: 5000.0,       // стандартная плотность воды
+            stiffness: 0.8,             // увеличиваем жесткость для лучшего сохранения формы
+            smoothing_radius: 0.2,      // увеличиваем радиус взаимодействия
+            restitution: 0.1,           // уменьшаем отскок для вязкости воды
+            viscosity: 20.5,            // значительно уменьшаем вязкость для текучести
+            gravity: [0.0, -100_000.0], // реальное ускорение свободного падения
+            particle_count: 10_000,
+            substeps: 1,
+            adaptive_time_step: false,
+            cfl_number: SimulationParams::DEFAULT_CFL_NUMBER,
+            min_time_step: 1.0e-5,
+            integrator: Integrator::default(),
+            domain_size: SimulationParams::DEFAULT_DOMAIN_SIZE,
+            boundary_particles: false,
+            equation_of_state: EquationOfState::default(),
+            speed_of_sound: SimulationParams::DEFAULT_SPEED_OF_SOUND,
+            clamp_negative_pressure: false,
+            pressure_solver: PressureSolver::default(),
+            density_error_tolerance: SimulationParams::DEFAULT_DENSITY_ERROR_TOLERANCE,
+            divergence_error_tolerance: SimulationParams::DEFAULT_DIVERGENCE_ERROR_TOLERANCE,
+            max_pressure_iterations: SimulationParams::DEFAULT_MAX_PRESSURE_ITERATIONS,
+        }
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            simulation: SimulationConfig::default(),
+            fluid_blocks: vec![FluidBlock {
+                min: [-0.5, -0.5],
+                max: [0.5, 0.5],
+                velocity: [0.0, -0.05],
+                velocity_jitter: [0.1, 0.0],
+            }],
+            obstacles: Vec::new(),
+            rigid_bodies: Vec::new(),
+        }
+    }
+}
+
+impl Scene {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read scene file {}", path.display()))?;
+
+        let scene: Scene = toml::from_str(&source)
+            .with_context(|| format!("Failed to parse scene file {}", path.display()))?;
+
+        scene
+            .validate()
+            .with_context(|| format!("Invalid scene file {}", path.display()))?;
+
+        Ok(scene)
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let simulation = &self.simulation;
+
+        ensure_positive("simulation.time_step", simulation.time_step)?;
+        ensure_positive("simulation.particle_mass", simulation.particle_mass)?;
+        ensure_positive("simulation.rest_density", simulation.rest_density)?;
+        ensure_non_negative("simulation.stiffness", simulation.stiffness)?;
+        ensure_positive("simulation.smoothing_radius", simulation.smoothing_radius)?;
+        ensure(
+            "simulation.smoothing_radius",
+            simulation.smoothing_radius
+                <= SimulationParams::MAX_SMOOTHING_RADIUS_SPACINGS
+                    * (simulation.particle_mass / simulation.rest_density).sqrt(),
+            &format!(
+                "must be at most {} particle spacings `sqrt(particle_mass / rest_density)`",
+                SimulationParams::MAX_SMOOTHING_RADIUS_SPACINGS
+            ),
+        )?;
+        ensure_non_negative("simulation.viscosity", simulation.viscosity)?;
+        ensure_positive("simulation.speed_of_sound", simulation.speed_of_sound)?;
+        ensure(
+            "simulation.restitution",
+            (0.0..=1.0).contains(&simulation.restitution),
+            "must be between 0 and 1",
+        )?;
+        ensure(
+            "simulation.gravity",
+            simulation.gravity.iter().all(|value| value.is_finite()),
+            "must be finite",
+        )?;
+        ensure(
+            "simulation.particle_count",
+            (1..=RadixSort::MAX_LEN).contains(&simulation.particle_count),
+            &format!("must be between 1 and {}", RadixSort::MAX_LEN),
+        )?;
+
+        ensure(
+            "simulation.substeps",
+            (1..=SimulationParams::MAX_SUBSTEPS).contains(&simulation.substeps),
+            &format!("must be between 1 and {}", SimulationParams::MAX_SUBSTEPS),
+        )?;
+        ensure_positive(
+            "simulation.density_error_tolerance",
+            simulation.density_error_tolerance,
+        )?;
+        ensure_positive(
+            "simulation.divergence_error_tolerance",
+            simulation.divergence_error_tolerance,
+        )?;
+        ensure(
+            "simulation.max_pressure_iterations",
+            (1..=SimulationParams::MAX_PRESSURE_ITERATIONS)
+                .contains(&simulation.max_pressure_iterations),
+            &format!(
+                "must be between 1 and {}",
+                SimulationParams::MAX_PRESSURE_ITERATIONS
+            ),
+        )?;
+        ensure(
+            "simulation.cfl_number",
+            simulation.cfl_number > 0.0 && simulation.cfl_number <= 1.0,
+            "must be greater than 0 and at most 1",
+        )?;
+        ensure_positive("simulation.min_time_step", simulation.min_time_step)?;
+        ensure(
+            "simulation.min_time_step",
+            simulation.min_time_step <= simulation.time_step / simulation.substeps as f32,
+            "must not exceed `time_step / substeps`",
+        )?;
+
+        ensure(
+            "simulation.domain_size",
+            simulation
+                .domain_size
+                .iter()
+                .all(|value| value.is_finite() && *value > 0.0),
+            "must be positive on both axes",
+        )?;
+
+        ensure(
+            "fluid_blocks",
+            !self.fluid_blocks.is_empty(),
+            "must contain at least one block",
+        )?;
+
+        let [width, height] = simulation.domain_size;
+        let domain = format!(
+            "must lie inside the domain [{}, {}] x [{}, {}]",
+            -0.5 * width,
+            0.5 * width,
+            -0.5 * height,
+            0.5 * height
+        );
+
+        for (i, block) in self.fluid_blocks.iter().enumerate() {
+            let in_domain = |[x, y]: [f32; 2]| x.abs() <= 0.5 * width && y.abs() <= 0.5 * height;
+
+            ensure(
+                &format!("fluid_blocks[{i}].min"),
+                in_domain(block.min),
+                &domain,
+            )?;
+            ensure(
+                &format!("fluid_blocks[{i}].max"),
+                in_domain(block.max),
+                &domain,
+            )?;
+            ensure(
+                &format!("fluid_blocks[{i}].max"),
+                block.max[0] > block.min[0] && block.max[1] > block.min[1],
+                "must be greater than `min` on both axes",
+            )?;
+            ensure(
+                &format!("fluid_blocks[{i}].velocity"),
+                block.velocity.iter().all(|value| value.is_finite()),
+                "must be finite",
+            )?;
+            ensure(
+                &format!("fluid_blocks[{i}].velocity_jitter"),
+                block
+                    .velocity_jitter
+                    .iter()
+                    .all(|value| value.is_finite() && *value >= 0.0),
+                "must be finite and non-negative",
+            )?;
+        }
+
+        for (i, obstacle) in self.obstacles.iter().enumerate() {
+            validate_obstacle(&format!("obstacles[{i}]"), obstacle)?;
+        }
+
+        for (i, rigid_body) in self.rigid_bodies.iter().enumerate() {
+            validate_rigid_body(
+                &format!("rigid_bodies[{i}]"),
+                rigid_body,
+                self.simulation.domain_size,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn simulation_params(&self) -> SimulationParams {
+        let simulation = &self.simulation;
+
+        let simulation_params = SimulationParams::new(
+            simulation.time_step,
+            simulation.particle_mass,
+            simulation.rest_density,
+            simulation.stiffness,
+            simulation.smoothing_radius,
+            simulation.restitution,
+            simulation.viscosity,
+            simulation.gravity,
+            simulation.particle_count,
+            simulation.substeps,
+        )
+        .with_integrator(simulation.integrator)
+        .with_domain_size(simulation.domain_size);
+
+        let simulation_params = if simulation.boundary_particles {
+            simulation_params.with_boundary_particles()
+        } else {
+            simulation_params
+        };
+
+        let simulation_params = match simulation.equation_of_state {
+            EquationOfState::Linear => simulation_params,
+            EquationOfState::Tait => {
+                simulation_params.with_tait_equation_of_state(simulation.speed_of_sound)
+            }
+        };
+
+        let simulation_params = if simulation.clamp_negative_pressure {
+            simulation_params.with_negative_pressure_clamp()
+        } else {
+            simulation_params
+        };
+
+        let simulation_params = match simulation.pressure_solver {
+            PressureSolver::Wcsph => simulation_params,
+            PressureSolver::Pcisph => simulation_params.with_pcisph(
+                simulation.density_error_tolerance,
+                simulation.max_pressure_iterations,
+            ),
+            PressureSolver::Dfsph => simulation_params.with_dfsph(
+                simulation.density_error_tolerance,
+                simulation.divergence_error_tolerance,
+                simulation.max_pressure_iterations,
+            ),
+        };
+
+        if simulation.adaptive_time_step {
+            simulation_params
+                .with_adaptive_time_step(simulation.cfl_number, simulation.min_time_step)
+        } else {
+            simulation_params
+        }
+    }
+
+    pub fn spawn_particles(&self) -> Vec<Particle> {
+        let particles_len = self.simulation.particle_count as usize;
+        let total_area: f32 = self.fluid_blocks.iter().map(FluidBlock::area).sum();
+        let spacing = (total_area / particles_len as f32).sqrt();
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        };
+
+        let mut particles = Vec::with_capacity(particles_len);
+        for block in &self.fluid_blocks {
+            let columns = ((block.max[0] - block.min[0]) / spacing).ceil() as usize;
+            let rows = ((block.max[1] - block.min[1]) / spacing).ceil() as usize;
+            let spacing_x = (block.max[0] - block.min[0]) / columns as f32;
+            let spacing_y = (block.max[1] - block.min[1]) / rows as f32;
+
+            for i in 0..columns {
+                for j in 0..rows {
+                    if particles.len() >= particles_len {
+                        return particles;
+                    }
+
+                    let x = block.min[0] + (i as f32 + 0.5) * spacing_x;
+                    let y = block.min[1] + (j as f32 + 0.5) * spacing_y;
+
+                    particles.push(Particle::new(
+                        [x, y],
+                        [
+                            block.velocity[0]
+                                + (rng.random::<f32>() - 0.5) * block.velocity_jitter[0],
+                            block.velocity[1]
+                                + (rng.random::<f32>() - 0.5) * block.velocity_jitter[1],
+                        ],
+                    ));
+                }
+            }
+        }
+
+        particles
+    }
+}
+
+impl FluidBlock {
+    fn area(&self) -> f32 {
+        (self.max[0] - self.min[0]) * (self.max[1] - self.min[1])
+    }
+}
+
+fn validate_obstacle(field: &str, obstacle: &Obstacle) -> anyhow::Result<()> {
+    let ensure_finite = |name: &str, point: &[f32; 2]| {
+        ensure(
+            &format!("{field}.{name}"),
+            point.iter().all(|value| value.is_finite()),
+            "must be finite",
+        )
+    };
+
+    match obstacle {
+        Obstacle::Circle { center, radius } => {
+            ensure_finite("center", center)?;
+            ensure_positive(&format!("{field}.radius"), *radius)
+        }
+        Obstacle::Box {
+            center,
+            half_size,
+            rotation,
+        } => {
+            ensure_finite("center", center)?;
+            ensure(
+                &format!("{field}.half_size"),
+                half_size
+                    .iter()
+                    .all(|value| value.is_finite() && *value > 0.0),
+                "must be positive on both axes",
+            )?;
+            ensure(
+                &format!("{field}.rotation"),
+                rotation.is_finite(),
+                "must be finite",
+            )
+        }
+        Obstacle::Capsule { start, end, radius } => {
+            ensure_finite("start", start)?;
+            ensure_finite("end", end)?;
+            ensure_positive(&format!("{field}.radius"), *radius)
+        }
+        Obstacle::Polygon { vertices } => {
+            ensure(
+                &format!("{field}.vertices"),
+                vertices.len() >= 3,
+                "must contain at least 3 vertices",
+            )?;
+            ensure(
+                &format!("{field}.vertices"),
+                vertices.iter().flatten().all(|value| value.is_finite()),
+                "must be finite",
+            )
+        }
+    }
+}
+
+/// Checks a body read from a scene or a checkpoint, naming it `field` in errors.
+pub(crate) fn validate_rigid_body(
+    field: &str,
+    rigid_body: &RigidBody,
+    domain_size: [f32; 2],
+) -> anyhow::Result<()> {
+    ensure_positive(&format!("{field}.density"), rigid_body.density)?;
+    ensure(
+        &format!("{field}.position"),
+        rigid_body
+            .position
+            .iter()
+            .zip(domain_size)
+            .all(|(value, size)| value.is_finite() && value.abs() < 0.5 * size),
+        "must lie inside the domain",
+    )?;
+    ensure(
+        &format!("{field}.angle"),
+        rigid_body.angle.is_finite(),
+        "must be finite",
+    )?;
+    ensure(
+        &format!("{field}.velocity"),
+        rigid_body.velocity.iter().all(|value| value.is_finite()),
+        "must be finite",
+    )?;
+    ensure(
+        &format!("{field}.angular_velocity"),
+        rigid_body.angular_velocity.is_finite(),
+        "must be finite",
+    )?;
+
+    match &rigid_body.shape {
+        BodyShape::Disc { radius } => ensure_positive(&format!("{field}.shape.radius"), *radius),
+        BodyShape::Box { half_size } => ensure(
+            &format!("{field}.shape.half_size"),
+            half_size
+                .iter()
+                .all(|value| value.is_finite() && *value > 0.0),
+            "must be positive on both axes",
+        ),
+        BodyShape::Polygon { vertices } => {
+            let field = format!("{field}.shape.vertices");
+            ensure(
+                &field,
+                vertices.len() >= 3,
+                "must contain at least 3 vertices",
+            )?;
+            ensure(
+                &field,
+                vertices.iter().flatten().all(|value| value.is_finite()),
+                "must be finite",
+            )?;
+            ensure(&field, is_convex(vertices), "must form a convex polygon")?;
+            ensure(
+                &field,
+                rigid_body.area() > 0.0,
+                "must enclose a non-zero area",
+            )
+        }
+    }
+}
+
+/// Whether every corner turns the same way, in either winding order.
+fn is_convex(vertices: &[[f32; 2]]) -> bool {
+    let turns: Vec<f32> = (0..vertices.len())
+        .map(|i| {
+            let [a, b, c] = [0, 1, 2].map(|offset| vertices[(i + offset) % vertices.len()]);
+            (b[0] - a[0]) * (c[1] - b[1]) - (b[1] - a[1]) * (c[0] - b[0])
+        })
+        .collect();
+
+    turns.iter().all(|turn| *turn >= 0.0) || turns.iter().all(|turn| *turn <= 0.0)
+}
+
+fn ensure(field: &str, condition: bool, message: &str) -> anyhow::Result<()> {
+    if !condition {
+        anyhow::bail!("`{field}` {message}");
+    }
+
+    Ok(())
+}
+
+fn ensure_positive(field: &str, value: f32) -> anyhow::Result<()> {
+    ensure(
+        field,
+        value.is_finite() && value > 0.0,
+        &format!("must be a positive number, got {value}"),
+    )
+}
+
+fn ensure_non_negative(field: &str, value: f32) -> anyhow::Result<()> {
+    ensure(
+        field,
+        value.is_finite() && value >= 0.0,
+        &format!("must be a non-negative number, got {value}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Puts one field of a valid scene out of range.
+    type BreakScene = fn(&mut Scene);
+
+    #[test]
+    fn default_scene_is_valid() {
+        Scene::default().validate().unwrap();
+    }
+
+    #[test]
+    fn rejects_out_of_range_fields() {
+        let cases: [(&str, BreakScene); 16] = [
+            ("simulation.time_step", |scene| {
+                scene.simulation.time_step = 0.0
+            }),
+            ("simulation.particle_mass", |scene| {
+                scene.simulation.particle_mass = f32::NAN
+            }),
+            ("simulation.stiffness", |scene| {
+                scene.simulation.stiffness = -1.0
+            }),
+            ("simulation.smoothing_radius", |scene| {
+                scene.simulation.smoothing_radius = 100.0
+            }),
+            ("simulation.restitution", |scene| {
+                scene.simulation.restitution = 1.5
+            }),
+            ("simulation.particle_count", |scene| {
+                scene.simulation.particle_count = 0
+            }),
+            ("simulation.particle_count", |scene| {
+                scene.simulation.particle_count = RadixSort::MAX_LEN + 1
+            }),
+            ("simulation.substeps", |scene| {
+                scene.simulation.substeps = SimulationParams::MAX_SUBSTEPS + 1
+            }),
+            ("simulation.max_pressure_iterations", |scene| {
+                scene.simulation.max_pressure_iterations = 0
+            }),
+            ("simulation.cfl_number", |scene| {
+                scene.simulation.cfl_number = 2.0
+            }),
+            ("simulation.min_time_step", |scene| {
+                scene.simulation.min_time_step = 2.0 * scene.simulation.time_step
+            }),
+            ("simulation.domain_size", |scene| {
+                scene.simulation.domain_size = [1.0, -1.0]
+            }),
+            ("fluid_blocks[0].max", |scene| {
+                scene.fluid_blocks[0].max = [10.0, 0.5]
+            }),
+            ("obstacles[0].radius", |scene| {
+                scene.obstacles.push(Obstacle::Circle {
+                    center: [0.0, 0.0],
+                    radius: -0.1,
+                })
+            }),
+            ("rigid_bodies[0].density", |scene| {
+                scene.rigid_bodies.push(RigidBody {
+                    shape: BodyShape::Disc { radius: 0.1 },
+                    density: 0.0,
+                    position: [0.0, 0.0],
+                    angle: 0.0,
+                    velocity: [0.0, 0.0],
+                    angular_velocity: 0.0,
+                })
+            }),
+            ("rigid_bodies[0].shape.vertices", |scene| {
+                scene.rigid_bodies.push(RigidBody {
+                    shape: BodyShape::Polygon {
+                        vertices: vec![[0.0, 0.0], [0.1, 0.0], [0.1, 0.1], [0.05, 0.02]],
+                    },
+                    density: 1.0,
+                    position: [0.0, 0.0],
+                    angle: 0.0,
+                    velocity: [0.0, 0.0],
+                    angular_velocity: 0.0,
+                })
+            }),
+        ];
+
+        for (field, break_scene) in cases {
+            let mut scene = Scene::default();
+            break_scene(&mut scene);
+
+            let Err(error) = scene.validate() else {
+                panic!("accepted an out-of-range `{field}`");
+            };
+            let error = error.to_string();
+            assert!(
+                error.contains(&format!("`{field}`")),
+                "expected an error about `{field}`, got: {error}"
+            );
+        }
+    }
+}
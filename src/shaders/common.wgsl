@@ -0,0 +1,87 @@
+struct SimulationParams {
+    time_step: f32, // 4 => 4
+    particle_mass: f32, // 4 => 8
+    rest_density: f32, // 4 => 12
+    stiffness: f32, // 4 => 16
+    smoothing_radius: f32, // 4 => 4
+    restitution: f32, // 4 => 8
+    viscosity: f32, // 4 => 12
+    particles_len: u32, // 4 => 16
+    gravity_force: vec2<f32>, // 8 => 8
+    hash_table_size: u32, // 4 => 12
+    substeps: u32, // 4 => 16
+    smoothing_radius_sq: f32,
+    density_smoothing_function_coeff: f32,
+    gradient_pressure_smoothing_function_coeff: f32,
+    laplacian_viscosity_smoothing_function_coeff: f32,
+    solver_time_step: f32, // written by `compute_time_step` before every solver step
+    adaptive_time_step: u32,
+    cfl_number: f32,
+    min_time_step: f32,
+    integrator: u32,
+    domain_size: vec2<f32>,
+    obstacles_len: u32,
+    rigid_bodies_len: u32,
+    boundary_particles: u32,
+    equation_of_state: u32,
+    speed_of_sound: f32,
+    clamp_negative_pressure: u32,
+    pressure_solver: u32,
+    max_pressure_iterations: u32,
+    density_error_tolerance: f32,
+    // PCISPH pressure per unit density error at a unit time step.
+    pcisph_scaling_factor: f32,
+    divergence_error_tolerance: f32,
+    // Fraction of the DFSPH pressure change applied per iteration.
+    dfsph_relaxation: f32,
+};
+
+@group(2) @binding(0) var<uniform> simulation_params: SimulationParams;
+
+const equation_of_state_linear: u32 = 0u;
+const equation_of_state_tait: u32 = 1u;
+const tait_exponent: f32 = 7.0;
+
+const pressure_solver_wcsph: u32 = 0u;
+const pressure_solver_pcisph: u32 = 1u;
+const pressure_solver_dfsph: u32 = 2u;
+
+fn speed_of_sound() -> f32 {
+    if simulation_params.equation_of_state == equation_of_state_tait {
+        return simulation_params.speed_of_sound;
+    }
+
+    return sqrt(simulation_params.stiffness);
+}
+
+fn pressure_from_density(density: f32) -> f32 {
+    var pressure: f32;
+
+    if simulation_params.equation_of_state == equation_of_state_tait {
+        let speed_of_sound = simulation_params.speed_of_sound;
+        let bulk_modulus = simulation_params.rest_density * speed_of_sound * speed_of_sound / tait_exponent;
+        let ratio = density / simulation_params.rest_density;
+        let ratio_sq = ratio * ratio;
+
+        pressure = bulk_modulus * (ratio_sq * ratio_sq * ratio_sq * ratio - 1.0);
+    } else {
+        pressure = simulation_params.stiffness * (density - simulation_params.rest_density);
+    }
+
+    if simulation_params.clamp_negative_pressure != 0u {
+        pressure = max(pressure, 0.0);
+    }
+
+    return pressure;
+}
+
+fn get_cell_coordinates(position_x: f32, position_y: f32) -> vec2<i32> {
+    return vec2<i32>(floor(vec2<f32>(position_x, position_y) / simulation_params.smoothing_radius));
+}
+
+fn hash_cell(cell: vec2<i32>) -> u32 {
+    let x = bitcast<u32>(cell.x) * 15823u;
+    let y = bitcast<u32>(cell.y) * 9737333u;
+
+    return (x + y) % simulation_params.hash_table_size;
+}
@@ -0,0 +1,339 @@
+// Divergence-free SPH. Both solves correct the velocities directly: the divergence solve
+// removes the density change the current velocities cause, the constant-density solve
+// the compression they would leave at the end of the step after the non-pressure
+// accelerations. Each iteration measures the relative density error of every particle
+// in `kappa_changes`, turns it into a pressure change with the factors of
+// `compute_factors` and applies it to the velocities. Like in `pcisph.wgsl`, the kernels
+// return early once the average error is within the tolerance.
+
+const reduction_size: u32 = 256u;
+
+@group(3) @binding(2) var<storage, read_write> factors: array<f32>;
+@group(3) @binding(3) var<storage, read_write> kappa_changes: array<f32>;
+@group(3) @binding(4) var<storage, read_write> solve_state: PressureSolveState;
+
+var<workgroup> density_errors: array<f32, reduction_size>;
+var<workgroup> converged: u32;
+
+// Whether an earlier check has stopped the iteration. Invocation 0 reads the flag for
+// the whole workgroup, so the reduction after it stays in uniform control flow.
+fn is_converged(local_index: u32) -> bool {
+    if local_index == 0u {
+        converged = solve_state.converged;
+    }
+
+    return workgroupUniformLoad(&converged) != 0u;
+}
+
+// Density divided by the squared gradient sums, the diagonal of the pressure system.
+// Zero for isolated particles, which have nothing to correct.
+@compute
+@workgroup_size(64)
+fn compute_factors(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
+
+    if i == 0u {
+        solve_state = PressureSolveState();
+    }
+
+    if i >= simulation_params.particles_len {
+        return;
+    }
+
+    let position = vec2<f32>(position_x[i], position_y[i]);
+    let mass = simulation_params.particle_mass;
+    var gradient_sum = vec2<f32>(0.0, 0.0);
+    var gradient_sq_sum: f32 = 0.0;
+    var keys = neighbor_cell_keys(i);
+
+    for (var k: u32 = 0u; k < 9u; k++) {
+        if keys[k] == empty_cell_key {
+            continue;
+        }
+
+        let range = cell_ranges[keys[k]];
+        for (var s: u32 = range.x; s < range.y; s++) {
+            let j = sorted_indices[s];
+
+            if i == j {
+                continue;
+            }
+
+            let gradient = mass * gradient_pressure_smoothing_function(position.x - position_x[j], position.y - position_y[j]);
+            gradient_sum += gradient;
+            gradient_sq_sum += dot(gradient, gradient);
+        }
+
+        if simulation_params.boundary_particles == 0u {
+            continue;
+        }
+
+        let boundary_range = boundary_cell_ranges[keys[k]];
+        for (var s: u32 = boundary_range.x; s < boundary_range.y; s++) {
+            let boundary_particle = boundary_particles[s];
+            let r = position - boundary_particle.position;
+            gradient_sum += boundary_particle.mass * gradient_pressure_smoothing_function(r.x, r.y);
+        }
+    }
+
+    let denominator = dot(gradient_sum, gradient_sum) + gradient_sq_sum;
+    factors[i] = select(0.0, densities[i] / denominator, denominator > 1.0e-6);
+}
+
+// Rate of change of the density of particle `i` under the current velocities.
+fn density_change(i: u32) -> f32 {
+    let position = vec2<f32>(position_x[i], position_y[i]);
+    let velocity = vec2<f32>(velocity_x[i], velocity_y[i]);
+    var fluid_sum: f32 = 0.0;
+    var boundary_sum: f32 = 0.0;
+    var keys = neighbor_cell_keys(i);
+
+    for (var k: u32 = 0u; k < 9u; k++) {
+        if keys[k] == empty_cell_key {
+            continue;
+        }
+
+        let range = cell_ranges[keys[k]];
+        for (var s: u32 = range.x; s < range.y; s++) {
+            let j = sorted_indices[s];
+            let gradient = gradient_pressure_smoothing_function(position.x - position_x[j], position.y - position_y[j]);
+
+            fluid_sum += dot(velocity - vec2<f32>(velocity_x[j], velocity_y[j]), gradient);
+        }
+
+        if simulation_params.boundary_particles == 0u {
+            continue;
+        }
+
+        let boundary_range = boundary_cell_ranges[keys[k]];
+        for (var s: u32 = boundary_range.x; s < boundary_range.y; s++) {
+            let boundary_particle = boundary_particles[s];
+            let r = position - boundary_particle.position;
+            boundary_sum += boundary_particle.mass * dot(velocity, gradient_pressure_smoothing_function(r.x, r.y));
+        }
+    }
+
+    return simulation_params.particle_mass * fluid_sum + boundary_sum;
+}
+
+// Relative density change over the solver step, counting compression only.
+@compute
+@workgroup_size(64)
+fn compute_divergence_errors(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
+
+    if i >= simulation_params.particles_len || solve_state.converged != 0u {
+        return;
+    }
+
+    let density_error = simulation_params.solver_time_step * density_change(i);
+    kappa_changes[i] = max(density_error, 0.0) / simulation_params.rest_density;
+}
+
+// Relative compression at the end of the solver step, counting compression only.
+@compute
+@workgroup_size(64)
+fn compute_density_errors(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
+
+    if i >= simulation_params.particles_len || solve_state.converged != 0u {
+        return;
+    }
+
+    let rest_density = simulation_params.rest_density;
+    let density_error = densities[i] + simulation_params.solver_time_step * density_change(i) - rest_density;
+    kappa_changes[i] = max(density_error, 0.0) / rest_density;
+}
+
+// Average of the errors in `kappa_changes`, returned to invocation 0 of the single
+// workgroup of the convergence checks.
+fn average_error(local_index: u32) -> f32 {
+    var error_sum: f32 = 0.0;
+
+    for (var i: u32 = local_index; i < simulation_params.particles_len; i += reduction_size) {
+        error_sum += kappa_changes[i];
+    }
+
+    density_errors[local_index] = error_sum;
+    workgroupBarrier();
+
+    for (var stride: u32 = reduction_size / 2u; stride > 0u; stride /= 2u) {
+        if local_index < stride {
+            density_errors[local_index] += density_errors[local_index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    return density_errors[0] / f32(simulation_params.particles_len);
+}
+
+@compute
+@workgroup_size(256)
+fn check_divergence_convergence(
+    @builtin(local_invocation_index) local_index: u32
+) {
+    if is_converged(local_index) {
+        return;
+    }
+
+    let divergence_error = average_error(local_index);
+
+    if local_index != 0u {
+        return;
+    }
+
+    solve_state.divergence_error = divergence_error;
+
+    if divergence_error <= simulation_params.divergence_error_tolerance {
+        solve_state.converged = 1u;
+    }
+}
+
+@compute
+@workgroup_size(256)
+fn check_density_convergence(
+    @builtin(local_invocation_index) local_index: u32
+) {
+    if is_converged(local_index) {
+        return;
+    }
+
+    let density_error = average_error(local_index);
+
+    if local_index != 0u {
+        return;
+    }
+
+    solve_state.density_error = density_error;
+
+    if density_error <= simulation_params.density_error_tolerance {
+        solve_state.converged = 1u;
+    }
+}
+
+// Turns the error of particle `i` into its pressure change divided by its density.
+fn update_kappa_change(i: u32) -> f32 {
+    let time_step = simulation_params.solver_time_step;
+    let scale = simulation_params.dfsph_relaxation * simulation_params.rest_density / (time_step * time_step);
+    let kappa_change = scale * kappa_changes[i] * factors[i];
+
+    kappa_changes[i] = kappa_change;
+    return kappa_change;
+}
+
+@compute
+@workgroup_size(64)
+fn update_divergence_kappas(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
+
+    if i >= simulation_params.particles_len || solve_state.converged != 0u {
+        return;
+    }
+
+    if i == 0u {
+        solve_state.divergence_iterations += 1u;
+    }
+
+    update_kappa_change(i);
+}
+
+// Also accumulates the pressures, so they describe the step like with the other solvers.
+@compute
+@workgroup_size(64)
+fn update_density_kappas(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
+
+    if i >= simulation_params.particles_len || solve_state.converged != 0u {
+        return;
+    }
+
+    if i == 0u {
+        solve_state.iterations += 1u;
+    }
+
+    pressures[i] += update_kappa_change(i) * densities[i];
+}
+
+@compute
+@workgroup_size(64)
+fn apply_kappa_changes(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
+
+    if i >= simulation_params.particles_len || solve_state.converged != 0u {
+        return;
+    }
+
+    let position = vec2<f32>(position_x[i], position_y[i]);
+    let own_kappa = kappa_changes[i] / max(densities[i], 1.0e-6);
+    var fluid_sum = vec2<f32>(0.0, 0.0);
+    var boundary_sum = vec2<f32>(0.0, 0.0);
+    var keys = neighbor_cell_keys(i);
+
+    for (var k: u32 = 0u; k < 9u; k++) {
+        if keys[k] == empty_cell_key {
+            continue;
+        }
+
+        let range = cell_ranges[keys[k]];
+        for (var s: u32 = range.x; s < range.y; s++) {
+            let j = sorted_indices[s];
+
+            if i == j {
+                continue;
+            }
+
+            let kappa = own_kappa + kappa_changes[j] / max(densities[j], 1.0e-6);
+            fluid_sum += kappa * gradient_pressure_smoothing_function(position.x - position_x[j], position.y - position_y[j]);
+        }
+
+        if simulation_params.boundary_particles == 0u {
+            continue;
+        }
+
+        let boundary_range = boundary_cell_ranges[keys[k]];
+        for (var s: u32 = boundary_range.x; s < boundary_range.y; s++) {
+            let boundary_particle = boundary_particles[s];
+            let r = position - boundary_particle.position;
+            boundary_sum += boundary_particle.mass * own_kappa * gradient_pressure_smoothing_function(r.x, r.y);
+        }
+    }
+
+    let velocity_change = -simulation_params.solver_time_step * (simulation_params.particle_mass * fluid_sum + boundary_sum);
+    velocity_x[i] += velocity_change.x;
+    velocity_y[i] += velocity_change.y;
+}
+
+// Adds the non-pressure accelerations ahead of the constant-density solve and restarts
+// the convergence check for it.
+@compute
+@workgroup_size(64)
+fn predict_velocities(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
+
+    if i == 0u {
+        solve_state.converged = 0u;
+    }
+
+    if i >= simulation_params.particles_len {
+        return;
+    }
+
+    let time_step = simulation_params.solver_time_step;
+    velocity_x[i] += accelerations[i].x * time_step;
+    velocity_y[i] += accelerations[i].y * time_step;
+    pressures[i] = 0.0;
+}
@@ -0,0 +1,57 @@
+@group(0) @binding(0) var<storage, read> position_x: array<f32>;
+@group(0) @binding(1) var<storage, read> position_y: array<f32>;
+
+@group(1) @binding(0) var<storage, read_write> cell_keys: array<u32>;
+@group(1) @binding(1) var<storage, read_write> sorted_indices: array<u32>;
+@group(1) @binding(2) var<storage, read_write> cell_ranges: array<vec2<u32>>;
+
+@compute
+@workgroup_size(256)
+fn calculate_hashes(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
+
+    if i >= simulation_params.particles_len {
+        return;
+    }
+
+    cell_keys[i] = hash_cell(get_cell_coordinates(position_x[i], position_y[i]));
+    sorted_indices[i] = i;
+}
+
+@compute
+@workgroup_size(256)
+fn clear_cell_ranges(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let key = global_invocation_id.x;
+
+    if key >= simulation_params.hash_table_size {
+        return;
+    }
+
+    cell_ranges[key] = vec2<u32>(0u, 0u);
+}
+
+@compute
+@workgroup_size(256)
+fn find_cell_ranges(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let s = global_invocation_id.x;
+
+    if s >= simulation_params.particles_len {
+        return;
+    }
+
+    let key = cell_keys[s];
+
+    if s == 0u || cell_keys[s - 1u] != key {
+        cell_ranges[key].x = s;
+    }
+
+    if s == simulation_params.particles_len - 1u || cell_keys[s + 1u] != key {
+        cell_ranges[key].y = s + 1u;
+    }
+}
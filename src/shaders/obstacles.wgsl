@@ -0,0 +1,146 @@
+// Signed distance functions of the scene obstacles and rigid bodies, shared by the
+// physics, rigid body and render shaders. The including shader declares the
+// `obstacle_vertices` storage buffer.
+
+const obstacle_circle: u32 = 0u;
+const obstacle_box: u32 = 1u;
+const obstacle_capsule: u32 = 2u;
+const obstacle_polygon: u32 = 3u;
+
+struct Obstacle {
+    kind: u32,
+    vertex_offset: u32,
+    vertex_count: u32,
+    // circle: center, radius; box: center, half size; capsule: start, end
+    params: vec4<f32>,
+    // box: rotation; capsule: radius
+    extra: vec4<f32>,
+};
+
+struct RigidBody {
+    // Outline in the local frame.
+    shape: Obstacle,
+    position: vec2<f32>,
+    angle: f32,
+    inverse_mass: f32,
+    velocity: vec2<f32>,
+    angular_velocity: f32,
+    inverse_inertia: f32,
+};
+
+// Per-body impulse accumulators. WGSL has no float atomics, so every component is a
+// 64-bit fixed-point number with 32 integer and 32 fraction bits, split over a `fractions`
+// and a `wholes` word that takes the carry. Components are x, y and angular; particles
+// add the opposite of their velocity change per unit mass.
+const impulse_components: u32 = 3u;
+const impulse_fraction_scale: f32 = 4294967296.0;
+
+// The largest impulse a component can hold over one solver step is 2^31 times the
+// particle mass, and the smallest velocity change it resolves is 2^-32. Single
+// contributions are clamped to this magnitude, so it takes 32768 of them at the limit
+// before the integer bits overflow.
+const max_impulse_contribution: f32 = 65536.0;
+
+struct RigidBodyImpulse {
+    fractions: array<atomic<u32>, impulse_components>,
+    wholes: array<atomic<i32>, impulse_components>,
+};
+
+fn rotate(v: vec2<f32>, angle: f32) -> vec2<f32> {
+    let s = sin(angle);
+    let c = cos(angle);
+
+    return vec2<f32>(c * v.x - s * v.y, s * v.x + c * v.y);
+}
+
+fn cross_2d(a: vec2<f32>, b: vec2<f32>) -> f32 {
+    return a.x * b.y - a.y * b.x;
+}
+
+// Velocity of the material point of `body` at world-space `point`.
+fn body_velocity_at(body: RigidBody, point: vec2<f32>) -> vec2<f32> {
+    let arm = point - body.position;
+    return body.velocity + body.angular_velocity * vec2<f32>(-arm.y, arm.x);
+}
+
+fn body_distance(body: RigidBody, point: vec2<f32>) -> f32 {
+    return obstacle_distance(body.shape, rotate(point - body.position, -body.angle));
+}
+
+fn body_normal(body: RigidBody, point: vec2<f32>, epsilon: f32) -> vec2<f32> {
+    return rotate(obstacle_normal(body.shape, rotate(point - body.position, -body.angle), epsilon), body.angle);
+}
+
+// Offset from the closest point of the segment `start`-`end` to `point`.
+fn segment_offset(point: vec2<f32>, start: vec2<f32>, end: vec2<f32>) -> vec2<f32> {
+    let to_point = point - start;
+    let segment = end - start;
+    let length_sq = dot(segment, segment);
+    var t: f32 = 0.0;
+    if length_sq > 0.0 {
+        t = clamp(dot(to_point, segment) / length_sq, 0.0, 1.0);
+    }
+
+    return to_point - segment * t;
+}
+
+fn polygon_distance(obstacle: Obstacle, point: vec2<f32>) -> f32 {
+    var distance_sq: f32 = 3.4e38;
+    var is_inside = false;
+    var previous = obstacle_vertices[obstacle.vertex_offset + obstacle.vertex_count - 1u];
+
+    for (var v: u32 = 0u; v < obstacle.vertex_count; v++) {
+        let vertex = obstacle_vertices[obstacle.vertex_offset + v];
+        let offset = segment_offset(point, previous, vertex);
+        distance_sq = min(distance_sq, dot(offset, offset));
+
+        // Crossing number test along +x.
+        if (vertex.y > point.y) != (previous.y > point.y)
+            && point.x < (previous.x - vertex.x) * (point.y - vertex.y) / (previous.y - vertex.y) + vertex.x {
+            is_inside = !is_inside;
+        }
+
+        previous = vertex;
+    }
+
+    let distance = sqrt(distance_sq);
+    return select(distance, -distance, is_inside);
+}
+
+fn obstacle_distance(obstacle: Obstacle, point: vec2<f32>) -> f32 {
+    switch obstacle.kind {
+        case obstacle_circle: {
+            return length(point - obstacle.params.xy) - obstacle.params.z;
+        }
+        case obstacle_box: {
+            let local = rotate(point - obstacle.params.xy, -obstacle.extra.x);
+            let d = abs(local) - obstacle.params.zw;
+
+            return length(max(d, vec2<f32>(0.0, 0.0))) + min(max(d.x, d.y), 0.0);
+        }
+        case obstacle_capsule: {
+            return length(segment_offset(point, obstacle.params.xy, obstacle.params.zw)) - obstacle.extra.x;
+        }
+        case obstacle_polygon: {
+            return polygon_distance(obstacle, point);
+        }
+        default: {
+            return 3.4e38;
+        }
+    }
+}
+
+// Outward unit normal from central differences of the signed distance.
+fn obstacle_normal(obstacle: Obstacle, point: vec2<f32>, epsilon: f32) -> vec2<f32> {
+    let gradient = vec2<f32>(
+        obstacle_distance(obstacle, point + vec2<f32>(epsilon, 0.0)) - obstacle_distance(obstacle, point - vec2<f32>(epsilon, 0.0)),
+        obstacle_distance(obstacle, point + vec2<f32>(0.0, epsilon)) - obstacle_distance(obstacle, point - vec2<f32>(0.0, epsilon)),
+    );
+    let gradient_length = length(gradient);
+
+    if gradient_length < 1.0e-12 {
+        return vec2<f32>(0.0, 1.0);
+    }
+
+    return gradient / gradient_length;
+}
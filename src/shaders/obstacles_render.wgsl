@@ -0,0 +1,50 @@
+struct ViewTransform {
+    scale: vec2<f32>,
+    offset: vec2<f32>,
+    particle_size: f32,
+};
+
+@group(0) @binding(0) var<uniform> view_transform: ViewTransform;
+
+@group(1) @binding(0) var<storage, read> obstacles: array<Obstacle>;
+@group(1) @binding(1) var<storage, read> obstacle_vertices: array<vec2<f32>>;
+@group(1) @binding(2) var<storage, read> rigid_bodies: array<RigidBody>;
+
+const obstacle_color: vec3<f32> = vec3<f32>(0.45, 0.45, 0.5);
+const rigid_body_color: vec3<f32> = vec3<f32>(0.8, 0.55, 0.3);
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec2<f32>,
+}
+
+// One triangle covering the whole target; every pixel evaluates the collider SDFs.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var output: VertexOutput;
+
+    let clip = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u)) * 2.0 - 1.0;
+
+    output.clip_position = vec4<f32>(clip, 0.0, 1.0);
+    output.world_position = (clip - view_transform.offset) / view_transform.scale;
+
+    return output;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The collider buffers are padded to one element, so the counts come from the params.
+    for (var b: u32 = 0u; b < simulation_params.rigid_bodies_len; b++) {
+        if body_distance(rigid_bodies[b], in.world_position) <= 0.0 {
+            return vec4<f32>(rigid_body_color, 1.0);
+        }
+    }
+
+    for (var o: u32 = 0u; o < simulation_params.obstacles_len; o++) {
+        if obstacle_distance(obstacles[o], in.world_position) <= 0.0 {
+            return vec4<f32>(obstacle_color, 1.0);
+        }
+    }
+
+    discard;
+}
@@ -0,0 +1,222 @@
+// Predictive-corrective incompressible SPH. `accelerations` holds everything but
+// pressure on entry; every iteration predicts positions with the current pressure
+// accelerations, measures the density error there and raises the pressures to cancel
+// it. The predicted densities stay in their own buffer, so `densities` keeps the ones
+// at the current positions for the outputs. Each kernel returns early once `check_convergence` has seen the error drop below
+// the tolerance, so the fixed number of recorded dispatches does no extra work.
+
+const reduction_size: u32 = 256u;
+
+@group(3) @binding(2) var<storage, read_write> predicted_positions: array<vec2<f32>>;
+@group(3) @binding(3) var<storage, read_write> pressure_accelerations: array<vec2<f32>>;
+@group(3) @binding(4) var<storage, read_write> solve_state: PressureSolveState;
+@group(3) @binding(5) var<storage, read_write> predicted_densities: array<f32>;
+
+var<workgroup> density_errors: array<f32, reduction_size>;
+var<workgroup> converged: u32;
+
+// Whether an earlier check has stopped the iteration. Invocation 0 reads the flag for
+// the whole workgroup, so the reduction after it stays in uniform control flow.
+fn is_converged(local_index: u32) -> bool {
+    if local_index == 0u {
+        converged = solve_state.converged;
+    }
+
+    return workgroupUniformLoad(&converged) != 0u;
+}
+
+@compute
+@workgroup_size(64)
+fn initialize(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
+
+    if i == 0u {
+        solve_state = PressureSolveState();
+    }
+
+    if i >= simulation_params.particles_len {
+        return;
+    }
+
+    pressures[i] = 0.0;
+    pressure_accelerations[i] = vec2<f32>(0.0, 0.0);
+}
+
+@compute
+@workgroup_size(64)
+fn predict_positions(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
+
+    if i >= simulation_params.particles_len || solve_state.converged != 0u {
+        return;
+    }
+
+    let time_step = simulation_params.solver_time_step;
+    let velocity = vec2<f32>(velocity_x[i], velocity_y[i]) + (accelerations[i] + pressure_accelerations[i]) * time_step;
+
+    predicted_positions[i] = vec2<f32>(position_x[i], position_y[i]) + velocity * time_step;
+}
+
+// Density at the predicted positions, using the neighbor grid of the current ones.
+@compute
+@workgroup_size(64)
+fn predict_densities(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
+
+    if i >= simulation_params.particles_len || solve_state.converged != 0u {
+        return;
+    }
+
+    let position = predicted_positions[i];
+    var density: f32 = 0.0;
+    var keys = neighbor_cell_keys(i);
+
+    for (var k: u32 = 0u; k < 9u; k++) {
+        if keys[k] == empty_cell_key {
+            continue;
+        }
+
+        let range = cell_ranges[keys[k]];
+        for (var s: u32 = range.x; s < range.y; s++) {
+            let r = position - predicted_positions[sorted_indices[s]];
+            density += density_smoothing_function(r.x, r.y);
+        }
+    }
+
+    predicted_densities[i] = simulation_params.particle_mass * density + boundary_density_at(position, keys);
+}
+
+// Runs as a single workgroup, like `compute_time_step`: averages the compression over
+// every particle and stops the iteration once it is within the tolerance. Expansion at
+// the free surface does not count, since pressures are never negative.
+@compute
+@workgroup_size(256)
+fn check_convergence(
+    @builtin(local_invocation_index) local_index: u32
+) {
+    if is_converged(local_index) {
+        return;
+    }
+
+    let rest_density = simulation_params.rest_density;
+    var error_sum: f32 = 0.0;
+
+    for (var i: u32 = local_index; i < simulation_params.particles_len; i += reduction_size) {
+        error_sum += max(predicted_densities[i] - rest_density, 0.0);
+    }
+
+    density_errors[local_index] = error_sum;
+    workgroupBarrier();
+
+    for (var stride: u32 = reduction_size / 2u; stride > 0u; stride /= 2u) {
+        if local_index < stride {
+            density_errors[local_index] += density_errors[local_index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if local_index != 0u {
+        return;
+    }
+
+    let density_error = density_errors[0] / (f32(simulation_params.particles_len) * rest_density);
+    solve_state.density_error = density_error;
+
+    if density_error <= simulation_params.density_error_tolerance {
+        solve_state.converged = 1u;
+    }
+}
+
+@compute
+@workgroup_size(64)
+fn update_pressures(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
+
+    if i >= simulation_params.particles_len || solve_state.converged != 0u {
+        return;
+    }
+
+    if i == 0u {
+        solve_state.iterations += 1u;
+    }
+
+    let time_step = simulation_params.solver_time_step;
+    let pressure_scale = simulation_params.pcisph_scaling_factor / (time_step * time_step);
+
+    pressures[i] = max(pressures[i] + pressure_scale * (predicted_densities[i] - simulation_params.rest_density), 0.0);
+}
+
+// Symmetric pressure acceleration at the predicted positions, with the rest density in
+// place of the particle densities as in the derivation of the scaling factor.
+@compute
+@workgroup_size(64)
+fn compute_pressure_accelerations(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
+
+    if i >= simulation_params.particles_len || solve_state.converged != 0u {
+        return;
+    }
+
+    let position = predicted_positions[i];
+    var pressure_sum = vec2<f32>(0.0, 0.0);
+    var boundary_sum = vec2<f32>(0.0, 0.0);
+    var keys = neighbor_cell_keys(i);
+
+    for (var k: u32 = 0u; k < 9u; k++) {
+        if keys[k] == empty_cell_key {
+            continue;
+        }
+
+        let range = cell_ranges[keys[k]];
+        for (var s: u32 = range.x; s < range.y; s++) {
+            let j = sorted_indices[s];
+
+            if i == j {
+                continue;
+            }
+
+            let r = position - predicted_positions[j];
+            pressure_sum += (pressures[i] + pressures[j]) * gradient_pressure_smoothing_function(r.x, r.y);
+        }
+
+        if simulation_params.boundary_particles == 0u {
+            continue;
+        }
+
+        let boundary_range = boundary_cell_ranges[keys[k]];
+        for (var s: u32 = boundary_range.x; s < boundary_range.y; s++) {
+            let boundary_particle = boundary_particles[s];
+            let r = position - boundary_particle.position;
+            boundary_sum += boundary_particle.mass * pressures[i] * gradient_pressure_smoothing_function(r.x, r.y);
+        }
+    }
+
+    let rest_density_sq = simulation_params.rest_density * simulation_params.rest_density;
+    pressure_accelerations[i] = -(simulation_params.particle_mass * pressure_sum + boundary_sum) / rest_density_sq;
+}
+
+// Adds the final pressure accelerations, after which the physics `integrate` kernel
+// advances the particles.
+@compute
+@workgroup_size(64)
+fn apply_pressure_accelerations(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
+
+    if i >= simulation_params.particles_len {
+        return;
+    }
+
+    accelerations[i] += pressure_accelerations[i];
+}
@@ -1,126 +1,161 @@
-
-const pi_value: f32 = 3.14159;
-
-struct SimulationParams {
-    time_step: f32, // 4 => 4
-    particle_mass: f32, // 4 => 8
-    rest_density: f32, // 4 => 12
-    stiffness: f32, // 4 => 16
-    smoothing_radius: f32, // 4 => 4
-    restitution: f32, // 4 => 8
-    viscosity: f32, // 4 => 12
-    particles_len: u32, // 4 => 16
-    gravity_force: vec2<f32>, // 8 => 8
-    _padding: vec2<f32>, // 8 => 16
-    smoothing_radius_sq: f32,
-    density_smoothing_function_coeff: f32,
-    gradient_pressure_smoothing_function_coeff: f32,
-    laplacian_viscosity_smoothing_function_coeff: f32,
+struct InteractionParams {
+    cursor: vec2<f32>,
+    radius: f32,
+    strength: f32,
 };
 
-@group(0) @binding(0) var<storage, read_write> position_x: array<f32>;
-@group(0) @binding(1) var<storage, read_write> position_y: array<f32>;
-@group(0) @binding(2) var<storage, read_write> velocity_x: array<f32>;
-@group(0) @binding(3) var<storage, read_write> velocity_y: array<f32>;
-
-@group(0) @binding(4) var<storage, read_write> hashes: array<u32>;
-@group(0) @binding(5) var<storage, read_write> indices: array<u32>;
-@group(0) @binding(6) var<storage, read_write> block_sums: array<u32>;
-
-@group(0) @binding(7) var<storage, read_write> prefix_sum_output: array<u32>;
-@group(0) @binding(8) var<storage, read_write> sums_output: array<u32>;
+@group(2) @binding(1) var<uniform> interaction_params: InteractionParams;
+
+@group(3) @binding(2) var<storage, read> obstacles: array<Obstacle>;
+@group(3) @binding(3) var<storage, read> obstacle_vertices: array<vec2<f32>>;
+@group(3) @binding(4) var<storage, read> rigid_bodies: array<RigidBody>;
+@group(3) @binding(5) var<storage, read_write> rigid_body_impulses: array<RigidBodyImpulse>;
+
+// Fraction of the domain size a particle is pushed back inside the wall it crossed.
+const wall_margin: f32 = 0.005;
+// Fraction of the smoothing radius particles are kept away from obstacle surfaces.
+const obstacle_margin: f32 = 0.01;
+// `SimulationParams::integrator` of the only integrator with one force evaluation per step.
+const integrator_symplectic_euler: u32 = 0u;
+
+// Same as `SimulationParams::force_evaluations`: the incompressible solvers always
+// advance with symplectic Euler.
+fn force_evaluations() -> f32 {
+    let single = simulation_params.integrator == integrator_symplectic_euler || simulation_params.pressure_solver != pressure_solver_wcsph;
+    return select(2.0, 1.0, single);
+}
 
-@group(0) @binding(9) var<storage, read_write> lsb: array<u32>;
-@group(0) @binding(11) var<storage, read_write> predicate_scan: array<u32>;
+fn calculate_density(i: u32) -> f32 {
+    var density: f32 = 0.0;
+    var keys = neighbor_cell_keys(i);
 
-@group(1) @binding(0) var<storage, read_write> densities: array<f32>;
-@group(1) @binding(1) var<storage, read_write> pressures: array<f32>;
+    for (var k: u32 = 0u; k < 9u; k++) {
+        if keys[k] == empty_cell_key {
+            continue;
+        }
 
-@group(2) @binding(0) var<uniform> simulation_params: SimulationParams;
+        let range = cell_ranges[keys[k]];
+        for (var s: u32 = range.x; s < range.y; s++) {
+            let j = sorted_indices[s];
+            density += density_smoothing_function(position_x[i] - position_x[j], position_y[i] - position_y[j]);
+        }
+    }
 
-fn density_smoothing_function(r_x: f32, r_y: f32) -> f32 {
-    let h = simulation_params.smoothing_radius;
-    let r_length_sq = r_x * r_x + r_y * r_y;
+    let position = vec2<f32>(position_x[i], position_y[i]);
+    return simulation_params.particle_mass * density + boundary_density_at(position, keys) + rigid_body_density_at(position);
+}
 
-    if r_length_sq > simulation_params.smoothing_radius_sq {
-        return 0.0;
+// Samples of rigid body `b` in `boundary_particles`, or an empty range when `position`
+// is out of their reach.
+fn rigid_body_sample_range(b: u32, position: vec2<f32>) -> vec2<u32> {
+    if body_distance(rigid_bodies[b], position) >= simulation_params.smoothing_radius {
+        return vec2<u32>(0u, 0u);
     }
 
-    let r_length = sqrt(r_length_sq);
-    let h_minus_r = simulation_params.smoothing_radius_sq - r_length * r_length;
+    return boundary_cell_ranges[simulation_params.hash_table_size + b];
+}
 
-    return simulation_params.density_smoothing_function_coeff * h_minus_r * h_minus_r * h_minus_r;
+// World-space position of a sample of `body`, which is stored in the local frame.
+fn rigid_body_sample_position(body: RigidBody, sample: BoundaryParticle) -> vec2<f32> {
+    return body.position + rotate(sample.position, body.angle);
 }
 
-fn calculate_density(i: u32) -> f32 {
+// Same as `boundary_density_at` for the samples of the rigid bodies.
+fn rigid_body_density_at(position: vec2<f32>) -> f32 {
     var density: f32 = 0.0;
 
-    for (var j: u32 = 0u; j < simulation_params.particles_len; j++) {
-        density += density_smoothing_function(position_x[i] - position_x[j], position_y[i] - position_y[j]);
-    }
-
-    return simulation_params.particle_mass * density;
-}
-
-fn gradient_pressure_smoothing_function(r_x: f32, r_y: f32) -> vec2<f32> {
-    let r_length_sq = r_x * r_x + r_y * r_y;
+    for (var b: u32 = 0u; b < simulation_params.rigid_bodies_len; b++) {
+        let body = rigid_bodies[b];
+        let range = rigid_body_sample_range(b, position);
 
-    if r_length_sq > simulation_params.smoothing_radius_sq || r_length_sq < 1.0e-8 {
-        return vec2<f32>(0.0, 0.0);
+        for (var s: u32 = range.x; s < range.y; s++) {
+            let sample = boundary_particles[s];
+            let r = position - rigid_body_sample_position(body, sample);
+            density += sample.mass * density_smoothing_function(r.x, r.y);
+        }
     }
 
-    let r_length = sqrt(r_length_sq);
-    let h_minus_r = simulation_params.smoothing_radius - r_length;
-    let coeff = simulation_params.gradient_pressure_smoothing_function_coeff * pow(h_minus_r, 2.0) / r_length ;
-
-    return vec2<f32>(coeff * r_x, coeff * r_y);
+    return density;
 }
 
 fn calculate_pressure(i: u32) -> f32 {
-    return simulation_params.stiffness * (densities[i] - simulation_params.rest_density);
+    return pressure_from_density(densities[i]);
 }
 
 fn calculate_pressure_force(i: u32) -> vec2<f32> {
     var pressure_force = vec2<f32>(0.0, 0.0);
+    var keys = neighbor_cell_keys(i);
 
-    for (var j: u32 = 0u; j < simulation_params.particles_len; j++) {
-        if densities[j] < 0.0001 || i == j { 
+    for (var k: u32 = 0u; k < 9u; k++) {
+        if keys[k] == empty_cell_key {
             continue;
         }
 
-        pressure_force -= (pressures[i] + pressures[j]) / (2f * densities[j]) * gradient_pressure_smoothing_function(position_x[i] - position_x[j], position_y[i] - position_y[j]);
+        let range = cell_ranges[keys[k]];
+        for (var s: u32 = range.x; s < range.y; s++) {
+            let j = sorted_indices[s];
+
+            if densities[j] < 0.0001 || i == j {
+                continue;
+            }
+
+            pressure_force -= (pressures[i] + pressures[j]) / (2f * densities[j]) * gradient_pressure_smoothing_function(position_x[i] - position_x[j], position_y[i] - position_y[j]);
+        }
     }
 
-    return simulation_params.particle_mass * pressure_force;
+    return simulation_params.particle_mass * pressure_force + calculate_boundary_pressure_force(i, keys);
 }
 
-fn laplacian_viscosity_smoothing_function(r_length: f32) -> f32 {
-    let h = simulation_params.smoothing_radius;
+// Pressure force of the boundary particles around particle `i`: each one pushes back
+// with the particle's own pressure and density, weighted by its volume.
+fn calculate_boundary_pressure_force(i: u32, keys: array<u32, 9>) -> vec2<f32> {
+    var pressure_force = vec2<f32>(0.0, 0.0);
+
+    if simulation_params.boundary_particles == 0u {
+        return pressure_force;
+    }
+
+    for (var k: u32 = 0u; k < 9u; k++) {
+        if keys[k] == empty_cell_key {
+            continue;
+        }
 
-    if r_length < 0.0001 || r_length > h {
-        return 0.0;
+        let range = boundary_cell_ranges[keys[k]];
+        for (var s: u32 = range.x; s < range.y; s++) {
+            let boundary_particle = boundary_particles[s];
+            pressure_force -= boundary_particle.mass * pressures[i] / densities[i] * gradient_pressure_smoothing_function(position_x[i] - boundary_particle.position.x, position_y[i] - boundary_particle.position.y);
+        }
     }
 
-    return simulation_params.laplacian_viscosity_smoothing_function_coeff * (h - r_length);
+    return pressure_force;
 }
 
 fn calculate_viscosity_force(i: u32) -> vec2<f32> {
     var viscosity_force = vec2<f32>(0.0, 0.0);
+    var keys = neighbor_cell_keys(i);
 
-    for (var j: u32 = 0u; j < simulation_params.particles_len; j++) {
-        let r_x = position_x[i] - position_x[j];
-        let r_y = position_y[i] - position_y[j];
-        let r_length_sq = r_x * r_x + r_y * r_y;
-
-        if i == j { continue; }
-        if densities[j] < 0.0001 || r_length_sq < 1.0e-8 {
+    for (var k: u32 = 0u; k < 9u; k++) {
+        if keys[k] == empty_cell_key {
             continue;
         }
 
-        let r_length = sqrt(r_length_sq);
-        let viscosity_velocity = vec2<f32>(velocity_x[j] - velocity_x[i], velocity_y[j] - velocity_y[i]);
+        let range = cell_ranges[keys[k]];
+        for (var s: u32 = range.x; s < range.y; s++) {
+            let j = sorted_indices[s];
+            let r_x = position_x[i] - position_x[j];
+            let r_y = position_y[i] - position_y[j];
+            let r_length_sq = r_x * r_x + r_y * r_y;
+
+            if i == j { continue; }
+            if densities[j] < 0.0001 || r_length_sq < 1.0e-8 {
+                continue;
+            }
+
+            let r_length = sqrt(r_length_sq);
+            let viscosity_velocity = vec2<f32>(velocity_x[j] - velocity_x[i], velocity_y[j] - velocity_y[i]);
 
-        viscosity_force += (simulation_params.particle_mass * viscosity_velocity / densities[j]) * laplacian_viscosity_smoothing_function(r_length);
+            viscosity_force += (simulation_params.particle_mass * viscosity_velocity / densities[j]) * laplacian_viscosity_smoothing_function(r_length);
+        }
     }
 
     return simulation_params.viscosity * viscosity_force;
@@ -155,26 +190,94 @@ fn compute_pressures(
     pressures[i] = calculate_pressure(i);
 }
 
+// Adds `value` to component `k` of the accumulators of body `b`, carrying into the whole
+// word when the fraction word wraps around.
+fn accumulate_impulse(b: u32, k: u32, value: f32) {
+    let clamped = clamp(value, -max_impulse_contribution, max_impulse_contribution);
+    let whole = floor(clamped);
+    let fraction = u32((clamped - whole) * impulse_fraction_scale);
+    let previous = atomicAdd(&rigid_body_impulses[b].fractions[k], fraction);
+    let carry = select(0, 1, previous + fraction < previous);
 
-fn get_cell_coordinates(position_x: f32, position_y: f32) -> vec2<u32> {
-    let cell = vec2<u32>(
-        u32(ceil(position_x / simulation_params.smoothing_radius)),
-        u32(ceil(position_y / simulation_params.smoothing_radius))
-    );
+    atomicAdd(&rigid_body_impulses[b].wholes[k], i32(whole) + carry);
+}
 
-    return cell;
+// Adds the opposite of a particle's velocity change and of its angular momentum about
+// the centre of body `b`, per unit mass, to the accumulators of the body.
+// `integrate_rigid_bodies` turns them into an impulse.
+fn add_rigid_body_impulse(b: u32, velocity_change: vec2<f32>, angular_change: f32) {
+    accumulate_impulse(b, 0u, -velocity_change.x);
+    accumulate_impulse(b, 1u, -velocity_change.y);
+    accumulate_impulse(b, 2u, -angular_change);
 }
 
-fn hash_cell(cell_x: u32, cell_y: u32) -> u32 {
-    let x = cell_x * 15823u;
-    let y = cell_y * 9737333u;
+// Pressure and viscous forces of the rigid body samples, which push back like the
+// boundary particles of the walls and drag the fluid along with the velocity of the body
+// surface. Each body takes the opposite force at its samples, split over the force
+// evaluations of the step.
+fn calculate_rigid_body_force(i: u32) -> vec2<f32> {
+    var rigid_body_force = vec2<f32>(0.0, 0.0);
+    let position = vec2<f32>(position_x[i], position_y[i]);
+    let velocity = vec2<f32>(velocity_x[i], velocity_y[i]);
+    let pressure_scale = pressures[i] / densities[i];
+    let viscosity_scale = simulation_params.viscosity / simulation_params.rest_density;
+    let time_step = simulation_params.solver_time_step / force_evaluations();
+
+    for (var b: u32 = 0u; b < simulation_params.rigid_bodies_len; b++) {
+        let body = rigid_bodies[b];
+        let range = rigid_body_sample_range(b, position);
+
+        if range.x == range.y {
+            continue;
+        }
+
+        var body_force = vec2<f32>(0.0, 0.0);
+        var body_torque: f32 = 0.0;
 
-    return x + y;
+        for (var s: u32 = range.x; s < range.y; s++) {
+            let sample = boundary_particles[s];
+            let sample_position = rigid_body_sample_position(body, sample);
+            let r = position - sample_position;
+            let pressure_force = -sample.mass * pressure_scale * gradient_pressure_smoothing_function(r.x, r.y);
+            let viscosity_force = viscosity_scale * sample.mass * (body_velocity_at(body, sample_position) - velocity)
+                * laplacian_viscosity_smoothing_function(length(r));
+            let force = pressure_force + viscosity_force;
+
+            body_force += force;
+            body_torque += cross_2d(sample_position - body.position, force);
+        }
+
+        rigid_body_force += body_force;
+        add_rigid_body_impulse(b, body_force / densities[i] * time_step, body_torque / densities[i] * time_step);
+    }
+
+    return rigid_body_force;
 }
 
+fn calculate_interaction_force(i: u32) -> vec2<f32> {
+    if interaction_params.strength == 0.0 {
+        return vec2<f32>(0.0, 0.0);
+    }
+
+    let offset = interaction_params.cursor - vec2<f32>(position_x[i], position_y[i]);
+    let distance = length(offset);
+
+    if distance >= interaction_params.radius || distance < 1.0e-6 {
+        return vec2<f32>(0.0, 0.0);
+    }
+
+    // Acceleration towards the cursor, fading out linearly towards the edge of the radius.
+    let falloff = 1.0 - distance / interaction_params.radius;
+
+    return densities[i] * interaction_params.strength * falloff * offset / distance;
+}
+
+// Forces only read positions and velocities, which stay untouched until `integrate`
+// runs in a later dispatch, so the result does not depend on invocation order. The
+// rigid body accumulators are integer sums, which do not depend on it either.
 @compute
-@workgroup_size(256)
-fn calculate_hashes(
+@workgroup_size(64)
+fn compute_accelerations(
     @builtin(global_invocation_id) global_invocation_id: vec3<u32>
 ) {
     let i = global_invocation_id.x;
@@ -183,140 +286,160 @@ fn calculate_hashes(
         return;
     }
 
-    let cell_coordinate = get_cell_coordinates(position_x[i], position_y[i]);
-    hashes[i] = hash_cell(cell_coordinate.x, cell_coordinate.y) / simulation_params.particles_len;
-    indices[i] = i;
-}
+    let force = simulation_params.gravity_force + calculate_pressure_force(i) + calculate_viscosity_force(i) + calculate_rigid_body_force(i) + calculate_interaction_force(i);
 
-const bank_size:u32 = 32u;
-fn bank_conflict_free_idx(idx: u32) -> u32 {
-    var chunk_id: u32 = idx / bank_size;
-    return idx + chunk_id;
+    accelerations[i] = force / densities[i];
 }
 
-const n: u32 = 512u;
-var<workgroup> temp_array: array<u32, 532>;
-@compute @workgroup_size(256)
-fn compute_local_prefix_sum(@builtin(global_invocation_id) global_invocation_id: vec3<u32>,
-    @builtin(local_invocation_id) local_invocation_id: vec3<u32>,
-    @builtin(workgroup_id) workgroup_id: vec3<u32>) {
+// Everything but pressure, for the incompressible solvers to correct. The rigid body
+// coupling uses the pressures of the previous solver step.
+@compute
+@workgroup_size(64)
+fn compute_non_pressure_accelerations(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
 
-    var thread_id: u32 = local_invocation_id.x;
+    if i >= simulation_params.particles_len {
+        return;
+    }
 
-    var global_thread_id: u32 = global_invocation_id.x;
+    let force = simulation_params.gravity_force + calculate_viscosity_force(i) + calculate_rigid_body_force(i) + calculate_interaction_force(i);
 
-    if thread_id < (n >> 1u) {
-        temp_array[bank_conflict_free_idx(2u * thread_id)] = hashes[2u * global_thread_id];
-        temp_array[bank_conflict_free_idx(2u * thread_id + 1u)] = hashes[2u * global_thread_id + 1u];
-    }
+    accelerations[i] = force / densities[i];
+}
 
-    workgroupBarrier();
+// Reflects particles that left the domain and puts them back slightly inside it.
+fn apply_walls(i: u32) {
+    let half_size = 0.5 * simulation_params.domain_size;
+    let limit = half_size - wall_margin * simulation_params.domain_size;
 
-    var offset: u32 = 1u;
-    for (var d: u32 = n >> 1u; d > 0u; d >>= 1u) {
-        if thread_id < d {
-            var ai: u32 = offset * (2u * thread_id + 1u) - 1u;
-            var bi: u32 = offset * (2u * thread_id + 2u) - 1u;
-            temp_array[bank_conflict_free_idx(bi)] += temp_array[bank_conflict_free_idx(ai)];
-        }
+    if position_x[i] < -half_size.x || position_x[i] > half_size.x {
+        velocity_x[i] *= (-1f) * simulation_params.restitution;
+        position_x[i] = clamp(position_x[i], -limit.x, limit.x);
+    }
 
-        offset *= 2u;
+    if position_y[i] < -half_size.y || position_y[i] > half_size.y {
+        velocity_y[i] *= (-1f) * simulation_params.restitution;
+        position_y[i] = clamp(position_y[i], -limit.y, limit.y);
     }
+}
 
-    workgroupBarrier();
+// Pushes particles out of every obstacle they entered and reflects the velocity
+// component into it.
+fn apply_obstacles(i: u32) {
+    let margin = obstacle_margin * simulation_params.smoothing_radius;
 
-    if thread_id == 0u {
-        block_sums[workgroup_id.x] = temp_array[bank_conflict_free_idx(n - 1u)];
-        temp_array[bank_conflict_free_idx(n - 1u)] = 0u;
-    }
+    for (var o: u32 = 0u; o < simulation_params.obstacles_len; o++) {
+        let obstacle = obstacles[o];
+        var position = vec2<f32>(position_x[i], position_y[i]);
+        let distance = obstacle_distance(obstacle, position);
 
-    workgroupBarrier();
+        if distance >= margin {
+            continue;
+        }
 
-    for (var d: u32 = 1u; d < n; d *= 2u) {
-        offset >>= 1u;
-        if thread_id < d {
-            var ai: u32 = offset * (2u * thread_id + 1u) - 1u;
-            var bi: u32 = offset * (2u * thread_id + 2u) - 1u;
-            var temp: u32 = temp_array[bank_conflict_free_idx(ai)];
-            temp_array[bank_conflict_free_idx(ai)] = temp_array[bank_conflict_free_idx(bi)];
-            temp_array[bank_conflict_free_idx(bi)] += temp;
+        let normal = obstacle_normal(obstacle, position, margin);
+        position += (margin - distance) * normal;
+
+        var velocity = vec2<f32>(velocity_x[i], velocity_y[i]);
+        let normal_speed = dot(velocity, normal);
+        if normal_speed < 0.0 {
+            velocity -= (1.0 + simulation_params.restitution) * normal_speed * normal;
         }
-        workgroupBarrier();
-    }
 
-    if thread_id < (n >> 1u) {
-        prefix_sum_output[2u * global_thread_id] = temp_array[bank_conflict_free_idx(2u * thread_id)];
-        prefix_sum_output[2u * global_thread_id + 1u] = temp_array[bank_conflict_free_idx(2u * thread_id + 1u)];
+        position_x[i] = position.x;
+        position_y[i] = position.y;
+        velocity_x[i] = velocity.x;
+        velocity_y[i] = velocity.y;
     }
 }
 
-@compute @workgroup_size(256)
-fn compute_block_sums(@builtin(global_invocation_id) global_invocation_id: vec3<u32>,
-    @builtin(local_invocation_id) local_invocation_id: vec3<u32>,
-    @builtin(workgroup_id) workgroup_id: vec3<u32>) {
+// Same as `apply_obstacles`, relative to the moving body surface, for particles that
+// got through the pressure of the rigid body samples. The body takes the
+// opposite of the velocity change.
+fn apply_rigid_bodies(i: u32) {
+    let margin = obstacle_margin * simulation_params.smoothing_radius;
 
-    var thread_id: u32 = local_invocation_id.x;
+    for (var b: u32 = 0u; b < simulation_params.rigid_bodies_len; b++) {
+        let body = rigid_bodies[b];
+        var position = vec2<f32>(position_x[i], position_y[i]);
+        let distance = body_distance(body, position);
 
-    var global_thread_id: u32 = global_invocation_id.x;
-
-    if thread_id < (n >> 1u) {
-        temp_array[bank_conflict_free_idx(2u * thread_id)] = block_sums[2u * global_thread_id];
-        temp_array[bank_conflict_free_idx(2u * thread_id + 1u)] = block_sums[2u * global_thread_id + 1u];
-    }
+        if distance >= margin {
+            continue;
+        }
 
-    workgroupBarrier();
+        let normal = body_normal(body, position, margin);
+        position += (margin - distance) * normal;
+        position_x[i] = position.x;
+        position_y[i] = position.y;
 
-    var offset: u32 = 1u;
-    for (var d: u32 = n >> 1u; d > 0u; d >>= 1u) {
-        if thread_id < d {
-            var ai: u32 = offset * (2u * thread_id + 1u) - 1u;
-            var bi: u32 = offset * (2u * thread_id + 2u) - 1u;
-            temp_array[bank_conflict_free_idx(bi)] += temp_array[bank_conflict_free_idx(ai)];
+        let velocity = vec2<f32>(velocity_x[i], velocity_y[i]);
+        let normal_speed = dot(velocity - body_velocity_at(body, position), normal);
+        if normal_speed >= 0.0 {
+            continue;
         }
 
-        offset *= 2u;
+        let velocity_change = -(1.0 + simulation_params.restitution) * normal_speed * normal;
+        velocity_x[i] = velocity.x + velocity_change.x;
+        velocity_y[i] = velocity.y + velocity_change.y;
+
+        add_rigid_body_impulse(b, velocity_change, cross_2d(position - body.position, velocity_change));
     }
+}
 
-    workgroupBarrier();
+fn apply_boundaries(i: u32) {
+    apply_walls(i);
+    apply_obstacles(i);
+    apply_rigid_bodies(i);
+}
 
-    if thread_id == 0u {
-        temp_array[bank_conflict_free_idx(n - 1u)] = 0u;
-    }
-    workgroupBarrier();
-
-    for (var d: u32 = 1u; d < n; d *= 2u) {
-        offset >>= 1u;
-        if thread_id < d {
-            var ai: u32 = offset * (2u * thread_id + 1u) - 1u;
-            var bi: u32 = offset * (2u * thread_id + 2u) - 1u;
-            var temp: u32 = temp_array[bank_conflict_free_idx(ai)];
-            temp_array[bank_conflict_free_idx(ai)] = temp_array[bank_conflict_free_idx(bi)];
-            temp_array[bank_conflict_free_idx(bi)] += temp;
-        }
-        workgroupBarrier();
-    }
+// Symplectic Euler: full kick with the new acceleration, then drift.
+@compute
+@workgroup_size(64)
+fn integrate(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
 
-    if thread_id < (n >> 1u) {
-        sums_output[2u * global_thread_id] = temp_array[bank_conflict_free_idx(2u * thread_id)];
-        sums_output[2u * global_thread_id + 1u] = temp_array[bank_conflict_free_idx(2u * thread_id + 1u)];
+    if i >= simulation_params.particles_len {
+        return;
     }
+
+    let time_step = simulation_params.solver_time_step;
+    let acceleration = accelerations[i];
+
+    velocity_x[i] += acceleration.x * time_step;
+    velocity_y[i] += acceleration.y * time_step;
+    position_x[i] += velocity_x[i] * time_step;
+    position_y[i] += velocity_y[i] * time_step;
+
+    apply_boundaries(i);
 }
 
-@compute @workgroup_size(256)
-fn add_block_sums_to_prefix_sum_output(@builtin(global_invocation_id) global_invocation_id: vec3<u32>,
-    @builtin(local_invocation_id) local_invocation_id: vec3<u32>,
-    @builtin(workgroup_id) workgroup_id: vec3<u32>) {
-    var thread_id: u32 = local_invocation_id.x;
-    var global_thread_id: u32 = global_invocation_id.x;
-    if thread_id < (n >> 1u) {
-        prefix_sum_output[2u * global_thread_id] += sums_output[workgroup_id.x];
-        prefix_sum_output[2u * global_thread_id + 1u] += sums_output[workgroup_id.x];
+// Leapfrog: half kick, run before and after `drift`.
+@compute
+@workgroup_size(64)
+fn half_kick(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
+
+    if i >= simulation_params.particles_len {
+        return;
     }
+
+    let half_time_step = 0.5 * simulation_params.solver_time_step;
+    let acceleration = accelerations[i];
+
+    velocity_x[i] += acceleration.x * half_time_step;
+    velocity_y[i] += acceleration.y * half_time_step;
 }
 
 @compute
 @workgroup_size(64)
-fn main(
+fn drift(
     @builtin(global_invocation_id) global_invocation_id: vec3<u32>
 ) {
     let i = global_invocation_id.x;
@@ -325,23 +448,54 @@ fn main(
         return;
     }
 
-    let force = simulation_params.gravity_force + calculate_pressure_force(i) + calculate_viscosity_force(i);
+    let time_step = simulation_params.solver_time_step;
 
-    let acceleration = (force / densities[i]) * simulation_params.time_step;
+    position_x[i] += velocity_x[i] * time_step;
+    position_y[i] += velocity_y[i] * time_step;
 
-    velocity_x[i] += acceleration.x;
-    velocity_y[i] += acceleration.y;
-    position_x[i] += velocity_x[i] * simulation_params.time_step;
-    position_y[i] += velocity_y[i] * simulation_params.time_step;
+    apply_boundaries(i);
+}
 
-    if position_x[i] < -1.0 || position_x[i] > 1.0 {
-        velocity_x[i] *= (-1f) * simulation_params.restitution;
-        position_x[i] = clamp(position_x[i], -0.99, 0.99);
-    }
+// Velocity Verlet, first half: advances positions with the current acceleration and
+// predicts the velocity the next force evaluation sees.
+@compute
+@workgroup_size(64)
+fn verlet_predict(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
 
-    if position_y[i] < -1.0 || position_y[i] > 1.0 {
-        velocity_y[i] *= (-1f) * simulation_params.restitution;
-        position_y[i] = clamp(position_y[i], -0.99, 0.99);
+    if i >= simulation_params.particles_len {
+        return;
     }
+
+    let time_step = simulation_params.solver_time_step;
+    let acceleration = accelerations[i];
+    previous_accelerations[i] = acceleration;
+
+    position_x[i] += (velocity_x[i] + 0.5 * acceleration.x * time_step) * time_step;
+    position_y[i] += (velocity_y[i] + 0.5 * acceleration.y * time_step) * time_step;
+    velocity_x[i] += acceleration.x * time_step;
+    velocity_y[i] += acceleration.y * time_step;
+
+    apply_boundaries(i);
 }
 
+// Velocity Verlet, second half: replaces the predicted velocity update by the average
+// of the old and new accelerations.
+@compute
+@workgroup_size(64)
+fn verlet_correct(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let i = global_invocation_id.x;
+
+    if i >= simulation_params.particles_len {
+        return;
+    }
+
+    let correction = 0.5 * (accelerations[i] - previous_accelerations[i]) * simulation_params.solver_time_step;
+
+    velocity_x[i] += correction.x;
+    velocity_y[i] += correction.y;
+}
@@ -1,9 +1,18 @@
+struct RadixSortParams {
+    radix_mask_id: u32,
+    len: u32,
+    num_blocks: u32,
+    _padding: u32,
+};
 
-
-@binding(0) @group(0) var<storage, read> input :array<u32>;
-@binding(1) @group(0) var<storage, read_write> output :array<vec4<u32>>;
-@binding(2) @group(0) var<storage, read_write> sums: array<u32>;
-@binding(0) @group(1) var<uniform> radixMaskId:u32;
+@binding(0) @group(0) var<storage, read> keys_input: array<u32>;
+@binding(1) @group(0) var<storage, read> values_input: array<u32>;
+@binding(2) @group(0) var<storage, read_write> keys_output: array<u32>;
+@binding(3) @group(0) var<storage, read_write> values_output: array<u32>;
+@binding(0) @group(1) var<storage, read_write> local_scans: array<vec4<u32>>;
+@binding(1) @group(1) var<storage, read_write> sums: array<u32>;
+@binding(2) @group(1) var<storage, read_write> block_sums: array<u32>;
+@binding(0) @group(2) var<uniform> params: RadixSortParams;
 const bank_size:u32 = 32u;
 const n:u32 = 512u;
 var<workgroup> temp0: array<u32,532>;
@@ -14,39 +23,34 @@ fn bank_conflict_free_idx(idx: u32) -> u32 {
     var chunk_id: u32 = idx / bank_size;
     return idx + chunk_id;
 }
- 
+
+fn digit(key: u32) -> u32 {
+    return (key >> (params.radix_mask_id << 1u)) & 3u;
+}
+
+fn mark_digit(idx: u32, global_idx: u32) {
+    if global_idx >= params.len {
+        return;
+    }
+
+    switch(digit(keys_input[global_idx])) {
+        case 0u: {temp0[bank_conflict_free_idx(idx)] = 1u;}
+        case 1u: {temp1[bank_conflict_free_idx(idx)] = 1u;}
+        case 2u: {temp2[bank_conflict_free_idx(idx)] = 1u;}
+        case 3u: {temp3[bank_conflict_free_idx(idx)] = 1u;}
+        default {}
+    }
+}
+
 @compute @workgroup_size(256)
-fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>,
+fn compute_local_digit_scans(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>,
     @builtin(local_invocation_id) LocalInvocationID: vec3<u32>,
     @builtin(workgroup_id) WorkgroupID: vec3<u32>) {
     var thid: u32 = LocalInvocationID.x;
     var globalThid: u32 = GlobalInvocationID.x;
-    var mask: u32 = u32(3) << (radixMaskId << 1u);
     if thid < (n >> 1u) {
-
-        var val: u32 = (input[2u * globalThid] & mask) >> (radixMaskId << 1u);
-
-        if val == 0u {
-            temp0[bank_conflict_free_idx(2u * thid)] = 1u;
-        } else if val == 1 {
-            temp1[bank_conflict_free_idx(2u * thid)] = 1u;
-        } else if val == 2 {
-            temp2[bank_conflict_free_idx(2u * thid)] = 1u;
-        } else if val == 3 {
-            temp3[bank_conflict_free_idx(2u * thid)] = 1u;
-        }
-
-        val = (input[2u * globalThid + 1u] & mask) >> (radixMaskId << 1u);
-
-        if val == 0u {
-            temp0[bank_conflict_free_idx(2u * thid + 1u)] = 1u;
-        } else if val == 1 {
-            temp1[bank_conflict_free_idx(2u * thid + 1u)] = 1u;
-        } else if val == 2 {
-            temp2[bank_conflict_free_idx(2u * thid + 1u)] = 1u;
-        } else if val == 3 {
-            temp3[bank_conflict_free_idx(2u * thid + 1u)] = 1u;
-        }
+        mark_digit(2u * thid, 2u * globalThid);
+        mark_digit(2u * thid + 1u, 2u * globalThid + 1u);
     }
     workgroupBarrier();
     var offset: u32 = 1u;
@@ -66,6 +70,11 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>,
     }
 
     if thid == 0u {
+        sums[WorkgroupID.x] = temp0[bank_conflict_free_idx(n - 1u)];
+        sums[params.num_blocks + WorkgroupID.x] = temp1[bank_conflict_free_idx(n - 1u)];
+        sums[2u * params.num_blocks + WorkgroupID.x] = temp2[bank_conflict_free_idx(n - 1u)];
+        sums[3u * params.num_blocks + WorkgroupID.x] = temp3[bank_conflict_free_idx(n - 1u)];
+
         temp0[bank_conflict_free_idx(n - 1u)] = 0u;
         temp1[bank_conflict_free_idx(n - 1u)] = 0u;
         temp2[bank_conflict_free_idx(n - 1u)] = 0u;
@@ -97,46 +106,166 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>,
         }
         workgroupBarrier();
     }
-    if thid == 0u {
-        var count0: u32 = temp0[bank_conflict_free_idx(2u * 255u)];
-        var count1: u32 = temp1[bank_conflict_free_idx(2u * 255u)];
-        var count2: u32 = temp2[bank_conflict_free_idx(2u * 255u)];
-        var count3: u32 = temp3[bank_conflict_free_idx(2u * 255u)];
-
-        var last: u32 = (input[2u * ((WorkgroupID.x + 1u) * 256u - 1u)] & mask) >> (radixMaskId << 1u);
-        switch(last) {
-              case 0u: {count0 += 1u;}
-              case 1u: {count1 += 1u;}
-              case 2u: {count2 += 1u;}
-              case 3u: {count3 += 1u;}
-              default {}
-          }
-
-        last = (input[2u * ((WorkgroupID.x + 1u) * 256u - 1u) + 1u] & mask) >> (radixMaskId << 1u);
-        switch(last) {
-              case 0u: {count0 += 1u;}
-              case 1u: {count1 += 1u;}
-              case 2u: {count2 += 1u;}
-              case 3u: {count3 += 1u;}
-              default {}
-          }
-
-        sums[WorkgroupID.x * 4u] = count0;
-        sums[WorkgroupID.x * 4u + 1u] = count1;
-        sums[WorkgroupID.x * 4u + 2u] = count2;
-        sums[WorkgroupID.x * 4u + 3u] = count3;
-    }
     if thid < (n >> 1u) {
-        output[2u * globalThid].x = temp0[bank_conflict_free_idx(2u * thid)];
-        output[2u * globalThid + 1u].x = temp0[bank_conflict_free_idx(2u * thid + 1u)];
+        local_scans[2u * globalThid] = vec4<u32>(
+            temp0[bank_conflict_free_idx(2u * thid)],
+            temp1[bank_conflict_free_idx(2u * thid)],
+            temp2[bank_conflict_free_idx(2u * thid)],
+            temp3[bank_conflict_free_idx(2u * thid)],
+        );
+        local_scans[2u * globalThid + 1u] = vec4<u32>(
+            temp0[bank_conflict_free_idx(2u * thid + 1u)],
+            temp1[bank_conflict_free_idx(2u * thid + 1u)],
+            temp2[bank_conflict_free_idx(2u * thid + 1u)],
+            temp3[bank_conflict_free_idx(2u * thid + 1u)],
+        );
+    }
+}
+
+fn load_sum(idx: u32) -> u32 {
+    if idx >= 4u * params.num_blocks {
+        return 0u;
+    }
+
+    return sums[idx];
+}
+
+fn store_sum(idx: u32, value: u32) {
+    if idx < 4u * params.num_blocks {
+        sums[idx] = value;
+    }
+}
+
+@compute @workgroup_size(256)
+fn compute_local_prefix_sum(@builtin(global_invocation_id) global_invocation_id: vec3<u32>,
+    @builtin(local_invocation_id) local_invocation_id: vec3<u32>,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>) {
+
+    var thread_id: u32 = local_invocation_id.x;
+
+    var global_thread_id: u32 = global_invocation_id.x;
+
+    if thread_id < (n >> 1u) {
+        temp0[bank_conflict_free_idx(2u * thread_id)] = load_sum(2u * global_thread_id);
+        temp0[bank_conflict_free_idx(2u * thread_id + 1u)] = load_sum(2u * global_thread_id + 1u);
+    }
+
+    workgroupBarrier();
+
+    var offset: u32 = 1u;
+    for (var d: u32 = n >> 1u; d > 0u; d >>= 1u) {
+        if thread_id < d {
+            var ai: u32 = offset * (2u * thread_id + 1u) - 1u;
+            var bi: u32 = offset * (2u * thread_id + 2u) - 1u;
+            temp0[bank_conflict_free_idx(bi)] += temp0[bank_conflict_free_idx(ai)];
+        }
+
+        offset *= 2u;
+
+        workgroupBarrier();
+    }
+
+    if thread_id == 0u {
+        block_sums[workgroup_id.x] = temp0[bank_conflict_free_idx(n - 1u)];
+        temp0[bank_conflict_free_idx(n - 1u)] = 0u;
+    }
+
+    workgroupBarrier();
+
+    for (var d: u32 = 1u; d < n; d *= 2u) {
+        offset >>= 1u;
+        if thread_id < d {
+            var ai: u32 = offset * (2u * thread_id + 1u) - 1u;
+            var bi: u32 = offset * (2u * thread_id + 2u) - 1u;
+            var temp: u32 = temp0[bank_conflict_free_idx(ai)];
+            temp0[bank_conflict_free_idx(ai)] = temp0[bank_conflict_free_idx(bi)];
+            temp0[bank_conflict_free_idx(bi)] += temp;
+        }
+        workgroupBarrier();
+    }
+
+    if thread_id < (n >> 1u) {
+        store_sum(2u * global_thread_id, temp0[bank_conflict_free_idx(2u * thread_id)]);
+        store_sum(2u * global_thread_id + 1u, temp0[bank_conflict_free_idx(2u * thread_id + 1u)]);
+    }
+}
+
+@compute @workgroup_size(256)
+fn compute_block_sums(@builtin(global_invocation_id) global_invocation_id: vec3<u32>,
+    @builtin(local_invocation_id) local_invocation_id: vec3<u32>,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>) {
 
-        output[2u * globalThid].y = temp1[bank_conflict_free_idx(2u * thid)];
-        output[2u * globalThid + 1u].y = temp1[bank_conflict_free_idx(2u * thid + 1u)];
+    var thread_id: u32 = local_invocation_id.x;
 
-        output[2u * globalThid].z = temp2[bank_conflict_free_idx(2u * thid)];
-        output[2u * globalThid + 1u].z = temp2[bank_conflict_free_idx(2u * thid + 1u)];
+    var global_thread_id: u32 = global_invocation_id.x;
 
-        output[2u * globalThid].w = temp3[bank_conflict_free_idx(2u * thid)];
-        output[2u * globalThid + 1u].w = temp3[bank_conflict_free_idx(2u * thid + 1u)];
+    if thread_id < (n >> 1u) {
+        temp0[bank_conflict_free_idx(2u * thread_id)] = block_sums[2u * global_thread_id];
+        temp0[bank_conflict_free_idx(2u * thread_id + 1u)] = block_sums[2u * global_thread_id + 1u];
+    }
+
+    workgroupBarrier();
+
+    var offset: u32 = 1u;
+    for (var d: u32 = n >> 1u; d > 0u; d >>= 1u) {
+        if thread_id < d {
+            var ai: u32 = offset * (2u * thread_id + 1u) - 1u;
+            var bi: u32 = offset * (2u * thread_id + 2u) - 1u;
+            temp0[bank_conflict_free_idx(bi)] += temp0[bank_conflict_free_idx(ai)];
+        }
+
+        offset *= 2u;
+
+        workgroupBarrier();
     }
+
+    if thread_id == 0u {
+        temp0[bank_conflict_free_idx(n - 1u)] = 0u;
+    }
+    workgroupBarrier();
+
+    for (var d: u32 = 1u; d < n; d *= 2u) {
+        offset >>= 1u;
+        if thread_id < d {
+            var ai: u32 = offset * (2u * thread_id + 1u) - 1u;
+            var bi: u32 = offset * (2u * thread_id + 2u) - 1u;
+            var temp: u32 = temp0[bank_conflict_free_idx(ai)];
+            temp0[bank_conflict_free_idx(ai)] = temp0[bank_conflict_free_idx(bi)];
+            temp0[bank_conflict_free_idx(bi)] += temp;
+        }
+        workgroupBarrier();
+    }
+
+    if thread_id < (n >> 1u) {
+        block_sums[2u * global_thread_id] = temp0[bank_conflict_free_idx(2u * thread_id)];
+        block_sums[2u * global_thread_id + 1u] = temp0[bank_conflict_free_idx(2u * thread_id + 1u)];
+    }
+}
+
+@compute @workgroup_size(256)
+fn add_block_sums_to_prefix_sum_output(@builtin(global_invocation_id) global_invocation_id: vec3<u32>,
+    @builtin(local_invocation_id) local_invocation_id: vec3<u32>,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>) {
+    var thread_id: u32 = local_invocation_id.x;
+    var global_thread_id: u32 = global_invocation_id.x;
+    if thread_id < (n >> 1u) {
+        store_sum(2u * global_thread_id, load_sum(2u * global_thread_id) + block_sums[workgroup_id.x]);
+        store_sum(2u * global_thread_id + 1u, load_sum(2u * global_thread_id + 1u) + block_sums[workgroup_id.x]);
+    }
+}
+
+@compute @workgroup_size(256)
+fn scatter(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    let i = global_invocation_id.x;
+
+    if i >= params.len {
+        return;
+    }
+
+    let key = keys_input[i];
+    let d = digit(key);
+    let destination = sums[d * params.num_blocks + i / n] + local_scans[i][d];
+
+    keys_output[destination] = key;
+    values_output[destination] = values_input[i];
 }
@@ -0,0 +1,107 @@
+@group(0) @binding(0) var<storage, read_write> rigid_bodies: array<RigidBody>;
+@group(0) @binding(1) var<storage, read_write> rigid_body_impulses: array<RigidBodyImpulse>;
+
+@group(1) @binding(0) var<storage, read> obstacle_vertices: array<vec2<f32>>;
+
+// World-space point of the outline furthest along `direction`.
+fn body_support(body: RigidBody, direction: vec2<f32>) -> vec2<f32> {
+    let local_direction = rotate(direction, -body.angle);
+    var local: vec2<f32>;
+
+    switch body.shape.kind {
+        case obstacle_circle: {
+            local = body.shape.params.z * local_direction;
+        }
+        case obstacle_box: {
+            let half_size = body.shape.params.zw;
+            local = select(-half_size, half_size, local_direction >= vec2<f32>(0.0, 0.0));
+        }
+        default: {
+            local = obstacle_vertices[body.shape.vertex_offset];
+            for (var v: u32 = 1u; v < body.shape.vertex_count; v++) {
+                let vertex = obstacle_vertices[body.shape.vertex_offset + v];
+                if dot(vertex, local_direction) > dot(local, local_direction) {
+                    local = vertex;
+                }
+            }
+        }
+    }
+
+    return body.position + rotate(local, body.angle);
+}
+
+// Pushes the body back inside the domain and applies a restitution impulse at the
+// deepest point of every wall it crossed.
+fn collide_with_walls(body: ptr<function, RigidBody>) {
+    let half_size = 0.5 * simulation_params.domain_size;
+    var directions = array<vec2<f32>, 4>(
+        vec2<f32>(-1.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, -1.0),
+        vec2<f32>(0.0, 1.0),
+    );
+
+    for (var k: u32 = 0u; k < 4u; k++) {
+        let direction = directions[k];
+        let support = body_support(*body, direction);
+        let penetration = dot(support, direction) - dot(half_size, abs(direction));
+
+        if penetration <= 0.0 {
+            continue;
+        }
+
+        (*body).position -= penetration * direction;
+
+        let contact = support - penetration * direction;
+        let normal = -direction;
+        let normal_speed = dot(body_velocity_at(*body, contact), normal);
+        if normal_speed >= 0.0 {
+            continue;
+        }
+
+        let arm_cross_normal = cross_2d(contact - (*body).position, normal);
+        let impulse = -(1.0 + simulation_params.restitution) * normal_speed
+            / ((*body).inverse_mass + (*body).inverse_inertia * arm_cross_normal * arm_cross_normal);
+
+        (*body).velocity += impulse * (*body).inverse_mass * normal;
+        (*body).angular_velocity += impulse * (*body).inverse_inertia * arm_cross_normal;
+    }
+}
+
+// Reads and clears component `k` of the accumulators of body `b`.
+fn take_impulse(b: u32, k: u32) -> f32 {
+    let fraction = atomicExchange(&rigid_body_impulses[b].fractions[k], 0u);
+    let whole = atomicExchange(&rigid_body_impulses[b].wholes[k], 0);
+
+    return f32(whole) + f32(fraction) / impulse_fraction_scale;
+}
+
+// Runs after the particles of a solver step have been moved: applies the impulses
+// they left in the accumulators and gravity, then advances the bodies.
+@compute
+@workgroup_size(64)
+fn integrate_rigid_bodies(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>
+) {
+    let b = global_invocation_id.x;
+
+    if b >= simulation_params.rigid_bodies_len {
+        return;
+    }
+
+    var body = rigid_bodies[b];
+    let time_step = simulation_params.solver_time_step;
+    let particle_mass = simulation_params.particle_mass;
+    let linear_impulse = particle_mass * vec2<f32>(take_impulse(b, 0u), take_impulse(b, 1u));
+    let angular_impulse = particle_mass * take_impulse(b, 2u);
+    let gravity = simulation_params.gravity_force / simulation_params.rest_density;
+
+    body.velocity += linear_impulse * body.inverse_mass + gravity * time_step;
+    body.angular_velocity += angular_impulse * body.inverse_inertia;
+    body.position += body.velocity * time_step;
+    body.angle += body.angular_velocity * time_step;
+
+    collide_with_walls(&body);
+
+    rigid_bodies[b] = body;
+}
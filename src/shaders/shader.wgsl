@@ -15,7 +15,13 @@ struct VertexOutput {
     @location(1) local_position: vec2<f32>,
 }
 
-const PARTICLE_SCALE: f32 = 1.0 / 15.0;
+struct ViewTransform {
+    scale: vec2<f32>,
+    offset: vec2<f32>,
+    particle_size: f32,
+};
+
+@group(0) @binding(0) var<uniform> view_transform: ViewTransform;
 
 @vertex
 fn vs_main(
@@ -38,9 +44,9 @@ fn vs_main(
 
     output.local_position = model.position;
 
-    let scaled = model.position * PARTICLE_SCALE;
+    let scaled = model.position * view_transform.particle_size;
     let moved = scaled + vec2<f32>(particle.position_x, particle.position_y);
-output.clip_position = vec4<f32>(moved, 0.0, 1.0); 
+    output.clip_position = vec4<f32>(moved * view_transform.scale + view_transform.offset, 0.0, 1.0);
 
     return output;
 }
@@ -0,0 +1,128 @@
+// Particle buffers, neighbor grid and kernels shared by every SPH solver. Prepended
+// after `common.wgsl` to the shaders that need them.
+const pi_value: f32 = 3.14159;
+
+@group(0) @binding(0) var<storage, read_write> position_x: array<f32>;
+@group(0) @binding(1) var<storage, read_write> position_y: array<f32>;
+@group(0) @binding(2) var<storage, read_write> velocity_x: array<f32>;
+@group(0) @binding(3) var<storage, read_write> velocity_y: array<f32>;
+
+@group(1) @binding(0) var<storage, read_write> densities: array<f32>;
+@group(1) @binding(1) var<storage, read_write> pressures: array<f32>;
+@group(1) @binding(2) var<storage, read_write> accelerations: array<vec2<f32>>;
+@group(1) @binding(3) var<storage, read_write> previous_accelerations: array<vec2<f32>>;
+
+@group(3) @binding(0) var<storage, read> sorted_indices: array<u32>;
+@group(3) @binding(1) var<storage, read> cell_ranges: array<vec2<u32>>;
+
+struct BoundaryParticle {
+    position: vec2<f32>,
+    // rest_density times the sampled volume
+    mass: f32,
+    _padding: u32,
+};
+
+// Static samples of the walls and obstacles, sorted by the same cell hash as the fluid.
+@group(3) @binding(6) var<storage, read> boundary_particles: array<BoundaryParticle>;
+@group(3) @binding(7) var<storage, read> boundary_cell_ranges: array<vec2<u32>>;
+
+// Progress of the iterative pressure solvers, bound by each of them in group 3.
+struct PressureSolveState {
+    // Average relative compression at the last check.
+    density_error: f32,
+    // Pressure corrections applied in the current solver step.
+    iterations: u32,
+    // Average relative density change at the last check of the DFSPH divergence solve.
+    divergence_error: f32,
+    // Divergence corrections applied in the current solver step.
+    divergence_iterations: u32,
+    converged: u32,
+    _padding: u32,
+};
+
+const empty_cell_key: u32 = 0xffffffffu;
+
+fn neighbor_cell_keys(i: u32) -> array<u32, 9> {
+    let cell = get_cell_coordinates(position_x[i], position_y[i]);
+    var keys: array<u32, 9>;
+    var k: u32 = 0u;
+
+    for (var offset_y: i32 = -1; offset_y <= 1; offset_y++) {
+        for (var offset_x: i32 = -1; offset_x <= 1; offset_x++) {
+            let key = hash_cell(cell + vec2<i32>(offset_x, offset_y));
+            keys[k] = key;
+
+            for (var m: u32 = 0u; m < k; m++) {
+                if keys[m] == key {
+                    keys[k] = empty_cell_key;
+                }
+            }
+
+            k++;
+        }
+    }
+
+    return keys;
+}
+
+fn density_smoothing_function(r_x: f32, r_y: f32) -> f32 {
+    let h = simulation_params.smoothing_radius;
+    let r_length_sq = r_x * r_x + r_y * r_y;
+
+    if r_length_sq > simulation_params.smoothing_radius_sq {
+        return 0.0;
+    }
+
+    let r_length = sqrt(r_length_sq);
+    let h_minus_r = simulation_params.smoothing_radius_sq - r_length * r_length;
+
+    return simulation_params.density_smoothing_function_coeff * h_minus_r * h_minus_r * h_minus_r;
+}
+
+// Volume-weighted density of the boundary particles around `position`, which makes up
+// for the fluid missing beyond walls and obstacles. `keys` are the neighbor cells.
+fn boundary_density_at(position: vec2<f32>, keys: array<u32, 9>) -> f32 {
+    var density: f32 = 0.0;
+
+    if simulation_params.boundary_particles == 0u {
+        return density;
+    }
+
+    for (var k: u32 = 0u; k < 9u; k++) {
+        if keys[k] == empty_cell_key {
+            continue;
+        }
+
+        let range = boundary_cell_ranges[keys[k]];
+        for (var s: u32 = range.x; s < range.y; s++) {
+            let boundary_particle = boundary_particles[s];
+            density += boundary_particle.mass * density_smoothing_function(position.x - boundary_particle.position.x, position.y - boundary_particle.position.y);
+        }
+    }
+
+    return density;
+}
+
+fn gradient_pressure_smoothing_function(r_x: f32, r_y: f32) -> vec2<f32> {
+    let r_length_sq = r_x * r_x + r_y * r_y;
+
+    if r_length_sq > simulation_params.smoothing_radius_sq || r_length_sq < 1.0e-8 {
+        return vec2<f32>(0.0, 0.0);
+    }
+
+    let r_length = sqrt(r_length_sq);
+    let h_minus_r = simulation_params.smoothing_radius - r_length;
+    let coeff = simulation_params.gradient_pressure_smoothing_function_coeff * pow(h_minus_r, 2.0) / r_length ;
+
+    return vec2<f32>(coeff * r_x, coeff * r_y);
+}
+
+fn laplacian_viscosity_smoothing_function(r_length: f32) -> f32 {
+    let h = simulation_params.smoothing_radius;
+
+    if r_length < 0.0001 || r_length > h {
+        return 0.0;
+    }
+
+    return simulation_params.laplacian_viscosity_smoothing_function_coeff * (h - r_length);
+}
@@ -0,0 +1,89 @@
+const workgroup_size: u32 = 256u;
+const nanos_per_second: f32 = 1.0e9;
+
+@group(0) @binding(0) var<storage, read> velocity_x: array<f32>;
+@group(0) @binding(1) var<storage, read> velocity_y: array<f32>;
+@group(0) @binding(2) var<storage, read> accelerations: array<vec2<f32>>;
+
+struct TimeStepState {
+    time_step: f32,
+    max_speed: f32,
+    max_acceleration: f32,
+    _padding: u32,
+    // Simulated time in nanoseconds as (low, high) words, so it does not lose
+    // precision the way an f32 sum of small steps would.
+    elapsed_nanos: vec2<u32>,
+};
+
+@group(1) @binding(0) var<storage, read_write> state: TimeStepState;
+
+var<workgroup> max_speeds: array<f32, workgroup_size>;
+var<workgroup> max_accelerations: array<f32, workgroup_size>;
+
+fn adaptive_time_step(max_speed: f32, max_acceleration: f32) -> f32 {
+    let max_time_step = simulation_params.time_step / f32(simulation_params.substeps);
+
+    if simulation_params.adaptive_time_step == 0u {
+        return max_time_step;
+    }
+
+    let h = simulation_params.smoothing_radius;
+    // Incompressible solvers are not bound by the speed of sound, only by the flow.
+    let speed_of_sound = select(0.0, speed_of_sound(), simulation_params.pressure_solver == pressure_solver_wcsph);
+    let gravity_acceleration = length(simulation_params.gravity_force) / simulation_params.rest_density;
+    let acceleration = max(max_acceleration, gravity_acceleration);
+    let kinematic_viscosity = simulation_params.viscosity / simulation_params.rest_density;
+
+    var time_step = simulation_params.cfl_number * h / max(speed_of_sound + max_speed, 1.0e-6);
+    if acceleration > 0.0 {
+        time_step = min(time_step, 0.25 * sqrt(h / acceleration));
+    }
+    if kinematic_viscosity > 0.0 {
+        time_step = min(time_step, 0.125 * h * h / kinematic_viscosity);
+    }
+
+    return min(max(time_step, simulation_params.min_time_step), max_time_step);
+}
+
+// Runs as a single workgroup: every invocation strides over the particles, then the
+// per-invocation maxima are reduced in workgroup memory.
+@compute
+@workgroup_size(256)
+fn compute_time_step(
+    @builtin(local_invocation_index) local_index: u32
+) {
+    var max_speed: f32 = 0.0;
+    var max_acceleration: f32 = 0.0;
+
+    for (var i: u32 = local_index; i < simulation_params.particles_len; i += workgroup_size) {
+        let velocity = vec2<f32>(velocity_x[i], velocity_y[i]);
+        max_speed = max(max_speed, length(velocity));
+        max_acceleration = max(max_acceleration, length(accelerations[i]));
+    }
+
+    max_speeds[local_index] = max_speed;
+    max_accelerations[local_index] = max_acceleration;
+    workgroupBarrier();
+
+    for (var stride: u32 = workgroup_size / 2u; stride > 0u; stride /= 2u) {
+        if local_index < stride {
+            max_speeds[local_index] = max(max_speeds[local_index], max_speeds[local_index + stride]);
+            max_accelerations[local_index] = max(max_accelerations[local_index], max_accelerations[local_index + stride]);
+        }
+        workgroupBarrier();
+    }
+
+    if local_index != 0u {
+        return;
+    }
+
+    let time_step = adaptive_time_step(max_speeds[0], max_accelerations[0]);
+
+    state.time_step = time_step;
+    state.max_speed = max_speeds[0];
+    state.max_acceleration = max_accelerations[0];
+
+    let nanos = u32(round(time_step * nanos_per_second));
+    let low = state.elapsed_nanos.x + nanos;
+    state.elapsed_nanos = vec2<u32>(low, state.elapsed_nanos.y + select(0u, 1u, low < nanos));
+}
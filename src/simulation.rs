@@ -1,9 +1,22 @@
 use std::f32::consts::PI;
 
-use cgmath::num_traits::{Pow, pow};
+use cgmath::num_traits::Pow;
+
+use crate::checkpoint::Checkpoint;
+use crate::obstacle::Obstacle;
+use crate::pipelines::compute::{ComputePipelineState, PressureSolveState};
+use crate::pipelines::radix_sort::RadixSort;
+use crate::pipelines::readback::{ParticleFrame, ParticleReadback};
+use crate::rigid_body::{GpuRigidBody, RigidBody};
+use crate::scene::Scene;
+
+pub mod boundary;
+pub mod cpu;
+
+pub const MAX_HASH_TABLE_SIZE: u32 = 512 * 512;
 
 #[repr(C)]
-#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Particle {
     pub position_x: f32,
     pub position_y: f32,
@@ -47,8 +60,108 @@ impl Particle {
     }
 }
 
+/// Time integration scheme of one solver step.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Integrator {
+    /// Kick with the new acceleration, then drift. One force evaluation per step.
+    #[default]
+    SymplecticEuler,
+    /// Kick-drift-kick leapfrog. Two force evaluations per step.
+    Leapfrog,
+    /// Velocity Verlet with a predicted velocity for the viscosity term. Two force
+    /// evaluations per step.
+    VelocityVerlet,
+}
+
+/// Relation between density and pressure.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EquationOfState {
+    /// `stiffness * (density - rest_density)`; the speed of sound is `sqrt(stiffness)`.
+    #[default]
+    Linear,
+    /// Tait equation `B * ((density / rest_density)^7 - 1)` with
+    /// `B = rest_density * speed_of_sound^2 / 7`, which resists compression much more
+    /// strongly and keeps density variations around 1% when the speed of sound is ten
+    /// times the largest flow speed.
+    Tait,
+}
+
+/// How pressure is computed in each solver step.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PressureSolver {
+    /// Weakly compressible SPH: pressure follows from the density through the
+    /// [`EquationOfState`] in a single pass, advanced by the chosen [`Integrator`].
+    #[default]
+    Wcsph,
+    /// Predictive-corrective incompressible SPH (Solenthaler and Pajarola 2009): pressure
+    /// is corrected from the predicted density error until it drops below the tolerance
+    /// or the iteration cap is hit. Pressures are never negative, and the step is
+    /// advanced with symplectic Euler whatever the integrator.
+    Pcisph,
+    /// Divergence-free SPH (Bender and Koschier 2015): a divergence-free solve on the
+    /// velocities and a constant-density solve on the predicted velocities, both
+    /// scaled by per-particle factors from the neighborhood. Suited to large steps with
+    /// low compression; like [`PressureSolver::Pcisph`] it ignores the integrator.
+    Dfsph,
+}
+
+impl PressureSolver {
+    fn from_index(index: u32) -> Self {
+        match index {
+            1 => Self::Pcisph,
+            2 => Self::Dfsph,
+            _ => Self::Wcsph,
+        }
+    }
+
+    fn index(self) -> u32 {
+        match self {
+            Self::Wcsph => 0,
+            Self::Pcisph => 1,
+            Self::Dfsph => 2,
+        }
+    }
+}
+
+impl EquationOfState {
+    fn from_index(index: u32) -> Self {
+        match index {
+            1 => Self::Tait,
+            _ => Self::Linear,
+        }
+    }
+
+    fn index(self) -> u32 {
+        match self {
+            Self::Linear => 0,
+            Self::Tait => 1,
+        }
+    }
+}
+
+impl Integrator {
+    fn from_index(index: u32) -> Self {
+        match index {
+            1 => Self::Leapfrog,
+            2 => Self::VelocityVerlet,
+            _ => Self::SymplecticEuler,
+        }
+    }
+
+    fn index(self) -> u32 {
+        match self {
+            Self::SymplecticEuler => 0,
+            Self::Leapfrog => 1,
+            Self::VelocityVerlet => 2,
+        }
+    }
+}
+
 #[repr(C)]
-#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct SimulationParams {
     time_step: f32,
     particle_mass: f32,
@@ -61,15 +174,62 @@ pub struct SimulationParams {
     pub particles_len: u32,
 
     gravity_force: [f32; 2],
-    _padding: [f32; 2],
+    pub hash_table_size: u32,
+    substeps: u32,
 
     smoothing_radius_sq: f32,
     density_smoothing_function_coeff: f32,
     gradient_pressure_smoothing_function_coeff: f32,
     laplacian_viscosity_smoothing_function_coeff: f32,
+
+    solver_time_step: f32,
+    adaptive_time_step: u32,
+    cfl_number: f32,
+    min_time_step: f32,
+
+    integrator: u32,
+    _padding: u32,
+    domain_size: [f32; 2],
+
+    obstacles_len: u32,
+    rigid_bodies_len: u32,
+    boundary_particles: u32,
+    equation_of_state: u32,
+
+    speed_of_sound: f32,
+    clamp_negative_pressure: u32,
+    pressure_solver: u32,
+    max_pressure_iterations: u32,
+
+    density_error_tolerance: f32,
+    pcisph_scaling_factor: f32,
+    divergence_error_tolerance: f32,
+    dfsph_relaxation: f32,
 }
 
 impl SimulationParams {
+    pub const DEFAULT_CFL_NUMBER: f32 = 0.4;
+    pub const DEFAULT_DOMAIN_SIZE: [f32; 2] = [2.0, 2.0];
+    pub const DEFAULT_SPEED_OF_SOUND: f32 = 20.0;
+    /// Exponent of the Tait equation of state.
+    pub const TAIT_EXPONENT: i32 = 7;
+    pub const DEFAULT_DENSITY_ERROR_TOLERANCE: f32 = 0.001;
+    pub const DEFAULT_DIVERGENCE_ERROR_TOLERANCE: f32 = 0.01;
+    pub const DEFAULT_MAX_PRESSURE_ITERATIONS: u32 = 50;
+    /// Every solver step of a [`Simulation::advance`] is recorded into one command
+    /// buffer, so the count per step is bounded.
+    pub const MAX_SUBSTEPS: u32 = 64;
+    /// Every pressure iteration is recorded into the command buffer, so the cap is bounded.
+    pub const MAX_PRESSURE_ITERATIONS: u32 = 100;
+    /// Largest smoothing radius in particle spacings. Neighbor searches, boundary
+    /// sampling and the pressure solver setup all grow with its square.
+    pub const MAX_SMOOTHING_RADIUS_SPACINGS: f32 = 16.0;
+
+    /// Byte offset of the solver step length the GPU writes into the uniform buffer.
+    pub const SOLVER_TIME_STEP_OFFSET: u64 =
+        std::mem::offset_of!(SimulationParams, solver_time_step) as u64;
+
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         time_step: f32,
         particle_mass: f32,
@@ -80,6 +240,7 @@ impl SimulationParams {
         viscosity: f32,
         gravity_force: [f32; 2],
         particles_len: u32,
+        substeps: u32,
     ) -> Self {
         let smoothing_radius_sq: f32 = smoothing_radius * smoothing_radius;
         let density_smoothing_function_coeff: f32 = 4.0 / (PI * smoothing_radius.pow(8.0));
@@ -87,7 +248,9 @@ impl SimulationParams {
             -30.0 / (PI * smoothing_radius.pow(5.0));
         let laplacian_viscosity_smoothing_function_coeff: f32 =
             40.0 * (PI * smoothing_radius.pow(4.0));
-
+        let hash_table_size = particles_len
+            .next_power_of_two()
+            .clamp(512, MAX_HASH_TABLE_SIZE);
         Self {
             time_step,
             particle_mass,
@@ -98,11 +261,975 @@ impl SimulationParams {
             viscosity,
             gravity_force,
             particles_len,
-            _padding: [0.0; 2],
+            hash_table_size,
+            substeps: substeps.max(1),
             smoothing_radius_sq,
             density_smoothing_function_coeff,
             gradient_pressure_smoothing_function_coeff,
             laplacian_viscosity_smoothing_function_coeff,
+            solver_time_step: time_step / substeps.max(1) as f32,
+            adaptive_time_step: 0,
+            cfl_number: Self::DEFAULT_CFL_NUMBER,
+            min_time_step: 0.0,
+            integrator: Integrator::default().index(),
+            _padding: 0,
+            domain_size: Self::DEFAULT_DOMAIN_SIZE,
+            obstacles_len: 0,
+            rigid_bodies_len: 0,
+            boundary_particles: 0,
+            equation_of_state: EquationOfState::default().index(),
+            speed_of_sound: Self::DEFAULT_SPEED_OF_SOUND,
+            clamp_negative_pressure: 0,
+            pressure_solver: PressureSolver::default().index(),
+            max_pressure_iterations: Self::DEFAULT_MAX_PRESSURE_ITERATIONS,
+            density_error_tolerance: Self::DEFAULT_DENSITY_ERROR_TOLERANCE,
+            pcisph_scaling_factor: 0.0,
+            divergence_error_tolerance: Self::DEFAULT_DIVERGENCE_ERROR_TOLERANCE,
+            dfsph_relaxation: 1.0,
+        }
+    }
+
+    /// Sets the width and height of the box the particles are kept in. The box is
+    /// centred on the origin.
+    pub fn with_domain_size(mut self, domain_size: [f32; 2]) -> Self {
+        self.domain_size = domain_size;
+        self
+    }
+
+    /// Samples the domain walls and obstacle outlines with static boundary particles
+    /// that add to the density and pressure force of nearby fluid particles.
+    pub fn with_boundary_particles(mut self) -> Self {
+        self.boundary_particles = 1;
+        self
+    }
+
+    /// Switches to the Tait equation of state with the given speed of sound.
+    pub fn with_tait_equation_of_state(mut self, speed_of_sound: f32) -> Self {
+        self.equation_of_state = EquationOfState::Tait.index();
+        self.speed_of_sound = speed_of_sound;
+        self
+    }
+
+    /// Clamps negative pressures to zero, so particles at a free surface or in a
+    /// dilated region do not pull each other together.
+    pub fn with_negative_pressure_clamp(mut self) -> Self {
+        self.clamp_negative_pressure = 1;
+        self
+    }
+
+    /// Switches to the PCISPH pressure solver, which iterates until the average
+    /// relative density error is below `density_error_tolerance` or `max_iterations`
+    /// corrections have run.
+    pub fn with_pcisph(mut self, density_error_tolerance: f32, max_iterations: u32) -> Self {
+        self.pressure_solver = PressureSolver::Pcisph.index();
+        self.density_error_tolerance = density_error_tolerance;
+        self.max_pressure_iterations = max_iterations.max(1);
+        self.pcisph_scaling_factor = self
+            .lattice_gradients()
+            .pcisph_scaling_factor(self.particle_mass, self.rest_density);
+        self
+    }
+
+    /// Switches to the DFSPH pressure solver. The constant-density solve stops at an
+    /// average relative density error of `density_error_tolerance`, the divergence-free
+    /// solve once the density would change by less than `divergence_error_tolerance`
+    /// over the step; each runs at most `max_iterations` corrections.
+    pub fn with_dfsph(
+        mut self,
+        density_error_tolerance: f32,
+        divergence_error_tolerance: f32,
+        max_iterations: u32,
+    ) -> Self {
+        self.pressure_solver = PressureSolver::Dfsph.index();
+        self.density_error_tolerance = density_error_tolerance;
+        self.divergence_error_tolerance = divergence_error_tolerance;
+        self.max_pressure_iterations = max_iterations.max(1);
+        self.dfsph_relaxation = self.lattice_gradients().dfsph_relaxation();
+        self
+    }
+
+    /// Kernel gradients over the rest lattice, which only the pressure solvers need.
+    fn lattice_gradients(&self) -> LatticeGradients {
+        LatticeGradients::new(
+            self.particle_mass,
+            self.rest_density,
+            self.smoothing_radius,
+            self.density_smoothing_function_coeff,
+            self.gradient_pressure_smoothing_function_coeff,
+        )
+    }
+
+    pub fn with_integrator(mut self, integrator: Integrator) -> Self {
+        self.integrator = integrator.index();
+        self
+    }
+
+    /// Lets every solver step pick its own length from the CFL, force and viscous
+    /// criteria, never exceeding [`Self::substep_time_step`] or going below
+    /// `min_time_step`.
+    pub fn with_adaptive_time_step(mut self, cfl_number: f32, min_time_step: f32) -> Self {
+        self.adaptive_time_step = 1;
+        self.cfl_number = cfl_number;
+        self.min_time_step = min_time_step;
+        self
+    }
+
+    /// Checks the invariants the GPU kernels rely on. Scenes, checkpoints and
+    /// [`Simulation::set_params`] all go through this before params reach the GPU.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !(1..=RadixSort::MAX_LEN).contains(&self.particles_len) {
+            anyhow::bail!(
+                "Particle count must be between 1 and {}, got {}",
+                RadixSort::MAX_LEN,
+                self.particles_len
+            );
+        }
+        if !self.hash_table_size.is_power_of_two() || self.hash_table_size > MAX_HASH_TABLE_SIZE {
+            anyhow::bail!(
+                "Hash table size {} is not a power of two up to {MAX_HASH_TABLE_SIZE}",
+                self.hash_table_size
+            );
+        }
+        if !(1..=Self::MAX_SUBSTEPS).contains(&self.substeps) {
+            anyhow::bail!(
+                "Substeps must be between 1 and {}, got {}",
+                Self::MAX_SUBSTEPS,
+                self.substeps
+            );
+        }
+        if !(1..=Self::MAX_PRESSURE_ITERATIONS).contains(&self.max_pressure_iterations) {
+            anyhow::bail!(
+                "Max pressure iterations must be between 1 and {}, got {}",
+                Self::MAX_PRESSURE_ITERATIONS,
+                self.max_pressure_iterations
+            );
+        }
+
+        for (name, value) in [
+            ("time step", self.time_step),
+            ("particle mass", self.particle_mass),
+            ("rest density", self.rest_density),
+            ("smoothing radius", self.smoothing_radius),
+            ("speed of sound", self.speed_of_sound),
+            ("density error tolerance", self.density_error_tolerance),
+            (
+                "divergence error tolerance",
+                self.divergence_error_tolerance,
+            ),
+            ("domain width", self.domain_size[0]),
+            ("domain height", self.domain_size[1]),
+        ] {
+            if !(value.is_finite() && value > 0.0) {
+                anyhow::bail!("The {name} must be positive, got {value}");
+            }
+        }
+        for (name, value) in [("stiffness", self.stiffness), ("viscosity", self.viscosity)] {
+            if !(value.is_finite() && value >= 0.0) {
+                anyhow::bail!("The {name} must not be negative, got {value}");
+            }
+        }
+        if !(0.0..=1.0).contains(&self.restitution) {
+            anyhow::bail!(
+                "Restitution must be between 0 and 1, got {}",
+                self.restitution
+            );
+        }
+        if !self.gravity_force.iter().all(|value| value.is_finite()) {
+            anyhow::bail!("Gravity must be finite, got {:?}", self.gravity_force);
+        }
+        if !(self.cfl_number > 0.0 && self.cfl_number <= 1.0) {
+            anyhow::bail!(
+                "The CFL number must be greater than 0 and at most 1, got {}",
+                self.cfl_number
+            );
+        }
+        if self.adaptive_time_step != 0
+            && !(self.min_time_step > 0.0 && self.min_time_step <= self.substep_time_step())
+        {
+            anyhow::bail!(
+                "The minimum time step must be positive and at most {}, got {}",
+                self.substep_time_step(),
+                self.min_time_step
+            );
+        }
+
+        if Integrator::from_index(self.integrator).index() != self.integrator
+            || PressureSolver::from_index(self.pressure_solver).index() != self.pressure_solver
+            || EquationOfState::from_index(self.equation_of_state).index() != self.equation_of_state
+        {
+            anyhow::bail!("Unknown integrator, pressure solver or equation of state");
+        }
+
+        Ok(())
+    }
+
+    /// Time covered by one [`Simulation::step`], split into [`Self::substeps`] solver steps.
+    pub fn time_step(&self) -> f32 {
+        self.time_step
+    }
+
+    /// Side of the square each particle fills at rest density.
+    pub fn particle_spacing(&self) -> f32 {
+        (self.particle_mass / self.rest_density).sqrt()
+    }
+
+    pub fn substeps(&self) -> u32 {
+        self.substeps
+    }
+
+    pub fn substep_time_step(&self) -> f32 {
+        self.time_step / self.substeps as f32
+    }
+
+    pub fn smoothing_radius(&self) -> f32 {
+        self.smoothing_radius
+    }
+
+    pub fn obstacles_len(&self) -> u32 {
+        self.obstacles_len
+    }
+
+    pub fn rigid_bodies_len(&self) -> u32 {
+        self.rigid_bodies_len
+    }
+
+    pub fn domain_size(&self) -> [f32; 2] {
+        self.domain_size
+    }
+
+    /// Records how many obstacles and rigid bodies are bound alongside these params.
+    pub(crate) fn set_colliders_len(&mut self, obstacles_len: u32, rigid_bodies_len: u32) {
+        self.obstacles_len = obstacles_len;
+        self.rigid_bodies_len = rigid_bodies_len;
+    }
+
+    /// Lower and upper corner of the domain in world space.
+    pub fn domain_bounds(&self) -> ([f32; 2], [f32; 2]) {
+        let [width, height] = self.domain_size;
+        ([-0.5 * width, -0.5 * height], [0.5 * width, 0.5 * height])
+    }
+
+    pub fn integrator(&self) -> Integrator {
+        Integrator::from_index(self.integrator)
+    }
+
+    pub fn is_adaptive_time_step(&self) -> bool {
+        self.adaptive_time_step != 0
+    }
+
+    pub fn has_boundary_particles(&self) -> bool {
+        self.boundary_particles != 0
+    }
+
+    pub fn pressure_solver(&self) -> PressureSolver {
+        PressureSolver::from_index(self.pressure_solver)
+    }
+
+    pub fn max_pressure_iterations(&self) -> u32 {
+        self.max_pressure_iterations
+    }
+
+    pub fn density_error_tolerance(&self) -> f32 {
+        self.density_error_tolerance
+    }
+
+    pub fn divergence_error_tolerance(&self) -> f32 {
+        self.divergence_error_tolerance
+    }
+
+    /// Pressure change per unit of predicted density error, for a solver step of
+    /// `time_step` seconds.
+    pub fn pcisph_pressure_scale(&self, time_step: f32) -> f32 {
+        self.pcisph_scaling_factor / (time_step * time_step)
+    }
+
+    /// Number of times the forces are evaluated in one solver step.
+    pub fn force_evaluations(&self) -> u32 {
+        match (self.pressure_solver(), self.integrator()) {
+            (PressureSolver::Wcsph, Integrator::Leapfrog | Integrator::VelocityVerlet) => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn equation_of_state(&self) -> EquationOfState {
+        EquationOfState::from_index(self.equation_of_state)
+    }
+
+    pub fn clamps_negative_pressure(&self) -> bool {
+        self.clamp_negative_pressure != 0
+    }
+
+    /// Speed of sound of the equation of state. Mirrors `speed_of_sound` in
+    /// `common.wgsl`.
+    pub fn speed_of_sound(&self) -> f32 {
+        match self.equation_of_state() {
+            EquationOfState::Linear => self.stiffness.sqrt(),
+            EquationOfState::Tait => self.speed_of_sound,
         }
     }
+
+    /// Pressure at `density`. Mirrors `pressure_from_density` in `common.wgsl`.
+    pub fn pressure(&self, density: f32) -> f32 {
+        let pressure = match self.equation_of_state() {
+            EquationOfState::Linear => self.stiffness * (density - self.rest_density),
+            EquationOfState::Tait => {
+                let bulk_modulus = self.rest_density * self.speed_of_sound * self.speed_of_sound
+                    / Self::TAIT_EXPONENT as f32;
+                bulk_modulus * ((density / self.rest_density).powi(Self::TAIT_EXPONENT) - 1.0)
+            }
+        };
+
+        if self.clamps_negative_pressure() {
+            pressure.max(0.0)
+        } else {
+            pressure
+        }
+    }
+
+    /// Length of the next solver step given the largest particle speed and
+    /// acceleration. Mirrors `compute_time_step` in `time_step.wgsl`.
+    pub fn adaptive_time_step(&self, max_speed: f32, max_acceleration: f32) -> f32 {
+        let max_time_step = self.substep_time_step();
+
+        if !self.is_adaptive_time_step() {
+            return max_time_step;
+        }
+
+        let h = self.smoothing_radius;
+        let speed_of_sound = match self.pressure_solver() {
+            PressureSolver::Wcsph => self.speed_of_sound(),
+            PressureSolver::Pcisph | PressureSolver::Dfsph => 0.0,
+        };
+        let max_acceleration = max_acceleration.max(self.gravity_acceleration());
+        let kinematic_viscosity = self.viscosity / self.rest_density;
+
+        let mut time_step = self.cfl_number * h / (speed_of_sound + max_speed).max(1.0e-6);
+        if max_acceleration > 0.0 {
+            time_step = time_step.min(0.25 * (h / max_acceleration).sqrt());
+        }
+        if kinematic_viscosity > 0.0 {
+            time_step = time_step.min(0.125 * h * h / kinematic_viscosity);
+        }
+
+        time_step.max(self.min_time_step).min(max_time_step)
+    }
+
+    /// Acceleration a particle at rest density gets from gravity alone; the lower
+    /// bound for the force criterion before any acceleration has been measured.
+    fn gravity_acceleration(&self) -> f32 {
+        let [x, y] = self.gravity_force;
+        (x * x + y * y).sqrt() / self.rest_density
+    }
+}
+
+/// Kernel gradients around a particle on a square lattice at rest density spacing,
+/// used to size the corrections of the iterative pressure solvers once per parameter
+/// set.
+///
+/// Both solvers correct every particle from its own density error, as if its
+/// neighbors' pressures did not change. With the wide neighborhoods used here,
+/// pressure waves a few particles long get several times that density response and
+/// the correction loops diverge, so the corrections are scaled to undo the error of
+/// the stiffest lattice wave exactly. Longer waves take more iterations instead.
+struct LatticeGradients {
+    spacing: f32,
+    /// Offset of each neighbor with its density and pressure kernel gradients, both
+    /// multiples of the offset.
+    neighbors: Vec<([f32; 2], f32, f32)>,
+}
+
+impl LatticeGradients {
+    /// Wave numbers sampled per axis between zero and the lattice limit.
+    const WAVE_SAMPLES: u32 = 32;
+
+    fn new(
+        particle_mass: f32,
+        rest_density: f32,
+        smoothing_radius: f32,
+        density_coeff: f32,
+        gradient_coeff: f32,
+    ) -> Self {
+        let spacing = (particle_mass / rest_density).sqrt();
+        let extent = (smoothing_radius / spacing).ceil() as i32;
+        let h_sq = smoothing_radius * smoothing_radius;
+        let mut neighbors = Vec::new();
+
+        for i in -extent..=extent {
+            for j in -extent..=extent {
+                let r = [i as f32 * spacing, j as f32 * spacing];
+                let r_length_sq = r[0] * r[0] + r[1] * r[1];
+
+                if r_length_sq >= h_sq || r_length_sq < 1.0e-16 {
+                    continue;
+                }
+
+                let r_length = r_length_sq.sqrt();
+                let density_gradient = -6.0 * density_coeff * (h_sq - r_length_sq).powi(2);
+                let pressure_gradient =
+                    gradient_coeff * (smoothing_radius - r_length).powi(2) / r_length;
+                neighbors.push((r, density_gradient, pressure_gradient));
+            }
+        }
+
+        Self { spacing, neighbors }
+    }
+
+    /// PCISPH scaling factor `delta` at a unit time step, halved for particles with
+    /// one-sided neighborhoods at the free surface.
+    fn pcisph_scaling_factor(&self, particle_mass: f32, rest_density: f32) -> f32 {
+        let max_response = self.max_wave_response(true);
+        if max_response <= 0.0 {
+            return 0.0;
+        }
+
+        rest_density * rest_density / (2.0 * particle_mass * particle_mass * max_response)
+    }
+
+    /// Relaxation of the DFSPH corrections. The per-particle factors already account
+    /// for one-sided neighborhoods, so only the lattice waves are left to damp.
+    fn dfsph_relaxation(&self) -> f32 {
+        let max_response = self.max_wave_response(false);
+        if max_response <= 0.0 {
+            return 1.0;
+        }
+
+        let gradient_sum: f32 = self
+            .neighbors
+            .iter()
+            .map(|(r, _, pressure_gradient)| {
+                pressure_gradient * pressure_gradient * (r[0] * r[0] + r[1] * r[1])
+            })
+            .sum();
+
+        (gradient_sum / max_response).min(1.0)
+    }
+
+    /// Largest density change per unit pressure over the pressure waves `cos(k x)`,
+    /// which move the particles along the pressure gradient sum and change the density
+    /// along the density gradient sum, both weighted by `sin(k r)`. PCISPH measures the
+    /// change with the density kernel, DFSPH with the pressure kernel. The lattice is
+    /// symmetric, so a quarter of the wave numbers suffice.
+    fn max_wave_response(&self, density_kernel: bool) -> f32 {
+        let samples = Self::WAVE_SAMPLES;
+        let mut max_response = 0.0f32;
+
+        for a in 0..=samples {
+            for b in 0..=samples {
+                let k = [
+                    PI * a as f32 / (samples as f32 * self.spacing),
+                    PI * b as f32 / (samples as f32 * self.spacing),
+                ];
+                let mut density_sum = [0.0f32; 2];
+                let mut pressure_sum = [0.0f32; 2];
+
+                for (r, density_gradient, pressure_gradient) in &self.neighbors {
+                    let wave = (k[0] * r[0] + k[1] * r[1]).sin();
+                    let density_gradient = if density_kernel {
+                        *density_gradient
+                    } else {
+                        *pressure_gradient
+                    };
+
+                    density_sum[0] += wave * density_gradient * r[0];
+                    density_sum[1] += wave * density_gradient * r[1];
+                    pressure_sum[0] += wave * pressure_gradient * r[0];
+                    pressure_sum[1] += wave * pressure_gradient * r[1];
+                }
+
+                max_response = max_response
+                    .max(density_sum[0] * pressure_sum[0] + density_sum[1] * pressure_sum[1]);
+            }
+        }
+
+        max_response
+    }
+}
+
+/// Cursor force applied by the `compute_accelerations` kernel to particles within `radius` of `cursor`.
+///
+/// `strength` is an acceleration: positive values pull particles towards the cursor,
+/// negative values push them away and zero disables the interaction.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InteractionParams {
+    pub cursor: [f32; 2],
+    pub radius: f32,
+    pub strength: f32,
+}
+
+/// GPU simulation of one particle set.
+///
+/// Owns the compute pipelines and the particle buffers, and keeps its own handles to
+/// the device and queue so it can be driven without a window.
+pub struct Simulation {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    compute_pipeline_state: ComputePipelineState,
+    simulation_params: SimulationParams,
+    interaction_params: InteractionParams,
+    obstacles: Vec<Obstacle>,
+    rigid_bodies: Vec<RigidBody>,
+    readback: ParticleReadback,
+    initial_state: Checkpoint,
+    step: u64,
+}
+
+impl Simulation {
+    /// Number of staging buffers in the readback ring.
+    pub const READBACK_SLOTS: usize = 3;
+
+    /// Uploads `particles`, `obstacles` and `rigid_bodies` and builds the compute
+    /// pipelines.
+    ///
+    /// `particles.len()` must equal `simulation_params.particles_len`.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        particles: &[Particle],
+        obstacles: &[Obstacle],
+        rigid_bodies: &[RigidBody],
+        mut simulation_params: SimulationParams,
+    ) -> anyhow::Result<Self> {
+        ensure_particles_len(particles.len(), simulation_params.particles_len)?;
+        simulation_params.validate()?;
+
+        let rigid_bodies: Vec<RigidBody> = rigid_bodies.iter().map(RigidBody::centered).collect();
+        simulation_params.set_colliders_len(obstacles.len() as u32, rigid_bodies.len() as u32);
+        let compute_pipeline_state = ComputePipelineState::new(
+            device,
+            particles,
+            obstacles,
+            &rigid_bodies,
+            &simulation_params,
+        );
+
+        let readback = ParticleReadback::new(
+            device,
+            simulation_params.particles_len,
+            Self::READBACK_SLOTS,
+        );
+
+        let particles_len = particles.len();
+        let initial_state = Checkpoint {
+            step: 0,
+            time: 0.0,
+            simulation_params,
+            particles: particles.to_vec(),
+            densities: vec![0.0; particles_len],
+            pressures: vec![0.0; particles_len],
+            obstacles: obstacles.to_vec(),
+            rigid_bodies: rigid_bodies.clone(),
+        };
+
+        Ok(Self {
+            device: device.clone(),
+            queue: queue.clone(),
+            compute_pipeline_state,
+            simulation_params,
+            interaction_params: InteractionParams::default(),
+            obstacles: obstacles.to_vec(),
+            rigid_bodies,
+            readback,
+            initial_state,
+            step: 0,
+        })
+    }
+
+    pub fn from_scene(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene: &Scene,
+    ) -> anyhow::Result<Self> {
+        Self::new(
+            device,
+            queue,
+            &scene.spawn_particles(),
+            &scene.obstacles,
+            &scene.rigid_bodies,
+            scene.simulation_params(),
+        )
+    }
+
+    /// Rebuilds a simulation from a saved checkpoint, including its step counter.
+    pub fn from_checkpoint(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        checkpoint: &Checkpoint,
+    ) -> anyhow::Result<Self> {
+        let mut simulation = Self::new(
+            device,
+            queue,
+            &checkpoint.particles,
+            &checkpoint.obstacles,
+            &checkpoint.rigid_bodies,
+            checkpoint.simulation_params,
+        )?;
+        simulation.write_fields(&checkpoint.densities, &checkpoint.pressures)?;
+        simulation.step = checkpoint.step;
+        simulation.write_time(checkpoint.time);
+        simulation.initial_state = checkpoint.clone();
+
+        Ok(simulation)
+    }
+
+    pub fn params(&self) -> &SimulationParams {
+        &self.simulation_params
+    }
+
+    pub fn particles_len(&self) -> u32 {
+        self.simulation_params.particles_len
+    }
+
+    pub fn obstacles(&self) -> &[Obstacle] {
+        &self.obstacles
+    }
+
+    /// Rigid bodies as they were uploaded, re-centred. Their current state lives on the
+    /// GPU; see [`Simulation::read_rigid_bodies`].
+    pub fn rigid_bodies(&self) -> &[RigidBody] {
+        &self.rigid_bodies
+    }
+
+    pub fn compute_pipeline_state(&self) -> &ComputePipelineState {
+        &self.compute_pipeline_state
+    }
+
+    /// Number of steps submitted so far.
+    pub fn current_step(&self) -> u64 {
+        self.step
+    }
+
+    /// Advances the simulation by up to `dt` seconds and submits it to the queue.
+    ///
+    /// The step is split into [`SimulationParams::substeps`] solver steps, all recorded
+    /// into a single command buffer. With a fixed time step they all last `dt / substeps`;
+    /// with [`SimulationParams::with_adaptive_time_step`] the GPU may shorten each of
+    /// them, so the step covers less simulated time. [`ParticleFrame::time`] reports the
+    /// time actually simulated.
+    pub fn step(&mut self, dt: f32) {
+        self.advance(dt, 1);
+    }
+
+    /// Runs `steps` [`Simulation::step`]s of `dt` seconds, recorded into a single command
+    /// buffer and submitted once.
+    pub fn advance(&mut self, dt: f32, steps: u32) {
+        if dt != self.simulation_params.time_step {
+            self.simulation_params.time_step = dt;
+            self.write_params();
+        }
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Simulation Step Encoder"),
+            });
+
+        for _ in 0..steps * self.simulation_params.substeps {
+            self.compute_pipeline_state
+                .encode(&mut encoder, &self.simulation_params);
+        }
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.step += steps as u64;
+    }
+
+    /// Changes how many solver steps each [`Simulation::step`] is split into.
+    pub fn set_substeps(&mut self, substeps: u32) {
+        self.simulation_params.substeps = substeps.max(1);
+        self.write_params();
+    }
+
+    /// Replaces the solver parameters and resamples the boundary particles. The particle
+    /// count cannot change, and the obstacle and rigid body counts always follow the
+    /// simulation's colliders.
+    pub fn set_params(&mut self, mut simulation_params: SimulationParams) -> anyhow::Result<()> {
+        ensure_particles_len(
+            simulation_params.particles_len as usize,
+            self.simulation_params.particles_len,
+        )?;
+        simulation_params.validate()?;
+
+        simulation_params.set_colliders_len(
+            self.simulation_params.obstacles_len,
+            self.simulation_params.rigid_bodies_len,
+        );
+        self.simulation_params = simulation_params;
+        self.compute_pipeline_state.set_boundary(
+            &self.device,
+            &self.simulation_params,
+            &self.obstacles,
+            &self.rigid_bodies,
+        );
+        self.compute_pipeline_state
+            .clear_pressure_solve_state(&self.queue);
+        self.write_params();
+
+        Ok(())
+    }
+
+    pub fn interaction(&self) -> &InteractionParams {
+        &self.interaction_params
+    }
+
+    /// Replaces the cursor interaction used by the following steps.
+    pub fn set_interaction(&mut self, interaction_params: InteractionParams) {
+        if interaction_params == self.interaction_params {
+            return;
+        }
+
+        self.interaction_params = interaction_params;
+        self.queue.write_buffer(
+            &self.compute_pipeline_state.interaction_params_buffer,
+            0,
+            bytemuck::cast_slice(&[interaction_params]),
+        );
+    }
+
+    /// Overwrites the positions and velocities of every particle.
+    pub fn write_particles(&mut self, particles: &[Particle]) -> anyhow::Result<()> {
+        ensure_particles_len(particles.len(), self.simulation_params.particles_len)?;
+
+        let state = &self.compute_pipeline_state;
+        let position_x: Vec<f32> = particles.iter().map(|p| p.position_x).collect();
+        let position_y: Vec<f32> = particles.iter().map(|p| p.position_y).collect();
+        let velocity_x: Vec<f32> = particles.iter().map(|p| p.velocity_x).collect();
+        let velocity_y: Vec<f32> = particles.iter().map(|p| p.velocity_y).collect();
+
+        self.queue.write_buffer(
+            &state.position_x_buffer,
+            0,
+            bytemuck::cast_slice(&position_x),
+        );
+        self.queue.write_buffer(
+            &state.position_y_buffer,
+            0,
+            bytemuck::cast_slice(&position_y),
+        );
+        self.queue.write_buffer(
+            &state.velocity_x_buffer,
+            0,
+            bytemuck::cast_slice(&velocity_x),
+        );
+        self.queue.write_buffer(
+            &state.velocity_y_buffer,
+            0,
+            bytemuck::cast_slice(&velocity_y),
+        );
+
+        Ok(())
+    }
+
+    /// Starts copying the current particle state back to the CPU without blocking.
+    ///
+    /// Returns `false` when every readback slot is busy; take finished frames with
+    /// [`Simulation::poll_readback`] to free them.
+    pub fn request_readback(&mut self) -> bool {
+        self.readback
+            .request(
+                &self.device,
+                &self.queue,
+                &self.compute_pipeline_state,
+                self.step,
+            )
+            .is_some()
+    }
+
+    /// Polls the device without blocking and returns the oldest finished readback.
+    pub fn poll_readback(&mut self) -> anyhow::Result<Option<ParticleFrame>> {
+        self.device.poll(wgpu::PollType::Poll)?;
+        self.readback.take()
+    }
+
+    /// Blocks until the GPU is idle and returns the oldest finished readback.
+    pub fn wait_readback(&mut self) -> anyhow::Result<Option<ParticleFrame>> {
+        self.device.poll(wgpu::PollType::Wait)?;
+        self.readback.take()
+    }
+
+    /// Copies the current particle state back to the CPU, blocking until the GPU is done.
+    ///
+    /// Frames requested earlier with [`Simulation::request_readback`] stay queued.
+    pub fn read_frame(&mut self) -> anyhow::Result<ParticleFrame> {
+        let ticket = self
+            .readback
+            .request(
+                &self.device,
+                &self.queue,
+                &self.compute_pipeline_state,
+                self.step,
+            )
+            .ok_or_else(|| anyhow::anyhow!("All particle readback slots are busy"))?;
+
+        self.device.poll(wgpu::PollType::Wait)?;
+        self.readback
+            .take_ticket(ticket)?
+            .ok_or_else(|| anyhow::anyhow!("Particle readback did not complete"))
+    }
+
+    /// Blocking readback of the particle positions and velocities only.
+    pub fn read_particles(&mut self) -> anyhow::Result<Vec<Particle>> {
+        Ok(self.read_frame()?.particles)
+    }
+
+    /// Copies the current rigid body positions and velocities back to the CPU, blocking
+    /// until the GPU is done.
+    pub fn read_rigid_bodies(&self) -> anyhow::Result<Vec<RigidBody>> {
+        if self.rigid_bodies.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let records: Vec<GpuRigidBody> = self.read_buffer(
+            "Rigid Bodies",
+            &self.compute_pipeline_state.colliders.rigid_bodies_buffer,
+        )?;
+
+        Ok(self
+            .rigid_bodies
+            .iter()
+            .zip(&records)
+            .map(|(rigid_body, record)| {
+                let mut rigid_body = rigid_body.clone();
+                rigid_body.update_from(record);
+                rigid_body
+            })
+            .collect())
+    }
+
+    /// Copies the outcome of the last pressure solve back to the CPU, blocking until the
+    /// GPU is done. Stays zeroed for [`PressureSolver::Wcsph`].
+    pub fn read_pressure_solve(&self) -> anyhow::Result<PressureSolveState> {
+        let states: Vec<PressureSolveState> = self.read_buffer(
+            "Pressure Solve State",
+            &self.compute_pipeline_state.pressure_solve_state_buffer,
+        )?;
+
+        Ok(states[0])
+    }
+
+    fn read_buffer<T: bytemuck::Pod>(
+        &self,
+        label: &str,
+        buffer: &wgpu::Buffer,
+    ) -> anyhow::Result<Vec<T>> {
+        let size = buffer.size();
+        let staging_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{label} Staging Buffer")),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some(&format!("{label} Readback Encoder")),
+            });
+        encoder.copy_buffer_to_buffer(buffer, 0, &staging_buffer, 0, size);
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = staging_buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::PollType::Wait)?;
+        receiver.recv()??;
+
+        let data = slice.get_mapped_range();
+        Ok(bytemuck::cast_slice(&data).to_vec())
+    }
+
+    /// Captures the current GPU state, blocking until it has been read back.
+    pub fn checkpoint(&mut self) -> anyhow::Result<Checkpoint> {
+        let frame = self.read_frame()?;
+        let rigid_bodies = self.read_rigid_bodies()?;
+
+        Ok(Checkpoint {
+            step: frame.step,
+            time: frame.time,
+            simulation_params: self.simulation_params,
+            particles: frame.particles,
+            densities: frame.densities,
+            pressures: frame.pressures,
+            obstacles: self.obstacles.clone(),
+            rigid_bodies,
+        })
+    }
+
+    /// Replaces the whole simulation state with `checkpoint`, which must hold the same
+    /// number of particles. The checkpoint is checked in full first, so a rejected one
+    /// leaves the simulation untouched.
+    pub fn restore(&mut self, checkpoint: &Checkpoint) -> anyhow::Result<()> {
+        let particles_len = self.simulation_params.particles_len;
+        ensure_particles_len(checkpoint.particles_len() as usize, particles_len)?;
+        ensure_particles_len(checkpoint.particles.len(), particles_len)?;
+        ensure_particles_len(checkpoint.densities.len(), particles_len)?;
+        ensure_particles_len(checkpoint.pressures.len(), particles_len)?;
+        checkpoint.simulation_params.validate()?;
+
+        self.set_colliders(&checkpoint.obstacles, &checkpoint.rigid_bodies);
+        self.set_params(checkpoint.simulation_params)?;
+        self.write_particles(&checkpoint.particles)?;
+        self.write_fields(&checkpoint.densities, &checkpoint.pressures)?;
+        self.step = checkpoint.step;
+        self.write_time(checkpoint.time);
+
+        Ok(())
+    }
+
+    /// Returns to the state the simulation was created with: the initial particle
+    /// layout, parameters and step counter, or the checkpoint it was resumed from.
+    pub fn reset(&mut self) -> anyhow::Result<()> {
+        let initial_state = self.initial_state.clone();
+        self.restore(&initial_state)
+    }
+
+    fn write_fields(&mut self, densities: &[f32], pressures: &[f32]) -> anyhow::Result<()> {
+        ensure_particles_len(densities.len(), self.simulation_params.particles_len)?;
+        ensure_particles_len(pressures.len(), self.simulation_params.particles_len)?;
+
+        let state = &self.compute_pipeline_state;
+        self.queue
+            .write_buffer(&state.densities_buffer, 0, bytemuck::cast_slice(densities));
+        self.queue
+            .write_buffer(&state.pressures_buffer, 0, bytemuck::cast_slice(pressures));
+
+        Ok(())
+    }
+
+    /// Uploads new obstacles and rigid bodies, which takes effect with the next write of
+    /// the params. Always rebuilds the buffers, since rigid bodies move on the GPU. The
+    /// boundary particles of the new colliders follow with [`Simulation::set_params`].
+    fn set_colliders(&mut self, obstacles: &[Obstacle], rigid_bodies: &[RigidBody]) {
+        let rigid_bodies: Vec<RigidBody> = rigid_bodies.iter().map(RigidBody::centered).collect();
+
+        self.compute_pipeline_state
+            .set_colliders(&self.device, obstacles, &rigid_bodies);
+        self.simulation_params
+            .set_colliders_len(obstacles.len() as u32, rigid_bodies.len() as u32);
+        self.obstacles = obstacles.to_vec();
+        self.rigid_bodies = rigid_bodies;
+    }
+
+    fn write_time(&self, time: f64) {
+        self.compute_pipeline_state
+            .time_step
+            .write_time(&self.queue, time);
+    }
+
+    fn write_params(&self) {
+        self.queue.write_buffer(
+            &self.compute_pipeline_state.simulation_params_buffer,
+            0,
+            bytemuck::cast_slice(&[self.simulation_params]),
+        );
+    }
+}
+
+fn ensure_particles_len(len: usize, particles_len: u32) -> anyhow::Result<()> {
+    if len != particles_len as usize {
+        anyhow::bail!("Expected {particles_len} particles, got {len}");
+    }
+
+    Ok(())
 }
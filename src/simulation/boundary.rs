@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+
+use crate::obstacle::Obstacle;
+use crate::rigid_body::RigidBody;
+use crate::simulation::SimulationParams;
+
+/// Spacing of the boundary samples as a fraction of the smoothing radius.
+const BOUNDARY_SPACING: f32 = 0.25;
+
+/// Mirror of `BoundaryParticle` in `sph.wgsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BoundaryParticle {
+    pub position: [f32; 2],
+    /// `rest_density` times the volume the sample stands in for (Akinci et al. 2012),
+    /// so densely sampled corners do not count more than straight walls. Scaled so a
+    /// fluid lattice at rest spacing that starts half a spacing from a straight wall is
+    /// at the rest density, which keeps the fluid against the surface.
+    pub mass: f32,
+    _padding: u32,
+}
+
+/// Static samples of the domain walls and obstacle outlines, sorted by the same cell
+/// hash as the fluid particles, and the samples of every rigid body outline. `cell_ranges`
+/// has one entry per hash table slot, laid out like the `cell_ranges` of the neighbor
+/// search.
+///
+/// The static samples are empty unless [`SimulationParams::has_boundary_particles`] is
+/// set; rigid bodies are always sampled. `rigid_body_samples` are in the local frame of
+/// their body and follow the static samples on the GPU, with the range of body `b` at
+/// `cell_ranges[hash_table_size + b]`.
+#[derive(Clone, Debug, Default)]
+pub struct BoundaryParticles {
+    pub particles: Vec<BoundaryParticle>,
+    pub cell_ranges: Vec<[u32; 2]>,
+    pub rigid_body_samples: Vec<Vec<BoundaryParticle>>,
+    inputs: SampleInputs,
+}
+
+/// Everything the samples are computed from, so unchanged colliders and parameters are
+/// not sampled again.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct SampleInputs {
+    boundary_particles: bool,
+    hash_table_size: u32,
+    smoothing_radius: f32,
+    particle_mass: f32,
+    rest_density: f32,
+    domain_size: [f32; 2],
+    obstacles: Vec<Obstacle>,
+    rigid_body_shapes: Vec<Obstacle>,
+}
+
+impl SampleInputs {
+    fn new(
+        simulation_params: &SimulationParams,
+        obstacles: &[Obstacle],
+        rigid_bodies: &[RigidBody],
+    ) -> Self {
+        Self {
+            boundary_particles: simulation_params.has_boundary_particles(),
+            hash_table_size: simulation_params.hash_table_size,
+            smoothing_radius: simulation_params.smoothing_radius,
+            particle_mass: simulation_params.particle_mass,
+            rest_density: simulation_params.rest_density,
+            domain_size: simulation_params.domain_size,
+            obstacles: obstacles.to_vec(),
+            rigid_body_shapes: rigid_bodies.iter().map(RigidBody::local_shape).collect(),
+        }
+    }
+}
+
+impl BoundaryParticles {
+    /// Rigid bodies are expected to be [`RigidBody::centered`].
+    pub fn new(
+        simulation_params: &SimulationParams,
+        obstacles: &[Obstacle],
+        rigid_bodies: &[RigidBody],
+    ) -> Self {
+        let inputs = SampleInputs::new(simulation_params, obstacles, rigid_bodies);
+        let spacing = BOUNDARY_SPACING * simulation_params.smoothing_radius;
+        let rigid_body_samples = rigid_bodies
+            .iter()
+            .map(|rigid_body| {
+                sample_volumes(
+                    simulation_params,
+                    &rigid_body.local_shape().sample_outline(spacing),
+                )
+            })
+            .collect();
+
+        if !simulation_params.has_boundary_particles() {
+            // The ranges of the rigid bodies follow the hash table slots on the GPU.
+            let cell_ranges = if rigid_bodies.is_empty() {
+                Vec::new()
+            } else {
+                vec![[0; 2]; simulation_params.hash_table_size as usize]
+            };
+
+            return Self {
+                particles: Vec::new(),
+                cell_ranges,
+                rigid_body_samples,
+                inputs,
+            };
+        }
+
+        let [width, height] = simulation_params.domain_size;
+        let walls = Obstacle::Box {
+            center: [0.0; 2],
+            half_size: [0.5 * width, 0.5 * height],
+            rotation: 0.0,
+        };
+
+        let positions: Vec<[f32; 2]> = std::iter::once(&walls)
+            .chain(obstacles)
+            .flat_map(|obstacle| obstacle.sample_outline(spacing))
+            .collect();
+
+        let mut particles = sample_volumes(simulation_params, &positions);
+        particles.sort_by_key(|particle| cell_key(simulation_params, particle.position));
+
+        let mut cell_ranges = vec![[0; 2]; simulation_params.hash_table_size as usize];
+        for (s, particle) in particles.iter().enumerate() {
+            let range = &mut cell_ranges[cell_key(simulation_params, particle.position) as usize];
+            if range[1] == 0 {
+                range[0] = s as u32;
+            }
+            range[1] = s as u32 + 1;
+        }
+
+        Self {
+            particles,
+            cell_ranges,
+            rigid_body_samples,
+            inputs,
+        }
+    }
+
+    /// Whether these samples are the ones [`Self::new`] would compute for the same
+    /// arguments. Rigid bodies are compared by their local outline only, since their
+    /// samples do not depend on where the body is.
+    pub fn is_sampled_for(
+        &self,
+        simulation_params: &SimulationParams,
+        obstacles: &[Obstacle],
+        rigid_bodies: &[RigidBody],
+    ) -> bool {
+        self.inputs == SampleInputs::new(simulation_params, obstacles, rigid_bodies)
+    }
+}
+
+/// Boundary particles at `positions`, each with the volume it stands in for among the
+/// others. Neighbors are looked up in a grid of smoothing radius sized cells.
+fn sample_volumes(
+    simulation_params: &SimulationParams,
+    positions: &[[f32; 2]],
+) -> Vec<BoundaryParticle> {
+    let volume_scale = volume_scale(simulation_params);
+    let h = simulation_params.smoothing_radius;
+    let cell = |position: &[f32; 2]| {
+        (
+            (position[0] / h).floor() as i32,
+            (position[1] / h).floor() as i32,
+        )
+    };
+
+    let mut grid: HashMap<(i32, i32), Vec<[f32; 2]>> = HashMap::new();
+    for position in positions {
+        grid.entry(cell(position)).or_default().push(*position);
+    }
+
+    positions
+        .iter()
+        .map(|position| {
+            let (x, y) = cell(position);
+            let kernel_sum: f32 = (x - 1..=x + 1)
+                .flat_map(|x| (y - 1..=y + 1).map(move |y| (x, y)))
+                .filter_map(|cell| grid.get(&cell))
+                .flatten()
+                .map(|other| {
+                    density_kernel(
+                        simulation_params,
+                        position[0] - other[0],
+                        position[1] - other[1],
+                    )
+                })
+                .sum();
+
+            BoundaryParticle {
+                position: *position,
+                mass: volume_scale * simulation_params.rest_density / kernel_sum,
+                _padding: 0,
+            }
+        })
+        .collect()
+}
+
+/// Ratio of the density the first row of a fluid lattice at rest spacing is missing
+/// beyond a straight wall half a spacing away, to the density a line of samples with
+/// their plain Akinci volumes adds there. Those stand in for the whole half of the
+/// kernel support behind the wall, which is too much once it spans several particle
+/// spacings.
+fn volume_scale(simulation_params: &SimulationParams) -> f32 {
+    let fluid_spacing = simulation_params.particle_spacing();
+    let sample_spacing = BOUNDARY_SPACING * simulation_params.smoothing_radius;
+    let reach = |spacing: f32| (simulation_params.smoothing_radius / spacing).ceil() as i32;
+    let row_sum = |spacing: f32, offset: f32| -> f32 {
+        (-reach(spacing)..=reach(spacing))
+            .map(|column| density_kernel(simulation_params, column as f32 * spacing, offset))
+            .sum()
+    };
+
+    let missing_density = simulation_params.particle_mass
+        * (1..=reach(fluid_spacing))
+            .map(|row| row_sum(fluid_spacing, row as f32 * fluid_spacing))
+            .sum::<f32>();
+    let boundary_density = simulation_params.rest_density
+        * row_sum(sample_spacing, 0.5 * fluid_spacing)
+        / row_sum(sample_spacing, 0.0);
+
+    if boundary_density > 0.0 {
+        missing_density / boundary_density
+    } else {
+        1.0
+    }
+}
+
+/// `hash_cell(get_cell_coordinates(..))` from `common.wgsl`.
+fn cell_key(simulation_params: &SimulationParams, position: [f32; 2]) -> u32 {
+    let h = simulation_params.smoothing_radius;
+    let x = (position[0] / h).floor() as i32 as u32;
+    let y = (position[1] / h).floor() as i32 as u32;
+
+    x.wrapping_mul(15823).wrapping_add(y.wrapping_mul(9737333)) % simulation_params.hash_table_size
+}
+
+fn density_kernel(simulation_params: &SimulationParams, r_x: f32, r_y: f32) -> f32 {
+    let r_length_sq = r_x * r_x + r_y * r_y;
+
+    if r_length_sq > simulation_params.smoothing_radius_sq {
+        return 0.0;
+    }
+
+    let h_minus_r = simulation_params.smoothing_radius_sq - r_length_sq;
+    simulation_params.density_smoothing_function_coeff * h_minus_r * h_minus_r * h_minus_r
+}
@@ -0,0 +1,1161 @@
+use std::collections::HashMap;
+
+use crate::obstacle::{Obstacle, dot, rotate};
+use crate::rigid_body::{RigidBody, RigidBodyImpulse};
+use crate::simulation::boundary::BoundaryParticles;
+use crate::simulation::{Integrator, Particle, PressureSolver, SimulationParams};
+
+pub struct CpuSolver {
+    pub particles: Vec<Particle>,
+    pub densities: Vec<f32>,
+    pub pressures: Vec<f32>,
+    /// Simulated time in seconds.
+    pub time: f64,
+    simulation_params: SimulationParams,
+    colliders: Colliders,
+    accelerations: Vec<[f32; 2]>,
+    previous_accelerations: Vec<[f32; 2]>,
+    time_step: f32,
+    density_error: f32,
+    pressure_iterations: u32,
+    divergence_error: f32,
+    divergence_iterations: u32,
+    /// DFSPH factors of the current solver step.
+    dfsph_factors: Vec<f32>,
+    grid: HashMap<(i32, i32), Vec<usize>>,
+    boundary: BoundaryParticles,
+    boundary_grid: HashMap<(i32, i32), Vec<usize>>,
+}
+
+/// The two correction loops of the DFSPH solver.
+#[derive(Copy, Clone)]
+enum DfsphSolve {
+    Divergence,
+    Density,
+}
+
+/// Obstacles and rigid bodies, with the same fixed-point impulse accumulators as the
+/// GPU so the two solvers stay comparable.
+struct Colliders {
+    obstacles: Vec<Obstacle>,
+    rigid_bodies: Vec<RigidBody>,
+    body_shapes: Vec<Obstacle>,
+    impulses: Vec<RigidBodyImpulse>,
+}
+
+impl CpuSolver {
+    pub fn new(
+        particles: Vec<Particle>,
+        obstacles: Vec<Obstacle>,
+        rigid_bodies: Vec<RigidBody>,
+        mut simulation_params: SimulationParams,
+    ) -> Self {
+        let particles_len = particles.len();
+        let rigid_bodies: Vec<RigidBody> = rigid_bodies.iter().map(RigidBody::centered).collect();
+        simulation_params.obstacles_len = obstacles.len() as u32;
+        simulation_params.rigid_bodies_len = rigid_bodies.len() as u32;
+
+        let boundary = BoundaryParticles::new(&simulation_params, &obstacles, &rigid_bodies);
+        let mut boundary_grid: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+        for (k, boundary_particle) in boundary.particles.iter().enumerate() {
+            let cell = cell_coordinates(&simulation_params, boundary_particle.position);
+            boundary_grid.entry(cell).or_default().push(k);
+        }
+
+        Self {
+            particles,
+            densities: vec![0.0; particles_len],
+            pressures: vec![0.0; particles_len],
+            time: 0.0,
+            simulation_params,
+            colliders: Colliders {
+                obstacles,
+                body_shapes: rigid_bodies.iter().map(RigidBody::local_shape).collect(),
+                impulses: vec![RigidBodyImpulse::default(); rigid_bodies.len()],
+                rigid_bodies,
+            },
+            accelerations: vec![[0.0; 2]; particles_len],
+            previous_accelerations: vec![[0.0; 2]; particles_len],
+            time_step: simulation_params.substep_time_step(),
+            density_error: 0.0,
+            pressure_iterations: 0,
+            divergence_error: 0.0,
+            divergence_iterations: 0,
+            dfsph_factors: vec![0.0; particles_len],
+            grid: HashMap::new(),
+            boundary,
+            boundary_grid,
+        }
+    }
+
+    pub fn params(&self) -> &SimulationParams {
+        &self.simulation_params
+    }
+
+    pub fn obstacles(&self) -> &[Obstacle] {
+        &self.colliders.obstacles
+    }
+
+    pub fn rigid_bodies(&self) -> &[RigidBody] {
+        &self.colliders.rigid_bodies
+    }
+
+    /// Length of the last solver step.
+    pub fn time_step(&self) -> f32 {
+        self.time_step
+    }
+
+    /// Average relative compression left by the pressure solve of the last solver step.
+    /// Always zero with [`PressureSolver::Wcsph`].
+    pub fn density_error(&self) -> f32 {
+        self.density_error
+    }
+
+    /// Pressure corrections applied in the last solver step.
+    pub fn pressure_iterations(&self) -> u32 {
+        self.pressure_iterations
+    }
+
+    /// Average relative density change over the last solver step left by the DFSPH
+    /// divergence solve. Zero with the other solvers.
+    pub fn divergence_error(&self) -> f32 {
+        self.divergence_error
+    }
+
+    /// Divergence corrections applied in the last solver step.
+    pub fn divergence_iterations(&self) -> u32 {
+        self.divergence_iterations
+    }
+
+    pub fn step(&mut self) {
+        for _ in 0..self.simulation_params.substeps() {
+            self.time_step = self.next_time_step();
+            self.time += self.time_step as f64;
+
+            match (
+                self.simulation_params.pressure_solver(),
+                self.simulation_params.integrator(),
+            ) {
+                (PressureSolver::Pcisph, _) => {
+                    self.compute_non_pressure_accelerations();
+                    self.solve_pcisph();
+                    self.integrate();
+                }
+                (PressureSolver::Dfsph, _) => {
+                    self.build_grid();
+                    self.compute_densities();
+                    self.compute_dfsph_factors();
+                    self.solve_dfsph(DfsphSolve::Divergence);
+                    self.accelerations = self.forces_per_density(false);
+                    self.predict_velocities();
+                    self.solve_dfsph(DfsphSolve::Density);
+                    self.drift();
+                }
+                (PressureSolver::Wcsph, Integrator::SymplecticEuler) => {
+                    self.compute_accelerations();
+                    self.integrate();
+                }
+                (PressureSolver::Wcsph, Integrator::Leapfrog) => {
+                    self.compute_accelerations();
+                    self.half_kick();
+                    self.drift();
+                    self.compute_accelerations();
+                    self.half_kick();
+                }
+                (PressureSolver::Wcsph, Integrator::VelocityVerlet) => {
+                    self.compute_accelerations();
+                    self.verlet_predict();
+                    self.compute_accelerations();
+                    self.verlet_correct();
+                }
+            }
+
+            self.integrate_rigid_bodies();
+        }
+    }
+
+    /// Same reduction as `compute_time_step`, using the accelerations of the previous
+    /// solver step.
+    fn next_time_step(&self) -> f32 {
+        let max_speed = self
+            .particles
+            .iter()
+            .map(|p| (p.velocity_x * p.velocity_x + p.velocity_y * p.velocity_y).sqrt())
+            .fold(0.0, f32::max);
+        let max_acceleration = self
+            .accelerations
+            .iter()
+            .map(|[x, y]| (x * x + y * y).sqrt())
+            .fold(0.0, f32::max);
+
+        self.simulation_params
+            .adaptive_time_step(max_speed, max_acceleration)
+    }
+
+    fn build_grid(&mut self) {
+        self.grid.clear();
+
+        for (i, particle) in self.particles.iter().enumerate() {
+            let cell = self.cell_coordinates(particle);
+            self.grid.entry(cell).or_default().push(i);
+        }
+    }
+
+    fn cell_coordinates(&self, particle: &Particle) -> (i32, i32) {
+        cell_coordinates(
+            &self.simulation_params,
+            [particle.position_x, particle.position_y],
+        )
+    }
+
+    fn neighbors(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        grid_neighbors(&self.grid, self.cell_coordinates(&self.particles[i]))
+    }
+
+    /// Indices into `self.boundary.particles` around particle `i`.
+    fn boundary_neighbors(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        grid_neighbors(
+            &self.boundary_grid,
+            self.cell_coordinates(&self.particles[i]),
+        )
+    }
+
+    fn density_smoothing_function(&self, r_x: f32, r_y: f32) -> f32 {
+        let params = &self.simulation_params;
+        let r_length_sq = r_x * r_x + r_y * r_y;
+
+        if r_length_sq > params.smoothing_radius_sq {
+            return 0.0;
+        }
+
+        let h_minus_r = params.smoothing_radius_sq - r_length_sq;
+
+        params.density_smoothing_function_coeff * h_minus_r * h_minus_r * h_minus_r
+    }
+
+    fn gradient_pressure_smoothing_function(&self, r_x: f32, r_y: f32) -> [f32; 2] {
+        let params = &self.simulation_params;
+        let r_length_sq = r_x * r_x + r_y * r_y;
+
+        if r_length_sq > params.smoothing_radius_sq || r_length_sq < 1.0e-8 {
+            return [0.0, 0.0];
+        }
+
+        let r_length = r_length_sq.sqrt();
+        let h_minus_r = params.smoothing_radius - r_length;
+        let coeff =
+            params.gradient_pressure_smoothing_function_coeff * h_minus_r * h_minus_r / r_length;
+
+        [coeff * r_x, coeff * r_y]
+    }
+
+    fn laplacian_viscosity_smoothing_function(&self, r_length: f32) -> f32 {
+        let params = &self.simulation_params;
+
+        if r_length < 0.0001 || r_length > params.smoothing_radius {
+            return 0.0;
+        }
+
+        params.laplacian_viscosity_smoothing_function_coeff * (params.smoothing_radius - r_length)
+    }
+
+    fn compute_densities(&mut self) {
+        let densities = (0..self.particles.len())
+            .map(|i| {
+                let particle = &self.particles[i];
+                let density: f32 = self
+                    .neighbors(i)
+                    .map(|j| {
+                        self.density_smoothing_function(
+                            particle.position_x - self.particles[j].position_x,
+                            particle.position_y - self.particles[j].position_y,
+                        )
+                    })
+                    .sum();
+                let boundary_density: f32 = self
+                    .boundary_neighbors(i)
+                    .map(|k| {
+                        let boundary_particle = &self.boundary.particles[k];
+                        boundary_particle.mass
+                            * self.density_smoothing_function(
+                                particle.position_x - boundary_particle.position[0],
+                                particle.position_y - boundary_particle.position[1],
+                            )
+                    })
+                    .sum();
+                let position = [particle.position_x, particle.position_y];
+                let rigid_body_density: f32 = (0..self.colliders.rigid_bodies.len())
+                    .flat_map(|b| self.rigid_body_samples(b, position))
+                    .map(|(sample_position, mass)| {
+                        mass * self.density_smoothing_function(
+                            position[0] - sample_position[0],
+                            position[1] - sample_position[1],
+                        )
+                    })
+                    .sum();
+
+                self.simulation_params.particle_mass * density
+                    + boundary_density
+                    + rigid_body_density
+            })
+            .collect();
+
+        self.densities = densities;
+    }
+
+    fn compute_pressures(&mut self) {
+        let params = &self.simulation_params;
+
+        self.pressures = self
+            .densities
+            .iter()
+            .map(|density| params.pressure(*density))
+            .collect();
+    }
+
+    fn pressure_force(&self, i: usize) -> [f32; 2] {
+        let particle = &self.particles[i];
+        let mut pressure_force = [0.0, 0.0];
+
+        for j in self.neighbors(i) {
+            if self.densities[j] < 0.0001 || i == j {
+                continue;
+            }
+
+            let gradient = self.gradient_pressure_smoothing_function(
+                particle.position_x - self.particles[j].position_x,
+                particle.position_y - self.particles[j].position_y,
+            );
+            let scale = (self.pressures[i] + self.pressures[j]) / (2.0 * self.densities[j]);
+
+            pressure_force[0] -= scale * gradient[0];
+            pressure_force[1] -= scale * gradient[1];
+        }
+
+        let mass = self.simulation_params.particle_mass;
+        let mut boundary_pressure_force = [0.0, 0.0];
+
+        for k in self.boundary_neighbors(i) {
+            let boundary_particle = &self.boundary.particles[k];
+            let gradient = self.gradient_pressure_smoothing_function(
+                particle.position_x - boundary_particle.position[0],
+                particle.position_y - boundary_particle.position[1],
+            );
+            let scale = boundary_particle.mass * self.pressures[i] / self.densities[i];
+
+            boundary_pressure_force[0] -= scale * gradient[0];
+            boundary_pressure_force[1] -= scale * gradient[1];
+        }
+
+        [
+            mass * pressure_force[0] + boundary_pressure_force[0],
+            mass * pressure_force[1] + boundary_pressure_force[1],
+        ]
+    }
+
+    fn viscosity_force(&self, i: usize) -> [f32; 2] {
+        let particle = &self.particles[i];
+        let mut viscosity_force = [0.0, 0.0];
+
+        for j in self.neighbors(i) {
+            let neighbor = &self.particles[j];
+            let r_x = particle.position_x - neighbor.position_x;
+            let r_y = particle.position_y - neighbor.position_y;
+            let r_length_sq = r_x * r_x + r_y * r_y;
+
+            if i == j || self.densities[j] < 0.0001 || r_length_sq < 1.0e-8 {
+                continue;
+            }
+
+            let laplacian = self.laplacian_viscosity_smoothing_function(r_length_sq.sqrt());
+            let scale = self.simulation_params.particle_mass / self.densities[j] * laplacian;
+
+            viscosity_force[0] += scale * (neighbor.velocity_x - particle.velocity_x);
+            viscosity_force[1] += scale * (neighbor.velocity_y - particle.velocity_y);
+        }
+
+        let viscosity = self.simulation_params.viscosity;
+        [
+            viscosity * viscosity_force[0],
+            viscosity * viscosity_force[1],
+        ]
+    }
+
+    fn compute_accelerations(&mut self) {
+        self.build_grid();
+        self.compute_densities();
+        self.compute_pressures();
+        self.accelerations = self.forces_per_density(true);
+    }
+
+    /// Same as `compute_non_pressure_accelerations` in `physics.wgsl`.
+    fn compute_non_pressure_accelerations(&mut self) {
+        self.build_grid();
+        self.compute_densities();
+        self.accelerations = self.forces_per_density(false);
+    }
+
+    fn forces_per_density(&mut self, with_pressure: bool) -> Vec<[f32; 2]> {
+        let gravity_force = self.simulation_params.gravity_force;
+        let mut impulses = std::mem::take(&mut self.colliders.impulses);
+        let accelerations = (0..self.particles.len())
+            .map(|i| {
+                let pressure_force = if with_pressure {
+                    self.pressure_force(i)
+                } else {
+                    [0.0, 0.0]
+                };
+                let viscosity_force = self.viscosity_force(i);
+                let rigid_body_force = self.rigid_body_force(i, &mut impulses);
+                let density = self.densities[i];
+
+                [
+                    (gravity_force[0]
+                        + pressure_force[0]
+                        + viscosity_force[0]
+                        + rigid_body_force[0])
+                        / density,
+                    (gravity_force[1]
+                        + pressure_force[1]
+                        + viscosity_force[1]
+                        + rigid_body_force[1])
+                        / density,
+                ]
+            })
+            .collect();
+
+        self.colliders.impulses = impulses;
+        accelerations
+    }
+
+    /// Same as the kernels of `pcisph.wgsl`: predicts positions with the current
+    /// pressure accelerations and corrects the pressures from the density error there
+    /// until it is within the tolerance, then adds the pressure accelerations.
+    fn solve_pcisph(&mut self) {
+        let params = self.simulation_params;
+        let time_step = self.time_step;
+        let pressure_scale = params.pcisph_pressure_scale(time_step);
+        let rest_density = params.rest_density;
+        let particles_len = self.particles.len();
+        let mut pressure_accelerations = vec![[0.0; 2]; particles_len];
+
+        self.pressures = vec![0.0; particles_len];
+        self.pressure_iterations = 0;
+
+        for iteration in 0..=params.max_pressure_iterations() {
+            let predicted_positions: Vec<[f32; 2]> = self
+                .particles
+                .iter()
+                .zip(&self.accelerations)
+                .zip(&pressure_accelerations)
+                .map(|((particle, acceleration), pressure_acceleration)| {
+                    let velocity_x = particle.velocity_x
+                        + (acceleration[0] + pressure_acceleration[0]) * time_step;
+                    let velocity_y = particle.velocity_y
+                        + (acceleration[1] + pressure_acceleration[1]) * time_step;
+
+                    [
+                        particle.position_x + velocity_x * time_step,
+                        particle.position_y + velocity_y * time_step,
+                    ]
+                })
+                .collect();
+
+            let predicted_densities: Vec<f32> = (0..particles_len)
+                .map(|i| self.predicted_density(i, &predicted_positions))
+                .collect();
+            self.density_error = predicted_densities
+                .iter()
+                .map(|density| (density - rest_density).max(0.0))
+                .sum::<f32>()
+                / (particles_len as f32 * rest_density);
+
+            if self.density_error <= params.density_error_tolerance()
+                || iteration == params.max_pressure_iterations()
+            {
+                break;
+            }
+
+            self.pressure_iterations += 1;
+            for (pressure, density) in self.pressures.iter_mut().zip(&predicted_densities) {
+                *pressure = (*pressure + pressure_scale * (density - rest_density)).max(0.0);
+            }
+
+            pressure_accelerations = (0..particles_len)
+                .map(|i| self.pcisph_pressure_acceleration(i, &predicted_positions))
+                .collect();
+        }
+
+        for (acceleration, pressure_acceleration) in
+            self.accelerations.iter_mut().zip(&pressure_accelerations)
+        {
+            acceleration[0] += pressure_acceleration[0];
+            acceleration[1] += pressure_acceleration[1];
+        }
+    }
+
+    fn predicted_density(&self, i: usize, predicted_positions: &[[f32; 2]]) -> f32 {
+        let position = predicted_positions[i];
+        let density: f32 = self
+            .neighbors(i)
+            .map(|j| {
+                self.density_smoothing_function(
+                    position[0] - predicted_positions[j][0],
+                    position[1] - predicted_positions[j][1],
+                )
+            })
+            .sum();
+        let boundary_density: f32 = self
+            .boundary_neighbors(i)
+            .map(|k| {
+                let boundary_particle = &self.boundary.particles[k];
+                boundary_particle.mass
+                    * self.density_smoothing_function(
+                        position[0] - boundary_particle.position[0],
+                        position[1] - boundary_particle.position[1],
+                    )
+            })
+            .sum();
+
+        self.simulation_params.particle_mass * density + boundary_density
+    }
+
+    fn pcisph_pressure_acceleration(&self, i: usize, predicted_positions: &[[f32; 2]]) -> [f32; 2] {
+        let params = &self.simulation_params;
+        let position = predicted_positions[i];
+        let mut pressure_sum = [0.0, 0.0];
+        let mut boundary_sum = [0.0, 0.0];
+
+        for j in self.neighbors(i) {
+            if i == j {
+                continue;
+            }
+
+            let gradient = self.gradient_pressure_smoothing_function(
+                position[0] - predicted_positions[j][0],
+                position[1] - predicted_positions[j][1],
+            );
+            let pressure = self.pressures[i] + self.pressures[j];
+
+            pressure_sum[0] += pressure * gradient[0];
+            pressure_sum[1] += pressure * gradient[1];
+        }
+
+        for k in self.boundary_neighbors(i) {
+            let boundary_particle = &self.boundary.particles[k];
+            let gradient = self.gradient_pressure_smoothing_function(
+                position[0] - boundary_particle.position[0],
+                position[1] - boundary_particle.position[1],
+            );
+            let scale = boundary_particle.mass * self.pressures[i];
+
+            boundary_sum[0] += scale * gradient[0];
+            boundary_sum[1] += scale * gradient[1];
+        }
+
+        let rest_density_sq = params.rest_density * params.rest_density;
+        [
+            -(params.particle_mass * pressure_sum[0] + boundary_sum[0]) / rest_density_sq,
+            -(params.particle_mass * pressure_sum[1] + boundary_sum[1]) / rest_density_sq,
+        ]
+    }
+
+    /// Same as `compute_factors` in `dfsph.wgsl`.
+    fn compute_dfsph_factors(&mut self) {
+        let mass = self.simulation_params.particle_mass;
+
+        self.dfsph_factors = (0..self.particles.len())
+            .map(|i| {
+                let particle = &self.particles[i];
+                let mut gradient_sum = [0.0, 0.0];
+                let mut gradient_sq_sum = 0.0;
+
+                for j in self.neighbors(i) {
+                    if i == j {
+                        continue;
+                    }
+
+                    let gradient = self.gradient_pressure_smoothing_function(
+                        particle.position_x - self.particles[j].position_x,
+                        particle.position_y - self.particles[j].position_y,
+                    );
+                    let gradient = [mass * gradient[0], mass * gradient[1]];
+
+                    gradient_sum[0] += gradient[0];
+                    gradient_sum[1] += gradient[1];
+                    gradient_sq_sum += gradient[0] * gradient[0] + gradient[1] * gradient[1];
+                }
+
+                for k in self.boundary_neighbors(i) {
+                    let boundary_particle = &self.boundary.particles[k];
+                    let gradient = self.gradient_pressure_smoothing_function(
+                        particle.position_x - boundary_particle.position[0],
+                        particle.position_y - boundary_particle.position[1],
+                    );
+
+                    gradient_sum[0] += boundary_particle.mass * gradient[0];
+                    gradient_sum[1] += boundary_particle.mass * gradient[1];
+                }
+
+                let denominator = gradient_sum[0] * gradient_sum[0]
+                    + gradient_sum[1] * gradient_sum[1]
+                    + gradient_sq_sum;
+
+                if denominator > 1.0e-6 {
+                    self.densities[i] / denominator
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+    }
+
+    /// Same as the kernels of `dfsph.wgsl`: corrects the velocities from the relative
+    /// density error of each particle until its average is within the tolerance. The
+    /// constant-density solve accumulates its corrections into `pressures`.
+    fn solve_dfsph(&mut self, solve: DfsphSolve) {
+        let params = self.simulation_params;
+        let time_step = self.time_step;
+        let tolerance = match solve {
+            DfsphSolve::Divergence => params.divergence_error_tolerance(),
+            DfsphSolve::Density => params.density_error_tolerance(),
+        };
+        let scale = params.dfsph_relaxation * params.rest_density / (time_step * time_step);
+        let particles_len = self.particles.len();
+        let mut error = 0.0;
+        let mut iterations = 0;
+
+        if let DfsphSolve::Density = solve {
+            self.pressures.fill(0.0);
+        }
+
+        for iteration in 0..=params.max_pressure_iterations() {
+            let errors: Vec<f32> = (0..particles_len)
+                .map(|i| self.dfsph_density_error(i, solve))
+                .collect();
+            error = errors.iter().sum::<f32>() / particles_len as f32;
+
+            if error <= tolerance || iteration == params.max_pressure_iterations() {
+                break;
+            }
+
+            iterations += 1;
+            let kappas: Vec<f32> = errors
+                .iter()
+                .zip(&self.dfsph_factors)
+                .map(|(error, factor)| scale * error * factor)
+                .collect();
+            if let DfsphSolve::Density = solve {
+                for ((pressure, kappa), density) in
+                    self.pressures.iter_mut().zip(&kappas).zip(&self.densities)
+                {
+                    *pressure += kappa * density;
+                }
+            }
+
+            self.apply_dfsph_kappas(&kappas);
+        }
+
+        match solve {
+            DfsphSolve::Divergence => {
+                self.divergence_error = error;
+                self.divergence_iterations = iterations;
+            }
+            DfsphSolve::Density => {
+                self.density_error = error;
+                self.pressure_iterations = iterations;
+            }
+        }
+    }
+
+    /// Relative density change of particle `i` over the solver step, counting
+    /// compression only: from the current density for the divergence solve and from
+    /// the rest density for the constant-density solve.
+    fn dfsph_density_error(&self, i: usize, solve: DfsphSolve) -> f32 {
+        let params = &self.simulation_params;
+        let particle = &self.particles[i];
+        let mut density_change = 0.0;
+
+        for j in self.neighbors(i) {
+            let neighbor = &self.particles[j];
+            let gradient = self.gradient_pressure_smoothing_function(
+                particle.position_x - neighbor.position_x,
+                particle.position_y - neighbor.position_y,
+            );
+
+            density_change += (particle.velocity_x - neighbor.velocity_x) * gradient[0]
+                + (particle.velocity_y - neighbor.velocity_y) * gradient[1];
+        }
+        density_change *= params.particle_mass;
+
+        for k in self.boundary_neighbors(i) {
+            let boundary_particle = &self.boundary.particles[k];
+            let gradient = self.gradient_pressure_smoothing_function(
+                particle.position_x - boundary_particle.position[0],
+                particle.position_y - boundary_particle.position[1],
+            );
+
+            density_change += boundary_particle.mass
+                * (particle.velocity_x * gradient[0] + particle.velocity_y * gradient[1]);
+        }
+
+        let density_error = match solve {
+            DfsphSolve::Divergence => self.time_step * density_change,
+            DfsphSolve::Density => {
+                self.densities[i] + self.time_step * density_change - params.rest_density
+            }
+        };
+
+        density_error.max(0.0) / params.rest_density
+    }
+
+    /// Velocity change from the pressure `kappa * density` of every particle over the
+    /// solver step.
+    fn apply_dfsph_kappas(&mut self, kappas: &[f32]) {
+        let time_step = self.time_step;
+        let mass = self.simulation_params.particle_mass;
+        let velocity_changes: Vec<[f32; 2]> = (0..self.particles.len())
+            .map(|i| {
+                let particle = &self.particles[i];
+                let own_kappa = kappas[i] / self.densities[i].max(1.0e-6);
+                let mut velocity_change = [0.0, 0.0];
+
+                for j in self.neighbors(i) {
+                    if i == j {
+                        continue;
+                    }
+
+                    let gradient = self.gradient_pressure_smoothing_function(
+                        particle.position_x - self.particles[j].position_x,
+                        particle.position_y - self.particles[j].position_y,
+                    );
+                    let kappa = own_kappa + kappas[j] / self.densities[j].max(1.0e-6);
+
+                    velocity_change[0] -= mass * kappa * gradient[0];
+                    velocity_change[1] -= mass * kappa * gradient[1];
+                }
+
+                for k in self.boundary_neighbors(i) {
+                    let boundary_particle = &self.boundary.particles[k];
+                    let gradient = self.gradient_pressure_smoothing_function(
+                        particle.position_x - boundary_particle.position[0],
+                        particle.position_y - boundary_particle.position[1],
+                    );
+
+                    velocity_change[0] -= boundary_particle.mass * own_kappa * gradient[0];
+                    velocity_change[1] -= boundary_particle.mass * own_kappa * gradient[1];
+                }
+
+                velocity_change
+            })
+            .collect();
+
+        for (particle, velocity_change) in self.particles.iter_mut().zip(&velocity_changes) {
+            particle.velocity_x += time_step * velocity_change[0];
+            particle.velocity_y += time_step * velocity_change[1];
+        }
+    }
+
+    /// World-space positions and masses of the samples of rigid body `b`, or none when
+    /// `position` is out of their reach, like `rigid_body_sample_range` in `physics.wgsl`.
+    fn rigid_body_samples(
+        &self,
+        b: usize,
+        position: [f32; 2],
+    ) -> impl Iterator<Item = ([f32; 2], f32)> + '_ {
+        let body = &self.colliders.rigid_bodies[b];
+        let in_reach = self.colliders.body_shapes[b].signed_distance(body.to_local(position))
+            < self.simulation_params.smoothing_radius;
+        let samples: &[_] = if in_reach {
+            &self.boundary.rigid_body_samples[b]
+        } else {
+            &[]
+        };
+
+        samples.iter().map(|sample| {
+            let offset = rotate(sample.position, body.angle);
+            (
+                [body.position[0] + offset[0], body.position[1] + offset[1]],
+                sample.mass,
+            )
+        })
+    }
+
+    /// Same as `calculate_rigid_body_force` in `physics.wgsl`.
+    fn rigid_body_force(&self, i: usize, impulses: &mut [RigidBodyImpulse]) -> [f32; 2] {
+        let params = &self.simulation_params;
+        let particle = &self.particles[i];
+        let position = [particle.position_x, particle.position_y];
+        let density = self.densities[i];
+        let pressure_scale = self.pressures[i] / density;
+        let viscosity_scale = params.viscosity / params.rest_density;
+        let time_step = self.time_step / params.force_evaluations() as f32;
+        let mut rigid_body_force = [0.0, 0.0];
+
+        for (b, (body, impulse)) in self.colliders.rigid_bodies.iter().zip(impulses).enumerate() {
+            let mut body_force = [0.0, 0.0];
+            let mut body_torque = 0.0;
+            let mut in_reach = false;
+
+            for (sample_position, mass) in self.rigid_body_samples(b, position) {
+                let r = [
+                    position[0] - sample_position[0],
+                    position[1] - sample_position[1],
+                ];
+                let gradient = self.gradient_pressure_smoothing_function(r[0], r[1]);
+                let laplacian = self.laplacian_viscosity_smoothing_function(r[0].hypot(r[1]));
+                let body_velocity = body.velocity_at(sample_position);
+                let force = [
+                    -mass * pressure_scale * gradient[0]
+                        + viscosity_scale
+                            * mass
+                            * (body_velocity[0] - particle.velocity_x)
+                            * laplacian,
+                    -mass * pressure_scale * gradient[1]
+                        + viscosity_scale
+                            * mass
+                            * (body_velocity[1] - particle.velocity_y)
+                            * laplacian,
+                ];
+                let arm = [
+                    sample_position[0] - body.position[0],
+                    sample_position[1] - body.position[1],
+                ];
+
+                body_force[0] += force[0];
+                body_force[1] += force[1];
+                body_torque += arm[0] * force[1] - arm[1] * force[0];
+                in_reach = true;
+            }
+
+            if !in_reach {
+                continue;
+            }
+
+            rigid_body_force[0] += body_force[0];
+            rigid_body_force[1] += body_force[1];
+            add_rigid_body_impulse(
+                impulse,
+                [
+                    body_force[0] / density * time_step,
+                    body_force[1] / density * time_step,
+                ],
+                body_torque / density * time_step,
+            );
+        }
+
+        rigid_body_force
+    }
+
+    fn integrate(&mut self) {
+        let time_step = self.time_step;
+
+        for (particle, acceleration) in self.particles.iter_mut().zip(&self.accelerations) {
+            particle.velocity_x += acceleration[0] * time_step;
+            particle.velocity_y += acceleration[1] * time_step;
+            particle.position_x += particle.velocity_x * time_step;
+            particle.position_y += particle.velocity_y * time_step;
+
+            apply_boundaries(particle, &self.simulation_params, &mut self.colliders);
+        }
+    }
+
+    /// Same as `predict_velocities` in `dfsph.wgsl`.
+    fn predict_velocities(&mut self) {
+        let time_step = self.time_step;
+
+        for (particle, acceleration) in self.particles.iter_mut().zip(&self.accelerations) {
+            particle.velocity_x += acceleration[0] * time_step;
+            particle.velocity_y += acceleration[1] * time_step;
+        }
+    }
+
+    fn half_kick(&mut self) {
+        let half_time_step = 0.5 * self.time_step;
+
+        for (particle, acceleration) in self.particles.iter_mut().zip(&self.accelerations) {
+            particle.velocity_x += acceleration[0] * half_time_step;
+            particle.velocity_y += acceleration[1] * half_time_step;
+        }
+    }
+
+    fn drift(&mut self) {
+        let time_step = self.time_step;
+
+        for particle in &mut self.particles {
+            particle.position_x += particle.velocity_x * time_step;
+            particle.position_y += particle.velocity_y * time_step;
+
+            apply_boundaries(particle, &self.simulation_params, &mut self.colliders);
+        }
+    }
+
+    fn verlet_predict(&mut self) {
+        let time_step = self.time_step;
+        self.previous_accelerations.clone_from(&self.accelerations);
+
+        for (particle, acceleration) in self.particles.iter_mut().zip(&self.accelerations) {
+            particle.position_x +=
+                (particle.velocity_x + 0.5 * acceleration[0] * time_step) * time_step;
+            particle.position_y +=
+                (particle.velocity_y + 0.5 * acceleration[1] * time_step) * time_step;
+            particle.velocity_x += acceleration[0] * time_step;
+            particle.velocity_y += acceleration[1] * time_step;
+
+            apply_boundaries(particle, &self.simulation_params, &mut self.colliders);
+        }
+    }
+
+    fn verlet_correct(&mut self) {
+        let time_step = self.time_step;
+
+        for ((particle, acceleration), previous_acceleration) in self
+            .particles
+            .iter_mut()
+            .zip(&self.accelerations)
+            .zip(&self.previous_accelerations)
+        {
+            particle.velocity_x += 0.5 * (acceleration[0] - previous_acceleration[0]) * time_step;
+            particle.velocity_y += 0.5 * (acceleration[1] - previous_acceleration[1]) * time_step;
+        }
+    }
+
+    /// Same as `integrate_rigid_bodies` in `rigid_bodies.wgsl`.
+    fn integrate_rigid_bodies(&mut self) {
+        let params = &self.simulation_params;
+        let time_step = self.time_step;
+        let gravity = [
+            params.gravity_force[0] / params.rest_density,
+            params.gravity_force[1] / params.rest_density,
+        ];
+
+        for (body, impulse) in self
+            .colliders
+            .rigid_bodies
+            .iter_mut()
+            .zip(&mut self.colliders.impulses)
+        {
+            let impulse = take_impulse(impulse);
+            let inverse_mass = 1.0 / body.mass();
+            let inverse_inertia = 1.0 / body.inertia();
+
+            for ((velocity, linear), gravity) in body.velocity.iter_mut().zip(impulse).zip(gravity)
+            {
+                *velocity += params.particle_mass * linear * inverse_mass + gravity * time_step;
+            }
+            body.angular_velocity += params.particle_mass * impulse[2] * inverse_inertia;
+            body.position[0] += body.velocity[0] * time_step;
+            body.position[1] += body.velocity[1] * time_step;
+            body.angle += body.angular_velocity * time_step;
+
+            collide_with_walls(body, params, inverse_mass, inverse_inertia);
+        }
+    }
+}
+
+/// Same as `collide_with_walls` in `rigid_bodies.wgsl`.
+fn collide_with_walls(
+    body: &mut RigidBody,
+    params: &SimulationParams,
+    inverse_mass: f32,
+    inverse_inertia: f32,
+) {
+    let [width, height] = params.domain_size();
+    let half_size = [0.5 * width, 0.5 * height];
+
+    for direction in [[-1.0, 0.0], [1.0, 0.0], [0.0, -1.0], [0.0, 1.0]] {
+        let support = body.support(direction);
+        let penetration =
+            dot(support, direction) - dot(half_size, [direction[0].abs(), direction[1].abs()]);
+
+        if penetration <= 0.0 {
+            continue;
+        }
+
+        body.position[0] -= penetration * direction[0];
+        body.position[1] -= penetration * direction[1];
+
+        let contact = [
+            support[0] - penetration * direction[0],
+            support[1] - penetration * direction[1],
+        ];
+        let normal = [-direction[0], -direction[1]];
+        let normal_speed = dot(body.velocity_at(contact), normal);
+        if normal_speed >= 0.0 {
+            continue;
+        }
+
+        let arm = [contact[0] - body.position[0], contact[1] - body.position[1]];
+        let arm_cross_normal = arm[0] * normal[1] - arm[1] * normal[0];
+        let impulse = -(1.0 + params.restitution) * normal_speed
+            / (inverse_mass + inverse_inertia * arm_cross_normal * arm_cross_normal);
+
+        body.velocity[0] += impulse * inverse_mass * normal[0];
+        body.velocity[1] += impulse * inverse_mass * normal[1];
+        body.angular_velocity += impulse * inverse_inertia * arm_cross_normal;
+    }
+}
+
+fn cell_coordinates(params: &SimulationParams, position: [f32; 2]) -> (i32, i32) {
+    let h = params.smoothing_radius;
+
+    (
+        (position[0] / h).floor() as i32,
+        (position[1] / h).floor() as i32,
+    )
+}
+
+fn grid_neighbors(
+    grid: &HashMap<(i32, i32), Vec<usize>>,
+    (cell_x, cell_y): (i32, i32),
+) -> impl Iterator<Item = usize> + '_ {
+    (-1..=1)
+        .flat_map(move |offset_y| (-1..=1).map(move |offset_x| (offset_x, offset_y)))
+        .filter_map(move |(offset_x, offset_y)| grid.get(&(cell_x + offset_x, cell_y + offset_y)))
+        .flatten()
+        .copied()
+}
+
+/// Fraction of the domain size a particle is pushed back inside the wall it crossed.
+const WALL_MARGIN: f32 = 0.005;
+
+fn apply_walls(particle: &mut Particle, params: &SimulationParams) {
+    let [width, height] = params.domain_size();
+    let half_size = [0.5 * width, 0.5 * height];
+    let limit = [
+        half_size[0] - WALL_MARGIN * width,
+        half_size[1] - WALL_MARGIN * height,
+    ];
+
+    if particle.position_x < -half_size[0] || particle.position_x > half_size[0] {
+        particle.velocity_x *= -params.restitution;
+        particle.position_x = particle.position_x.clamp(-limit[0], limit[0]);
+    }
+
+    if particle.position_y < -half_size[1] || particle.position_y > half_size[1] {
+        particle.velocity_y *= -params.restitution;
+        particle.position_y = particle.position_y.clamp(-limit[1], limit[1]);
+    }
+}
+
+/// Fraction of the smoothing radius particles are kept away from obstacle surfaces.
+const OBSTACLE_MARGIN: f32 = 0.01;
+
+fn apply_obstacles(particle: &mut Particle, params: &SimulationParams, obstacles: &[Obstacle]) {
+    let margin = OBSTACLE_MARGIN * params.smoothing_radius;
+
+    for obstacle in obstacles {
+        let position = [particle.position_x, particle.position_y];
+        let distance = obstacle.signed_distance(position);
+
+        if distance >= margin {
+            continue;
+        }
+
+        let normal = obstacle.normal(position, margin);
+        particle.position_x += (margin - distance) * normal[0];
+        particle.position_y += (margin - distance) * normal[1];
+
+        let normal_speed = particle.velocity_x * normal[0] + particle.velocity_y * normal[1];
+        if normal_speed < 0.0 {
+            particle.velocity_x -= (1.0 + params.restitution) * normal_speed * normal[0];
+            particle.velocity_y -= (1.0 + params.restitution) * normal_speed * normal[1];
+        }
+    }
+}
+
+/// Scale of the fraction words of the rigid body impulse accumulators, as in
+/// `obstacles.wgsl`.
+const IMPULSE_FRACTION_SCALE: f32 = 4_294_967_296.0;
+
+/// Largest single contribution to an impulse accumulator, as in `obstacles.wgsl`.
+const MAX_IMPULSE_CONTRIBUTION: f32 = 65536.0;
+
+fn apply_rigid_bodies(
+    particle: &mut Particle,
+    params: &SimulationParams,
+    colliders: &mut Colliders,
+) {
+    let margin = OBSTACLE_MARGIN * params.smoothing_radius;
+
+    for ((body, shape), impulse) in colliders
+        .rigid_bodies
+        .iter()
+        .zip(&colliders.body_shapes)
+        .zip(&mut colliders.impulses)
+    {
+        let position = [particle.position_x, particle.position_y];
+        let local = body.to_local(position);
+        let distance = shape.signed_distance(local);
+
+        if distance >= margin {
+            continue;
+        }
+
+        let normal = rotate(shape.normal(local, margin), body.angle);
+        particle.position_x += (margin - distance) * normal[0];
+        particle.position_y += (margin - distance) * normal[1];
+
+        let position = [particle.position_x, particle.position_y];
+        let body_velocity = body.velocity_at(position);
+        let normal_speed = (particle.velocity_x - body_velocity[0]) * normal[0]
+            + (particle.velocity_y - body_velocity[1]) * normal[1];
+        if normal_speed >= 0.0 {
+            continue;
+        }
+
+        let velocity_change = [
+            -(1.0 + params.restitution) * normal_speed * normal[0],
+            -(1.0 + params.restitution) * normal_speed * normal[1],
+        ];
+        particle.velocity_x += velocity_change[0];
+        particle.velocity_y += velocity_change[1];
+
+        let arm = [
+            position[0] - body.position[0],
+            position[1] - body.position[1],
+        ];
+        add_rigid_body_impulse(
+            impulse,
+            velocity_change,
+            arm[0] * velocity_change[1] - arm[1] * velocity_change[0],
+        );
+    }
+}
+
+/// Same as `accumulate_impulse` in `physics.wgsl`.
+fn accumulate_impulse(impulse: &mut RigidBodyImpulse, k: usize, value: f32) {
+    let clamped = value.clamp(-MAX_IMPULSE_CONTRIBUTION, MAX_IMPULSE_CONTRIBUTION);
+    let whole = clamped.floor();
+    let fraction = ((clamped - whole) * IMPULSE_FRACTION_SCALE) as u32;
+    let (sum, carry) = impulse.fractions[k].overflowing_add(fraction);
+
+    impulse.fractions[k] = sum;
+    impulse.wholes[k] = impulse.wholes[k].wrapping_add(whole as i32 + carry as i32);
+}
+
+/// Same as `add_rigid_body_impulse` in `physics.wgsl`.
+fn add_rigid_body_impulse(
+    impulse: &mut RigidBodyImpulse,
+    velocity_change: [f32; 2],
+    angular_change: f32,
+) {
+    accumulate_impulse(impulse, 0, -velocity_change[0]);
+    accumulate_impulse(impulse, 1, -velocity_change[1]);
+    accumulate_impulse(impulse, 2, -angular_change);
+}
+
+/// Same as `take_impulse` in `rigid_bodies.wgsl`, for all three components.
+fn take_impulse(impulse: &mut RigidBodyImpulse) -> [f32; 3] {
+    let impulse = std::mem::take(impulse);
+
+    std::array::from_fn(|k| {
+        impulse.wholes[k] as f32 + impulse.fractions[k] as f32 / IMPULSE_FRACTION_SCALE
+    })
+}
+
+fn apply_boundaries(particle: &mut Particle, params: &SimulationParams, colliders: &mut Colliders) {
+    apply_walls(particle, params);
+    apply_obstacles(particle, params, &colliders.obstacles);
+    apply_rigid_bodies(particle, params, colliders);
+}
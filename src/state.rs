@@ -1,88 +1,125 @@
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Instant;
 
 use winit::window::Window;
 
-use crate::constants::BACKGROUND_COLOR;
-use crate::pipelines::compute::ComputePipelineState;
-use crate::pipelines::render::RenderPipelineState;
-use crate::simulation::{Particle, SimulationParams};
+use fluid_simulation::camera::Camera;
+use fluid_simulation::checkpoint::Checkpoint;
+use fluid_simulation::export::FrameExporter;
+use fluid_simulation::gpu;
+use fluid_simulation::pipelines::render::RenderPipelineState;
+use fluid_simulation::playback::Playback;
+use fluid_simulation::recording::FrameRecorder;
+use fluid_simulation::scene::Scene;
+use fluid_simulation::simulation::{InteractionParams, Simulation};
+
+use crate::cli::{RecordArgs, RunArgs};
+
+/// Zoom factor applied per mouse wheel line.
+const ZOOM_PER_LINE: f32 = 1.1;
+/// Pixels of a touchpad scroll that count as one mouse wheel line.
+const PIXELS_PER_LINE: f64 = 40.0;
 
 pub struct State {
     pub window: Arc<Window>,
     surface: wgpu::Surface<'static>,
     is_surface_configured: bool,
     render_pipeline_state: RenderPipelineState,
-    compute_pipeline_state: ComputePipelineState,
+    simulation: Simulation,
+    camera: Camera,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
-    particles: Vec<Particle>,
-    simulation_params: SimulationParams,
+    checkpoint_path: PathBuf,
+    exporter: Option<FrameExporter>,
+    record_dir: PathBuf,
+    record_args: RecordArgs,
+    recorder: Option<FrameRecorder>,
+    is_recording: bool,
+    /// Cursor in clip space; mapped into the domain when the interaction is built.
+    cursor_position: Option<[f32; 2]>,
+    is_panning: bool,
+    is_attracting: bool,
+    is_repelling: bool,
+    interaction_radius: f32,
+    interaction_strength: f32,
+    playback: Playback,
+    last_update: Option<Instant>,
 }
 
 impl State {
-    pub async fn new(window: Arc<Window>) -> anyhow::Result<State> {
+    pub async fn new(
+        window: Arc<Window>,
+        args: &RunArgs,
+        scene: &Scene,
+        resume: Option<&Checkpoint>,
+    ) -> anyhow::Result<State> {
         let size = window.inner_size();
-        let instance = wgpu::Instance::default();
+        let present_mode = args.present_mode.into();
+        let instance = gpu::create_instance(args.scene.backend.into());
         let surface = instance.create_surface(Arc::clone(&window))?;
         let adapter = Self::init_adapter(&instance, &surface).await?;
-        let (device, queue) = Self::init_device(&adapter).await?;
-        let config = surface
+        let (device, queue) = gpu::init_device(&adapter).await?;
+
+        let supported_present_modes = surface.get_capabilities(&adapter).present_modes;
+        if !matches!(
+            present_mode,
+            wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync
+        ) && !supported_present_modes.contains(&present_mode)
+        {
+            anyhow::bail!(
+                "Present mode {present_mode:?} is not supported, available modes: {supported_present_modes:?}"
+            );
+        }
+
+        let mut config = surface
             .get_default_config(&adapter, size.width, size.height)
             .unwrap();
+        config.present_mode = present_mode;
         surface.configure(&device, &config);
 
-        let render_pipeline_state = RenderPipelineState::new(&device, &config);
-
-        let simulation_params = SimulationParams::new(
-            1.0 / 60.0,        // time_step — увеличиваем шаг для большей текучести
-            10.0,              // particle_mass — уменьшаем массу для легкости
-            5000.0,            // rest_density — стандартная плотность воды
-            0.8,               // stiffness — увеличиваем жесткость для лучшего сохранения формы
-            0.2,               // smoothing_radius — увеличиваем радиус взаимодействия
-            0.1,               // restitution — уменьшаем отскок для вязкости воды
-            20.5,              // viscosity — значительно уменьшаем вязкость для текучести
-            [0.0, -100_000.0], // gravity_force — реальное ускорение свободного падения
-            10_000,            // particles_len
-        );
+        let render_pipeline_state = RenderPipelineState::new(&device, config.format);
 
-        let particles_len = simulation_params.particles_len as usize;
-        let grid_size = (particles_len as f32).sqrt().ceil() as usize;
+        let mut simulation = match resume {
+            Some(checkpoint) => Simulation::from_checkpoint(&device, &queue, checkpoint)?,
+            None => Simulation::from_scene(&device, &queue, scene)?,
+        };
 
-        let spacing = 1.0 / grid_size as f32;
-        let start = -0.5 + spacing / 2.0;
+        let camera = Camera::new(simulation.params(), size.width, size.height);
 
-        let mut particles = Vec::new();
-        for i in 0..grid_size {
-            for j in 0..grid_size {
-                if particles.len() >= particles_len {
-                    break;
-                }
+        let mut playback = Playback::default();
+        playback.set_max_steps_per_frame(args.max_steps_per_frame);
 
-                let x = start + i as f32 * spacing;
-                let y = start + j as f32 * spacing;
-
-                particles.push(Particle::new(
-                    [x, y],
-                    [rand::random::<f32>() * 0.1 - 0.05, -0.05],
-                ));
-            }
+        let mut exporter = args.export.exporter()?;
+        if let Some(exporter) = &mut exporter {
+            exporter.update(&mut simulation)?;
         }
 
-        let compute_pipeline_state =
-            ComputePipelineState::new(&device, &particles, &simulation_params);
-
         Ok(Self {
             window,
             surface,
             render_pipeline_state,
-            compute_pipeline_state,
+            simulation,
+            camera,
             device,
             queue,
             config,
             is_surface_configured: false,
-            particles,
-            simulation_params,
+            checkpoint_path: args.checkpoint.clone(),
+            exporter,
+            record_dir: args.record_dir.clone(),
+            record_args: args.record.clone(),
+            recorder: None,
+            is_recording: false,
+            cursor_position: None,
+            is_panning: false,
+            is_attracting: false,
+            is_repelling: false,
+            interaction_radius: args.interaction_radius,
+            interaction_strength: args.interaction_strength,
+            playback,
+            last_update: None,
         })
     }
 
@@ -100,18 +137,6 @@ impl State {
             .expect("No adapter found"))
     }
 
-    async fn init_device(adapter: &wgpu::Adapter) -> anyhow::Result<(wgpu::Device, wgpu::Queue)> {
-        Ok(adapter
-            .request_device(&wgpu::DeviceDescriptor {
-                label: None,
-                required_features: wgpu::Features::empty(),
-                required_limits: wgpu::Limits::default(),
-                memory_hints: wgpu::MemoryHints::MemoryUsage,
-                trace: wgpu::Trace::Off,
-            })
-            .await?)
-    }
-
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.config.width = new_size.width;
@@ -119,6 +144,8 @@ impl State {
 
             self.surface.configure(&self.device, &self.config);
             self.is_surface_configured = true;
+
+            self.camera.resize(new_size.width, new_size.height);
         }
     }
 
@@ -129,16 +156,185 @@ impl State {
         is_pressed: bool,
     ) {
         match (code, is_pressed) {
-            (winit::keyboard::KeyCode::Escape, true) => event_loop.exit(),
-            (winit::keyboard::KeyCode::Enter, true) => event_loop.exit(),
+            (winit::keyboard::KeyCode::Escape, true) => self.exit(event_loop),
+            (winit::keyboard::KeyCode::Enter, true) => self.exit(event_loop),
+            (winit::keyboard::KeyCode::Space, true) => {
+                self.playback.toggle_pause();
+                log::info!(
+                    "Simulation {}",
+                    if self.playback.is_paused() {
+                        "paused"
+                    } else {
+                        "resumed"
+                    }
+                );
+            }
+            (winit::keyboard::KeyCode::KeyN, true) => self.playback.step_once(),
+            (winit::keyboard::KeyCode::Backspace, true) => match self.simulation.reset() {
+                Ok(()) => log::info!("Simulation reset"),
+                Err(e) => log::error!("Unable to reset simulation: {e:#}"),
+            },
+            (winit::keyboard::KeyCode::BracketLeft, true) => {
+                self.playback.slower();
+                log::info!("Time scale {}x", self.playback.time_scale());
+            }
+            (winit::keyboard::KeyCode::BracketRight, true) => {
+                self.playback.faster();
+                log::info!("Time scale {}x", self.playback.time_scale());
+            }
+            (winit::keyboard::KeyCode::KeyR, true) => {
+                if let Err(e) = self.toggle_recording() {
+                    log::error!("Unable to start recording: {e:#}");
+                }
+            }
+            (winit::keyboard::KeyCode::F5, true) => {
+                if let Err(e) = self.save_checkpoint() {
+                    log::error!("Unable to save checkpoint: {e:#}");
+                }
+            }
+            (winit::keyboard::KeyCode::Home, true) => self.camera.fit(),
+            (winit::keyboard::KeyCode::F9, true) => {
+                if let Err(e) = self.load_checkpoint() {
+                    log::error!("Unable to load checkpoint: {e:#}");
+                }
+            }
             _ => {}
         }
     }
 
-    pub fn handle_mouse_moved(&mut self, _position: winit::dpi::PhysicalPosition<f64>) {
+    pub fn exit(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if let Err(e) = self.flush_export() {
+            log::error!("Unable to export frames: {e:#}");
+        }
+
+        event_loop.exit();
+    }
+
+    fn flush_export(&mut self) -> anyhow::Result<()> {
+        match &mut self.exporter {
+            Some(exporter) => exporter.flush(&mut self.simulation),
+            None => Ok(()),
+        }
+    }
+
+    fn toggle_recording(&mut self) -> anyhow::Result<()> {
+        if self.recorder.is_none() {
+            self.recorder = Some(FrameRecorder::new(
+                &self.device,
+                &self.record_dir,
+                self.record_args.record_size,
+                self.record_args.record_stride,
+            )?);
+        }
+
+        self.is_recording = !self.is_recording;
+        log::info!(
+            "Recording {} in {}",
+            if self.is_recording {
+                "started"
+            } else {
+                "stopped"
+            },
+            self.record_dir.display()
+        );
+
+        Ok(())
+    }
+
+    fn save_checkpoint(&mut self) -> anyhow::Result<()> {
+        self.flush_export()?;
+
+        let checkpoint = self.simulation.checkpoint()?;
+        checkpoint.save(&self.checkpoint_path)?;
+        log::info!(
+            "Saved step {} to {}",
+            checkpoint.step,
+            self.checkpoint_path.display()
+        );
+
+        Ok(())
+    }
+
+    fn load_checkpoint(&mut self) -> anyhow::Result<()> {
+        let checkpoint = Checkpoint::load(&self.checkpoint_path)?;
+        let domain_size = self.simulation.params().domain_size();
+        self.simulation.restore(&checkpoint)?;
+
+        if self.simulation.params().domain_size() != domain_size {
+            self.camera.set_domain(self.simulation.params());
+        }
+        log::info!(
+            "Restored step {} from {}",
+            checkpoint.step,
+            self.checkpoint_path.display()
+        );
+
+        Ok(())
+    }
+
+    pub fn handle_mouse_moved(&mut self, position: winit::dpi::PhysicalPosition<f64>) {
         let size = self.window.inner_size();
 
-        if size.width > 0 && size.height > 0 {}
+        if size.width > 0 && size.height > 0 {
+            let cursor = [
+                (2.0 * position.x / size.width as f64 - 1.0) as f32,
+                (1.0 - 2.0 * position.y / size.height as f64) as f32,
+            ];
+
+            if self.is_panning
+                && let Some(previous) = self.cursor_position
+            {
+                self.camera
+                    .pan([cursor[0] - previous[0], cursor[1] - previous[1]]);
+            }
+
+            self.cursor_position = Some(cursor);
+        }
+    }
+
+    pub fn handle_cursor_left(&mut self) {
+        self.cursor_position = None;
+    }
+
+    pub fn handle_mouse_input(&mut self, button: winit::event::MouseButton, is_pressed: bool) {
+        match button {
+            winit::event::MouseButton::Left => self.is_attracting = is_pressed,
+            winit::event::MouseButton::Right => self.is_repelling = is_pressed,
+            winit::event::MouseButton::Middle => self.is_panning = is_pressed,
+            _ => {}
+        }
+    }
+
+    /// Zooms towards the cursor, or the centre of the window when it is outside.
+    pub fn handle_mouse_wheel(&mut self, delta: winit::event::MouseScrollDelta) {
+        let lines = match delta {
+            winit::event::MouseScrollDelta::LineDelta(_, y) => y,
+            winit::event::MouseScrollDelta::PixelDelta(position) => {
+                (position.y / PIXELS_PER_LINE) as f32
+            }
+        };
+
+        self.camera.zoom_at(
+            ZOOM_PER_LINE.powf(lines),
+            self.cursor_position.unwrap_or([0.0, 0.0]),
+        );
+    }
+
+    fn interaction(&self) -> InteractionParams {
+        let direction = match (self.is_attracting, self.is_repelling) {
+            (true, false) => 1.0,
+            (false, true) => -1.0,
+            _ => 0.0,
+        };
+
+        match self.cursor_position {
+            Some(cursor) if direction != 0.0 => InteractionParams {
+                cursor: self.camera.clip_to_world(cursor),
+                radius: self.interaction_radius,
+                strength: direction * self.interaction_strength,
+            },
+            _ => InteractionParams::default(),
+        }
     }
 
     pub fn render(&mut self) -> anyhow::Result<(), wgpu::SurfaceError> {
@@ -160,47 +356,10 @@ impl State {
                 label: Some("Render Encoder"),
             });
 
-        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Render Pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: BACKGROUND_COLOR[0],
-                        g: BACKGROUND_COLOR[1],
-                        b: BACKGROUND_COLOR[2],
-                        a: 1.0,
-                    }),
-                    store: wgpu::StoreOp::Store,
-                },
-                depth_slice: None,
-            })],
-            depth_stencil_attachment: None,
-            occlusion_query_set: None,
-            timestamp_writes: None,
-        });
-
-        render_pass.set_pipeline(&self.render_pipeline_state.render_pipeline);
-
-        render_pass.set_vertex_buffer(0, self.render_pipeline_state.vertex_buffer.slice(..));
-        render_pass.set_vertex_buffer(1, self.compute_pipeline_state.position_x_buffer.slice(..));
-        render_pass.set_vertex_buffer(2, self.compute_pipeline_state.position_y_buffer.slice(..));
-        render_pass.set_vertex_buffer(3, self.compute_pipeline_state.velocity_x_buffer.slice(..));
-        render_pass.set_vertex_buffer(4, self.compute_pipeline_state.velocity_y_buffer.slice(..));
-
-        render_pass.set_index_buffer(
-            self.render_pipeline_state.index_buffer.slice(..),
-            wgpu::IndexFormat::Uint16,
-        );
-
-        render_pass.draw_indexed(
-            0..self.render_pipeline_state.num_indices,
-            0,
-            0..self.particles.len() as _,
-        );
-
-        drop(render_pass);
+        self.render_pipeline_state
+            .write_view_transform(&self.queue, &self.camera.view_transform());
+        self.render_pipeline_state
+            .draw(&self.device, &mut encoder, &view, &self.simulation);
 
         self.queue.submit(std::iter::once(encoder.finish()));
         output.present();
@@ -209,32 +368,61 @@ impl State {
     }
 
     pub fn update(&mut self) {
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
-
-        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-            label: Some("Compute Pass Densities"),
-            timestamp_writes: None,
-        });
-
-        compute_pass.set_pipeline(&self.compute_pipeline_state.compute_densities_pipeline);
-        compute_pass.set_bind_group(0, &self.compute_pipeline_state.compute_bind_group_0, &[]);
-        compute_pass.set_bind_group(1, &self.compute_pipeline_state.compute_bind_group_1, &[]);
-        compute_pass.set_bind_group(2, &self.compute_pipeline_state.compute_bind_group_2, &[]);
-
-        compute_pass.dispatch_workgroups((self.particles.len() as u32 + 63).div_ceil(64), 1, 1);
-        compute_pass.set_pipeline(&self.compute_pipeline_state.compute_pressures_pipeline);
-
-        compute_pass.dispatch_workgroups((self.particles.len() as u32 + 63).div_ceil(64), 1, 1);
+        self.simulation.set_interaction(self.interaction());
+
+        let now = Instant::now();
+        let elapsed = self
+            .last_update
+            .map_or(0.0, |last_update| (now - last_update).as_secs_f64());
+        self.last_update = Some(now);
+
+        // Steps are batched into one submission up to each step an output wants.
+        let time_step = self.simulation.params().time_step();
+        let mut remaining = self.playback.advance(elapsed, time_step);
+        while remaining > 0 {
+            let steps = self.steps_until_output(remaining);
+            self.simulation.advance(time_step, steps);
+            self.update_outputs();
+            remaining -= steps;
+        }
+    }
 
-        compute_pass.set_pipeline(&self.compute_pipeline_state.compute_new_positions_pipeline);
+    /// Number of steps, at most `max_steps`, after which the exporter or recorder wants
+    /// the simulation state.
+    fn steps_until_output(&self, max_steps: u32) -> u32 {
+        let step = self.simulation.current_step();
+        let wants = |steps: u32| {
+            let step = step + steps as u64;
+
+            self.exporter
+                .as_ref()
+                .is_some_and(|exporter| exporter.wants(step))
+                || (self.is_recording
+                    && self
+                        .recorder
+                        .as_ref()
+                        .is_some_and(|recorder| recorder.wants(step)))
+        };
+
+        (1..max_steps)
+            .find(|&steps| wants(steps))
+            .unwrap_or(max_steps)
+    }
 
-        compute_pass.dispatch_workgroups((self.particles.len() as u32 + 63).div_ceil(64), 1, 1);
-        drop(compute_pass);
+    fn update_outputs(&mut self) {
+        if let Some(exporter) = &mut self.exporter
+            && let Err(e) = exporter.update(&mut self.simulation)
+        {
+            log::error!("Unable to export frames, stopping export: {e:#}");
+            self.exporter = None;
+        }
 
-        self.queue.submit(std::iter::once(encoder.finish()));
+        if self.is_recording
+            && let Some(recorder) = &mut self.recorder
+            && let Err(e) = recorder.update(&self.device, &self.queue, &self.simulation)
+        {
+            log::error!("Unable to record frame, stopping recording: {e:#}");
+            self.is_recording = false;
+        }
     }
 }
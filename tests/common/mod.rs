@@ -0,0 +1,19 @@
+use fluid_simulation::gpu;
+
+/// Device and queue of the headless adapter, or `None` when the machine has no usable
+/// adapter, in which case GPU tests pass without running.
+pub fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = gpu::create_instance(wgpu::Backends::all());
+    let device = pollster::block_on(async {
+        let adapter = gpu::init_headless_adapter(&instance).await?;
+        gpu::init_device(&adapter).await
+    });
+
+    match device {
+        Ok(device) => Some(device),
+        Err(error) => {
+            eprintln!("Skipping GPU test: {error}");
+            None
+        }
+    }
+}
@@ -0,0 +1,225 @@
+mod common;
+
+use fluid_simulation::scene::{FluidBlock, Scene, SimulationConfig};
+use fluid_simulation::simulation::cpu::CpuSolver;
+use fluid_simulation::simulation::{Integrator, Particle, PressureSolver, Simulation};
+
+const PARTICLE_MASS: f32 = 0.5;
+const REST_DENSITY: f32 = 5000.0;
+
+/// Rest density spacing of `PARTICLE_MASS` particles.
+fn spacing() -> f32 {
+    (PARTICLE_MASS / REST_DENSITY).sqrt()
+}
+
+/// 400 particles at rest density resting on the floor of a small tank, short enough to
+/// step on the CPU in a debug build.
+fn tank_scene(pressure_solver: PressureSolver, integrator: Integrator) -> Scene {
+    let width = 20.0 * spacing();
+
+    Scene {
+        seed: Some(1),
+        simulation: SimulationConfig {
+            time_step: 1.0 / 120.0,
+            particle_mass: PARTICLE_MASS,
+            rest_density: REST_DENSITY,
+            stiffness: 400.0,
+            smoothing_radius: 4.0 * spacing(),
+            viscosity: 20.5,
+            particle_count: 400,
+            substeps: 20,
+            integrator,
+            domain_size: [0.5, 0.5],
+            // PCISPH only converges on the average error, so single particles at the
+            // walls are left with large pressures that amplify float differences.
+            boundary_particles: pressure_solver != PressureSolver::Pcisph,
+            pressure_solver,
+            ..SimulationConfig::default()
+        },
+        fluid_blocks: vec![FluidBlock {
+            min: [-0.5 * width, -0.25 + 0.5 * spacing()],
+            max: [0.5 * width, -0.25 + 0.5 * spacing() + width],
+            velocity: [0.0, 0.0],
+            velocity_jitter: [0.0, 0.0],
+        }],
+        ..Scene::default()
+    }
+}
+
+/// Largest absolute difference between two fields.
+fn max_difference(a: &[f32], b: &[f32]) -> f32 {
+    assert_eq!(a.len(), b.len());
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a - b).abs())
+        .fold(0.0, f32::max)
+}
+
+fn max_position_difference(a: &[Particle], b: &[Particle]) -> f32 {
+    assert_eq!(a.len(), b.len());
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a.position_x - b.position_x).hypot(a.position_y - b.position_y))
+        .fold(0.0, f32::max)
+}
+
+#[test]
+fn gpu_matches_cpu_reference() {
+    let Some((device, queue)) = common::device() else {
+        return;
+    };
+
+    let cases = [
+        (PressureSolver::Wcsph, Integrator::SymplecticEuler),
+        (PressureSolver::Wcsph, Integrator::Leapfrog),
+        (PressureSolver::Wcsph, Integrator::VelocityVerlet),
+        (PressureSolver::Pcisph, Integrator::SymplecticEuler),
+        (PressureSolver::Dfsph, Integrator::SymplecticEuler),
+    ];
+
+    for (pressure_solver, integrator) in cases {
+        let scene = tank_scene(pressure_solver, integrator);
+        let particles = scene.spawn_particles();
+        let params = scene.simulation_params();
+        let mut simulation = Simulation::new(
+            &device,
+            &queue,
+            &particles,
+            &scene.obstacles,
+            &scene.rigid_bodies,
+            params,
+        )
+        .unwrap();
+        let mut solver = CpuSolver::new(
+            particles,
+            scene.obstacles.clone(),
+            scene.rigid_bodies.clone(),
+            params,
+        );
+
+        // Later frames drift apart as float differences grow, so only the first two count.
+        for frame in 0..2 {
+            simulation.step(params.time_step());
+            solver.step();
+
+            let gpu_frame = simulation.read_frame().unwrap();
+            let case = format!("{pressure_solver:?}/{integrator:?} frame {frame}");
+            let max_pressure = solver
+                .pressures
+                .iter()
+                .fold(1.0f32, |max, p| max.max(p.abs()));
+
+            let density_difference = max_difference(&gpu_frame.densities, &solver.densities);
+            assert!(
+                density_difference <= 1.0e-3 * REST_DENSITY,
+                "{case}: densities differ by {density_difference}"
+            );
+
+            let pressure_difference = max_difference(&gpu_frame.pressures, &solver.pressures);
+            assert!(
+                pressure_difference <= 1.0e-3 * max_pressure,
+                "{case}: pressures differ by {pressure_difference} of {max_pressure}"
+            );
+
+            let position_difference =
+                max_position_difference(&gpu_frame.particles, &solver.particles);
+            assert!(
+                position_difference <= 1.0e-2 * spacing(),
+                "{case}: positions differ by {position_difference}"
+            );
+        }
+    }
+}
+
+/// Particles on a square lattice at rest density spacing without gravity, stepped for
+/// one millisecond per frame so the free surface does not fly apart.
+fn lattice_solver(side: usize, substeps: u32, velocity: impl Fn(usize) -> [f32; 2]) -> CpuSolver {
+    let spacing = spacing();
+    let offset = 0.5 * (side - 1) as f32 * spacing;
+    let particles: Vec<Particle> = (0..side * side)
+        .map(|i| {
+            let position = [
+                (i % side) as f32 * spacing - offset,
+                (i / side) as f32 * spacing - offset,
+            ];
+            Particle::new(position, velocity(i))
+        })
+        .collect();
+
+    let scene = Scene {
+        simulation: SimulationConfig {
+            time_step: 1.0e-3,
+            particle_mass: PARTICLE_MASS,
+            rest_density: REST_DENSITY,
+            stiffness: 400.0,
+            smoothing_radius: 4.0 * spacing,
+            gravity: [0.0, 0.0],
+            particle_count: particles.len() as u32,
+            substeps,
+            domain_size: [4.0, 4.0],
+            ..SimulationConfig::default()
+        },
+        ..Scene::default()
+    };
+
+    CpuSolver::new(particles, Vec::new(), Vec::new(), scene.simulation_params())
+}
+
+#[test]
+fn uniform_lattice_has_rest_density() {
+    let side = 21;
+    // A single substep, so the densities are those of the lattice itself.
+    let mut solver = lattice_solver(side, 1, |_| [0.0, 0.0]);
+    solver.step();
+
+    // Particles at least a smoothing radius from the edge see a full neighborhood.
+    let margin = 4;
+    for row in margin..side - margin {
+        for column in margin..side - margin {
+            let density = solver.densities[row * side + column];
+            assert!(
+                (density - REST_DENSITY).abs() <= 0.01 * REST_DENSITY,
+                "density {density} at ({column}, {row})"
+            );
+        }
+    }
+}
+
+#[test]
+fn momentum_is_conserved_without_walls() {
+    let side = 15;
+    // Deterministic velocities in [-0.5, 0.5] with a nonzero total.
+    let mut solver = lattice_solver(side, 4, |i| {
+        let hash = (i as u32).wrapping_mul(2_654_435_761);
+        [
+            (hash % 1000) as f32 / 1000.0 - 0.5,
+            ((hash >> 10) % 1000) as f32 / 1000.0 - 0.5,
+        ]
+    });
+    let momentum = |solver: &CpuSolver| {
+        solver.particles.iter().fold([0.0f64; 2], |sum, particle| {
+            [
+                sum[0] + (PARTICLE_MASS * particle.velocity_x) as f64,
+                sum[1] + (PARTICLE_MASS * particle.velocity_y) as f64,
+            ]
+        })
+    };
+    let speed_sum: f64 = solver
+        .particles
+        .iter()
+        .map(|particle| (PARTICLE_MASS * particle.velocity_x.hypot(particle.velocity_y)) as f64)
+        .sum();
+
+    let initial = momentum(&solver);
+    for _ in 0..5 {
+        solver.step();
+    }
+    let last = momentum(&solver);
+
+    for axis in 0..2 {
+        assert!(
+            (last[axis] - initial[axis]).abs() <= 1.0e-4 * speed_sum,
+            "momentum changed from {initial:?} to {last:?}"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use fluid_simulation::pipelines::compute::storage_buffer;
+use fluid_simulation::pipelines::radix_sort::RadixSort;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Sorts `keys` with `values` on the GPU and returns both read back.
+fn gpu_sort(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    keys: &[u32],
+    values: &[u32],
+    key_bits: u32,
+) -> (Vec<u32>, Vec<u32>) {
+    let size = keys.len() as u64 * 4;
+    let keys_buffer = storage_buffer(device, "Test Keys Buffer", size);
+    let values_buffer = storage_buffer(device, "Test Values Buffer", size);
+    queue.write_buffer(&keys_buffer, 0, bytemuck::cast_slice(keys));
+    queue.write_buffer(&values_buffer, 0, bytemuck::cast_slice(values));
+
+    let radix_sort = RadixSort::new(
+        device,
+        &keys_buffer,
+        &values_buffer,
+        keys.len() as u32,
+        key_bits,
+    );
+
+    let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Test Staging Buffer"),
+        size: 2 * size,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&Default::default());
+    {
+        let mut compute_pass = encoder.begin_compute_pass(&Default::default());
+        radix_sort.dispatch(&mut compute_pass);
+    }
+    encoder.copy_buffer_to_buffer(&keys_buffer, 0, &staging_buffer, 0, size);
+    encoder.copy_buffer_to_buffer(&values_buffer, 0, &staging_buffer, size, size);
+    queue.submit(std::iter::once(encoder.finish()));
+
+    staging_buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, |result| result.unwrap());
+    device.poll(wgpu::PollType::Wait).unwrap();
+
+    let data = staging_buffer.slice(..).get_mapped_range();
+    let sorted: &[u32] = bytemuck::cast_slice(&data);
+    let (keys, values) = sorted.split_at(keys.len());
+    (keys.to_vec(), values.to_vec())
+}
+
+/// Checks the GPU sort of `keys` against a stable CPU sort, with each value set to the
+/// original index so any reordering of equal keys shows up.
+fn assert_sorts_like_cpu(device: &wgpu::Device, queue: &wgpu::Queue, keys: &[u32], key_bits: u32) {
+    let values: Vec<u32> = (0..keys.len() as u32).collect();
+    let mask = u32::MAX >> (u32::BITS - key_bits);
+
+    let mut expected: Vec<(u32, u32)> = keys.iter().copied().zip(values.iter().copied()).collect();
+    expected.sort_by_key(|&(key, _)| key & mask);
+    let (expected_keys, expected_values): (Vec<u32>, Vec<u32>) = expected.into_iter().unzip();
+
+    let (sorted_keys, sorted_values) = gpu_sort(device, queue, keys, &values, key_bits);
+    assert_eq!(sorted_keys, expected_keys, "keys of length {}", keys.len());
+    assert_eq!(
+        sorted_values,
+        expected_values,
+        "values of length {}",
+        keys.len()
+    );
+}
+
+#[test]
+fn sorts_random_keys_like_cpu() {
+    let Some((device, queue)) = common::device() else {
+        return;
+    };
+    let mut rng = StdRng::seed_from_u64(1);
+
+    // One element, one short of a full block, one past it and several blocks.
+    for len in [1, 511, 513, 1000] {
+        let keys: Vec<u32> = (0..len).map(|_| rng.random()).collect();
+        assert_sorts_like_cpu(&device, &queue, &keys, u32::BITS);
+    }
+}
+
+#[test]
+fn sorts_low_key_bits_like_cpu() {
+    let Some((device, queue)) = common::device() else {
+        return;
+    };
+    let mut rng = StdRng::seed_from_u64(2);
+
+    // The high bits are ignored, so keys that differ only there must keep their order.
+    let keys: Vec<u32> = (0..1000).map(|_| rng.random()).collect();
+    assert_sorts_like_cpu(&device, &queue, &keys, 12);
+}
+
+#[test]
+fn keeps_order_of_equal_keys() {
+    let Some((device, queue)) = common::device() else {
+        return;
+    };
+
+    let keys: Vec<u32> = (0..1000).map(|i| [7, 3, 3, 7, 0][i % 5]).collect();
+    assert_sorts_like_cpu(&device, &queue, &keys, u32::BITS);
+}